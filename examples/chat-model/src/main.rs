@@ -0,0 +1,34 @@
+use anyhow::Result;
+use async_google_gemini::{
+    client::Client,
+    config::GeminiConfig,
+    types::chat::{ChatMessage, ChatRequest, ModelId},
+};
+
+use futures::StreamExt as _;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config = GeminiConfig::try_from_service_account_env()?;
+    let client = Client::new(config)?;
+
+    // e.g. `gemini-2.0-flash-001` or `claude-3-5-sonnet-v2@20241022`
+    let model: ModelId = std::env::var("MODEL")?.parse()?;
+    let model = client.chat_model(model);
+
+    let req = ChatRequest::builder()
+        .system("You are a poet")
+        .messages(vec![ChatMessage::user("Write me a poem about crabs")])
+        .max_tokens(300u32)
+        .build()?;
+
+    let response = model.chat(req.clone()).await?;
+    println!("{}", response.text());
+
+    let mut stream = model.chat_stream(req).await?;
+    while let Some(chunk) = stream.next().await {
+        println!("{:?}", chunk?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use async_google_gemini::{client::Client, config::GeminiConfig, types::claude};
+
+use futures::StreamExt as _;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config = GeminiConfig::try_from_service_account_env()?;
+    let client = Client::new(config)?;
+
+    let mut conversation = client
+        .claude()
+        .conversation(claude::ClaudeModel::Claude35SonnetV2, 300)
+        .with_system(claude::ClaudeSystemPrompt::Text(
+            "You are a poet who only writes about the sea".to_string(),
+        ));
+
+    let response = conversation.send("Write me a poem about crabs").await?;
+    dbg!(response);
+
+    let mut stream = conversation.send_stream("Now make it shorter").await?;
+    while let Some(message) = stream.next().await {
+        let response = message?;
+        println!("{:?}", response);
+    }
+
+    dbg!(conversation.messages());
+    Ok(())
+}
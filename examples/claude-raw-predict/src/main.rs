@@ -11,7 +11,7 @@ async fn main() -> Result<()> {
         .stream(false)
         .messages(vec![claude::ClaudeMessage {
             role: "user".to_string(),
-            content: "Write me a poem about crabs".to_string(),
+            content: "Write me a poem about crabs".into(),
         }])
         .build()?;
 
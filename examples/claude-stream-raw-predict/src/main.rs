@@ -13,7 +13,7 @@ async fn main() -> Result<()> {
         .stream(true)
         .messages(vec![claude::ClaudeMessage {
             role: "user".to_string(),
-            content: "Write me a poem about crabs".to_string(),
+            content: "Write me a poem about crabs".into(),
         }])
         .build()?;
 
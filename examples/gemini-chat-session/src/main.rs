@@ -0,0 +1,34 @@
+use anyhow::Result;
+use async_google_gemini::{
+    client::Client,
+    config::GeminiConfig,
+    types::{content::StartChatParams, gemini::GeminiModel},
+};
+use futures::StreamExt as _;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config = GeminiConfig::try_from_service_account_env()?;
+    let client = Client::new(config)?;
+
+    let mut chat = client
+        .gemini()
+        .start_chat(GeminiModel::Gemini15Flash002, StartChatParams::default());
+
+    let response = chat
+        .send_message(vec!["Write me a poem about crabs".into()])
+        .await?;
+    println!("{}", response.text().unwrap_or_default());
+
+    let mut stream = chat
+        .send_message_stream(vec!["Now make it rhyme".into()])
+        .await?;
+
+    while let Some(message) = stream.next().await {
+        let response = message?;
+        print!("{}", response.text().unwrap_or_default());
+    }
+
+    dbg!(chat.history());
+    Ok(())
+}
@@ -0,0 +1,368 @@
+//! Vertex AI batch prediction jobs, which answer large numbers of Gemini or Claude requests offline at a lower price.
+//!
+//! The requests are read from JSONL files in Cloud Storage or from a BigQuery table,
+//! see [gemini_jsonl] and [claude_jsonl] for the line format, and [parse_gemini_output] and
+//! [parse_claude_output] to read the results back.
+
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    client::Client,
+    error::BatchError,
+    middleware::{apply_request, notify_response, RequestContext},
+    types::{
+        batch::{
+            BatchPredictionJob, ClaudeBatchRequest, ClaudeBatchResponse,
+            CreateBatchPredictionJobRequest, GeminiBatchRequest, GeminiBatchResponse,
+            ListBatchPredictionJobsResponse,
+        },
+        chat::ModelId,
+        claude::RawPredictRequest,
+        content::{GenerateContentRequest, RequestOptions},
+    },
+    usage::Tags,
+};
+
+/// How [Batch::wait] polls a job, backing off from the initial to the max interval.
+#[derive(Clone, Debug)]
+pub struct PollOptions {
+    pub initial_interval: Duration,
+    pub max_interval: Duration,
+    pub multiplier: f64,
+    /// Gives up waiting after this long, the job keeps running.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for PollOptions {
+    fn default() -> Self {
+        Self {
+            initial_interval: Duration::from_secs(10),
+            max_interval: Duration::from_secs(300),
+            multiplier: 1.5,
+            timeout: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Batch {
+    client: Client,
+    location: Option<String>,
+    options: RequestOptions,
+}
+
+impl Batch {
+    pub fn new(client: Client) -> Self {
+        Self {
+            options: client.options().clone(),
+            client,
+            location: None,
+        }
+    }
+
+    /// Applies the options, such as timeouts and custom headers, to calls made through this handle instead of the client defaults
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Manages jobs in the given region instead of the location of the [crate::config::GeminiConfig]
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    /// The region jobs are managed in
+    pub fn location(&self) -> &str {
+        self.location
+            .as_deref()
+            .unwrap_or(self.client.config().location())
+    }
+
+    /// The model resource a job runs against, e.g. `publishers/anthropic/models/claude-3-5-sonnet-v2@20241022`
+    pub fn model_resource(model: &ModelId) -> String {
+        match model {
+            ModelId::Gemini(model) => match model.resource_name() {
+                Some(resource_name) => resource_name.to_string(),
+                None => format!("publishers/google/models/{}", model),
+            },
+            ModelId::Claude(model) => format!("publishers/anthropic/models/{}", model),
+        }
+    }
+
+    fn jobs_url(&self) -> String {
+        format!(
+            "{}/v1/projects/{}/locations/{}/batchPredictionJobs",
+            self.client.endpoint(self.location()),
+            self.client.config().project_id(),
+            self.location(),
+        )
+    }
+
+    /// The url of a job from its id or full resource name
+    fn job_url(&self, job: &str) -> String {
+        match job.contains('/') {
+            true => format!("{}/v1/{}", self.client.endpoint(self.location()), job),
+            false => format!("{}/{}", self.jobs_url(), job),
+        }
+    }
+
+    /// Sends a request through the middleware of the client, `method` names the api method, e.g. `batchPredictionJobs.get`
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        http_method: reqwest::Method,
+        url: String,
+        query: &[(&str, String)],
+        body: Option<&impl Serialize>,
+    ) -> Result<T, BatchError> {
+        let token = self.client.config().token().await.map_err(|e| {
+            tracing::error!(error=?e, "failed to get authentication token");
+            BatchError::AuthenticationError(e.to_string())
+        })?;
+
+        let body = body.map(serde_json::to_string).transpose().map_err(|e| {
+            tracing::error!(error=?e, "failed to serialize request");
+            BatchError::ParseError(format!("failed to serialize request: {}", e))
+        })?;
+
+        let ctx = RequestContext {
+            model: None,
+            method: method.to_string(),
+            url,
+            region: self.location().to_string(),
+            stream: false,
+            body: body.clone().unwrap_or_default(),
+            tags: Tags::default(),
+        };
+
+        let mut request = self
+            .client
+            .http_client()
+            .request(http_method, &ctx.url)
+            .query(query)
+            .header("Authorization", format!("Bearer {}", token));
+        if let Some(body) = body {
+            request = request
+                .header("content-type", "application/json; charset=utf-8")
+                .body(body);
+        }
+        let request = self.options.apply(request);
+
+        let request = apply_request(self.client.middleware(), &ctx, request);
+        let res = self.client.execute(&ctx, request).await.map_err(|e| {
+            tracing::error!(error=?e, "failed to send request to google vertex");
+            BatchError::from(e)
+        })?;
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.text().await?;
+
+        notify_response(
+            self.client.middleware(),
+            &ctx,
+            status,
+            &headers,
+            Some(&body),
+        );
+
+        if !status.is_success() {
+            let error = BatchError::from_response(status.as_u16(), &body);
+            tracing::error!(error=?error, status=%status, url=%ctx.url, "batch request failed");
+            return Err(error);
+        }
+
+        // cancel and delete answer with an empty object or an operation, which are not needed
+        let body = match body.trim().is_empty() {
+            true => "null",
+            false => body.as_str(),
+        };
+        serde_json::from_str::<T>(body).map_err(|e| {
+            tracing::error!(error=?e, "failed to parse response from google vertex");
+            BatchError::ParseError(format!("failed to parse response: {}", e))
+        })
+    }
+
+    /// Creates a job, which starts once Vertex AI has capacity for it
+    pub async fn create(
+        &self,
+        request: &CreateBatchPredictionJobRequest,
+    ) -> Result<BatchPredictionJob, BatchError> {
+        self.send(
+            "batchPredictionJobs.create",
+            reqwest::Method::POST,
+            self.jobs_url(),
+            &[],
+            Some(request),
+        )
+        .await
+    }
+
+    /// Gets a job by its id or full resource name
+    pub async fn get(&self, job: &str) -> Result<BatchPredictionJob, BatchError> {
+        self.send(
+            "batchPredictionJobs.get",
+            reqwest::Method::GET,
+            self.job_url(job),
+            &[],
+            None::<&()>,
+        )
+        .await
+    }
+
+    /// Lists one page of the jobs in the region, newest first
+    pub async fn list(
+        &self,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> Result<ListBatchPredictionJobsResponse, BatchError> {
+        let query = [
+            page_size.map(|size| ("pageSize", size.to_string())),
+            page_token.map(|token| ("pageToken", token.to_string())),
+        ];
+        let query = query.into_iter().flatten().collect::<Vec<_>>();
+        self.send(
+            "batchPredictionJobs.list",
+            reqwest::Method::GET,
+            self.jobs_url(),
+            &query,
+            None::<&()>,
+        )
+        .await
+    }
+
+    /// Lists all jobs in the region, following the page tokens
+    pub async fn list_all(&self) -> Result<Vec<BatchPredictionJob>, BatchError> {
+        let mut jobs = Vec::new();
+        let mut page_token = None;
+        loop {
+            let page = self.list(None, page_token.as_deref()).await?;
+            jobs.extend(page.batch_prediction_jobs);
+            match page.next_page_token.filter(|token| !token.is_empty()) {
+                Some(token) => page_token = Some(token),
+                None => return Ok(jobs),
+            }
+        }
+    }
+
+    /// Requests the cancellation of a job, which happens asynchronously, use [Batch::wait] to wait for it
+    pub async fn cancel(&self, job: &str) -> Result<(), BatchError> {
+        let url = format!("{}:cancel", self.job_url(job));
+        self.send::<Option<serde_json::Value>>(
+            "batchPredictionJobs.cancel",
+            reqwest::Method::POST,
+            url,
+            &[],
+            Some(&serde_json::json!({})),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes a job, running jobs have to be cancelled first
+    pub async fn delete(&self, job: &str) -> Result<(), BatchError> {
+        self.send::<Option<serde_json::Value>>(
+            "batchPredictionJobs.delete",
+            reqwest::Method::DELETE,
+            self.job_url(job),
+            &[],
+            None::<&()>,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Polls a job until it reaches a terminal state and returns it, logging its progress in between
+    pub async fn wait(
+        &self,
+        job: &str,
+        options: PollOptions,
+    ) -> Result<BatchPredictionJob, BatchError> {
+        let started = tokio::time::Instant::now();
+        let mut interval = options.initial_interval;
+
+        loop {
+            let job = self.get(job).await?;
+            if job.state.is_terminal() {
+                tracing::info!(job=%job.name, state=?job.state, output=?job.output_location(), "batch job finished");
+                return Ok(job);
+            }
+
+            let stats = job.completion_stats.clone().unwrap_or_default();
+            tracing::info!(
+                job=%job.name,
+                state=?job.state,
+                successful=stats.successful_count,
+                failed=stats.failed_count,
+                "batch job running"
+            );
+
+            if let Some(timeout) = options.timeout {
+                let remaining = timeout.saturating_sub(started.elapsed());
+                if remaining.is_zero() {
+                    return Err(BatchError::Timeout(job.name));
+                }
+                interval = interval.min(remaining);
+            }
+
+            tokio::time::sleep(interval).await;
+            interval = interval
+                .mul_f64(options.multiplier.max(1.0))
+                .min(options.max_interval);
+        }
+    }
+}
+
+/// Serializes requests into the JSONL input of a Gemini batch job, one `{"request": ...}` per line
+pub fn gemini_jsonl<'r>(
+    requests: impl IntoIterator<Item = &'r GenerateContentRequest>,
+) -> Result<String, serde_json::Error> {
+    requests
+        .into_iter()
+        .map(|request| {
+            serde_json::to_string(&GeminiBatchRequest {
+                request: request.clone(),
+            })
+        })
+        .map(|line| line.map(|line| line + "\n"))
+        .collect()
+}
+
+/// Serializes requests into the JSONL input of a Claude batch job, one `{"custom_id": ..., "request": ...}` per line.
+/// Batch requests can't be streamed, so `stream` is turned off.
+pub fn claude_jsonl<'r>(
+    requests: impl IntoIterator<Item = (&'r str, &'r RawPredictRequest)>,
+) -> Result<String, serde_json::Error> {
+    requests
+        .into_iter()
+        .map(|(custom_id, request)| {
+            let mut request = request.clone();
+            request.stream = false;
+            serde_json::to_string(&ClaudeBatchRequest {
+                custom_id: custom_id.to_string(),
+                request,
+            })
+        })
+        .map(|line| line.map(|line| line + "\n"))
+        .collect()
+}
+
+/// Parses the JSONL output of a Gemini batch job
+pub fn parse_gemini_output(jsonl: &str) -> Result<Vec<GeminiBatchResponse>, serde_json::Error> {
+    parse_jsonl(jsonl)
+}
+
+/// Parses the JSONL output of a Claude batch job
+pub fn parse_claude_output(jsonl: &str) -> Result<Vec<ClaudeBatchResponse>, serde_json::Error> {
+    parse_jsonl(jsonl)
+}
+
+fn parse_jsonl<T: DeserializeOwned>(jsonl: &str) -> Result<Vec<T>, serde_json::Error> {
+    jsonl
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
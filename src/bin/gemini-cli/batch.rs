@@ -0,0 +1,233 @@
+//! Batch mode, which answers a JSONL file of requests.
+//!
+//! Every input line is a JSON object with an optional `id` (or `request_id`), `model` and `system`, and either
+//! a `prompt` (or a `title` and `body`) or a full chat `request`. Every answered line is appended to the output
+//! as `{"id", "model", "response"}`, or `{"id", "model", "error"}` if it failed. Requests which already have a
+//! response in the output are skipped, so a failed or interrupted run is resumed by running it again.
+
+use std::{
+    collections::HashSet,
+    io::{BufRead, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use async_google_gemini::{
+    client::Client,
+    error::ChatError,
+    types::chat::{ChatMessage, ChatRequest, ChatResponse, ModelId},
+};
+use clap::Args;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// JSONL file of requests
+    input: PathBuf,
+
+    /// JSONL file the responses are appended to
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Model of requests which don't name one
+    #[arg(short, long, default_value = "gemini-1.5-flash-002")]
+    model: ModelId,
+
+    /// System prompt of requests which don't set one
+    #[arg(short, long)]
+    system: Option<String>,
+
+    /// Requests in flight at the same time
+    #[arg(short, long, default_value_t = 4)]
+    concurrency: usize,
+
+    /// Retries of requests failing with a retryable error
+    #[arg(long, default_value_t = 2)]
+    retries: u32,
+
+    #[arg(long)]
+    max_tokens: Option<u32>,
+
+    #[arg(long)]
+    temperature: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct BatchInput {
+    #[serde(default, alias = "request_id")]
+    id: Option<String>,
+    #[serde(default)]
+    model: Option<ModelId>,
+    #[serde(default)]
+    system: Option<String>,
+    #[serde(default, alias = "body")]
+    prompt: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    request: Option<ChatRequest>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BatchOutput {
+    id: String,
+    model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<ChatResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub async fn run(client: &Client, args: BatchArgs, location: Option<&str>) -> Result<()> {
+    let done = answered(&args.output)?;
+    let input = std::fs::File::open(&args.input)
+        .with_context(|| format!("failed to open {}", args.input.display()))?;
+
+    let mut pending = Vec::new();
+    for (number, line) in std::io::BufReader::new(input).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let input = serde_json::from_str::<BatchInput>(&line)
+            .with_context(|| format!("invalid request on line {}", number + 1))?;
+        let id = input
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("line-{}", number + 1));
+        if !done.contains(&id) {
+            pending.push((id, input));
+        }
+    }
+
+    eprintln!(
+        "{} requests to answer, {} already answered",
+        pending.len(),
+        done.len()
+    );
+
+    let mut output = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&args.output)
+        .with_context(|| format!("failed to open {}", args.output.display()))?;
+
+    let total = pending.len();
+    let mut results = futures::stream::iter(pending)
+        .map(|(id, input)| answer(client, &args, location, id, input))
+        .buffer_unordered(args.concurrency.max(1));
+
+    let (mut answered, mut failed) = (0, 0);
+    while let Some(result) = results.next().await {
+        match &result.error {
+            Some(error) => {
+                failed += 1;
+                eprintln!("{} failed: {}", result.id, error);
+            }
+            None => answered += 1,
+        }
+
+        // written as soon as it is done, so an interrupted run keeps its progress
+        writeln!(output, "{}", serde_json::to_string(&result)?)?;
+        output.flush()?;
+        eprintln!("{}/{} done, {} failed", answered + failed, total, failed);
+    }
+
+    if failed > 0 {
+        bail!(
+            "{} of {} requests failed, run the batch again to retry them",
+            failed,
+            total
+        );
+    }
+
+    Ok(())
+}
+
+/// The ids which already have a response in the output file.
+fn answered(output: &PathBuf) -> Result<HashSet<String>> {
+    let file = match std::fs::File::open(output) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut answered = HashSet::new();
+    for line in std::io::BufReader::new(file).lines() {
+        // a run killed while writing may leave a partial last line
+        if let Ok(output) = serde_json::from_str::<BatchOutput>(&line?) {
+            if output.response.is_some() {
+                answered.insert(output.id);
+            }
+        }
+    }
+
+    Ok(answered)
+}
+
+async fn answer(
+    client: &Client,
+    args: &BatchArgs,
+    location: Option<&str>,
+    id: String,
+    input: BatchInput,
+) -> BatchOutput {
+    let model_id = input.model.clone().unwrap_or_else(|| args.model.clone());
+    let model = client.chat_model_in(model_id.clone(), location);
+
+    let result = match request(args, input) {
+        Ok(request) => {
+            let mut attempt = 0;
+            loop {
+                match model.chat(request.clone()).await {
+                    Err(e) if e.is_retryable() && attempt < args.retries => {
+                        attempt += 1;
+                        tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                    }
+                    result => break result,
+                }
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    BatchOutput {
+        id,
+        model: model_id.to_string(),
+        error: result.as_ref().err().map(ToString::to_string),
+        response: result.ok(),
+    }
+}
+
+fn request(args: &BatchArgs, input: BatchInput) -> Result<ChatRequest, ChatError> {
+    let mut request = match (input.request, input.prompt) {
+        (Some(request), _) => request,
+        (None, Some(prompt)) => {
+            let prompt = match input.title {
+                Some(title) => format!("{}\n\n{}", title, prompt),
+                None => prompt,
+            };
+            ChatRequest {
+                messages: vec![ChatMessage::user(prompt)],
+                ..Default::default()
+            }
+        }
+        (None, None) => {
+            return Err(ChatError::Conversion(
+                "request has neither a prompt nor a request".to_string(),
+            ))
+        }
+    };
+
+    request.system = request
+        .system
+        .or(input.system)
+        .or_else(|| args.system.clone());
+    request.max_tokens = request.max_tokens.or(args.max_tokens);
+    request.temperature = request.temperature.or(args.temperature);
+
+    Ok(request)
+}
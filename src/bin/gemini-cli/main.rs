@@ -0,0 +1,260 @@
+//! Command line client for the Gemini and Claude models on Vertex AI.
+//!
+//! - `gemini-cli prompt` sends a single prompt and streams the reply to stdout
+//! - `gemini-cli chat` starts an interactive conversation
+//! - `gemini-cli batch` answers a JSONL file of prompts, see [batch]
+
+mod batch;
+mod repl;
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_google_gemini::{
+    chat::ChatModel,
+    client::Client,
+    config::{ClaudeBackend, GeminiConfig},
+    types::{
+        chat::{ChatMessage, ChatPart, ChatRequest, ChatResponse, ChatRole, ModelId},
+        content::Part,
+    },
+};
+use clap::{Args, Parser, Subcommand};
+use futures::StreamExt;
+
+#[derive(Parser)]
+#[command(
+    name = "gemini-cli",
+    version,
+    about = "Gemini and Claude models on Vertex AI"
+)]
+struct Cli {
+    /// Service account json file, defaults to the GCP_SERVICE_ACCOUNT, GCP_TOKEN and GCP_PROJECT_ID, or GEMINI_API_KEY variables
+    #[arg(long, global = true, env = "GCP_SERVICE_ACCOUNT_FILE")]
+    credentials: Option<PathBuf>,
+
+    /// Region requests are sent to, defaults to the configured location, or us-east5 for Claude
+    #[arg(long, global = true)]
+    location: Option<String>,
+
+    /// The api Claude models are sent to, `anthropic` needs the ANTHROPIC_API_KEY variable
+    #[arg(long, global = true, env = "CLAUDE_BACKEND", default_value = "vertex")]
+    claude_backend: ClaudeBackend,
+
+    /// Send requests to this url instead of the Vertex AI endpoint, e.g. a proxy
+    #[arg(long, global = true, env = "GEMINI_BASE_URL")]
+    base_url: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send a single prompt and print the reply
+    Prompt {
+        /// The prompt, read from stdin if missing
+        prompt: Vec<String>,
+
+        #[command(flatten)]
+        options: ChatOptions,
+
+        /// Print the whole reply at once instead of streaming it
+        #[arg(long)]
+        no_stream: bool,
+
+        /// Print the response as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Start an interactive conversation, type /help for commands
+    Chat {
+        #[command(flatten)]
+        options: ChatOptions,
+    },
+    /// Answer every request of a JSONL file, appending the responses to another JSONL file
+    Batch(batch::BatchArgs),
+}
+
+/// Options shared by the prompt and chat commands.
+#[derive(Args, Clone)]
+pub struct ChatOptions {
+    /// Any Gemini or Claude model id, e.g. gemini-1.5-pro-002 or claude-3-5-sonnet-v2@20241022
+    #[arg(short, long, default_value = "gemini-1.5-flash-002")]
+    pub model: ModelId,
+
+    /// System prompt
+    #[arg(short, long, conflicts_with = "system_file")]
+    pub system: Option<String>,
+
+    /// File containing the system prompt
+    #[arg(long)]
+    pub system_file: Option<PathBuf>,
+
+    /// Files attached to the first message, e.g. images, audio, video or pdfs
+    #[arg(short, long = "attach")]
+    pub attachments: Vec<PathBuf>,
+
+    #[arg(long)]
+    pub max_tokens: Option<u32>,
+
+    #[arg(long)]
+    pub temperature: Option<f32>,
+}
+
+impl ChatOptions {
+    pub fn system_prompt(&self) -> Result<Option<String>> {
+        match &self.system_file {
+            Some(path) => Ok(Some(std::fs::read_to_string(path).with_context(|| {
+                format!("failed to read system prompt {}", path.display())
+            })?)),
+            None => Ok(self.system.clone()),
+        }
+    }
+
+    pub fn request(&self, messages: Vec<ChatMessage>) -> Result<ChatRequest> {
+        Ok(ChatRequest {
+            system: self.system_prompt()?,
+            messages,
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            ..Default::default()
+        })
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut builder = Client::builder(config(cli.credentials.as_deref(), cli.claude_backend)?);
+    if let Some(base_url) = cli.base_url {
+        builder = builder.base_url(base_url);
+    }
+    let client = builder.build()?;
+    let location = cli.location;
+
+    match cli.command {
+        Command::Prompt {
+            prompt,
+            options,
+            no_stream,
+            json,
+        } => {
+            let prompt = match prompt.is_empty() {
+                true => std::io::read_to_string(std::io::stdin())?,
+                false => prompt.join(" "),
+            };
+            let mut parts = attachments(&options.attachments).await?;
+            parts.push(ChatPart::text(prompt));
+
+            let model = client.chat_model_in(options.model.clone(), location.as_deref());
+            let request = options.request(vec![ChatMessage {
+                role: ChatRole::User,
+                parts,
+            }])?;
+
+            match no_stream || json {
+                true => {
+                    let response = model.chat(request).await?;
+                    match json {
+                        true => println!("{}", serde_json::to_string_pretty(&response)?),
+                        false => println!("{}", response.text()),
+                    }
+                }
+                false => {
+                    stream_reply(model.as_ref(), request).await?;
+                }
+            }
+        }
+        Command::Chat { options } => repl::run(&client, options, location.as_deref()).await?,
+        Command::Batch(args) => batch::run(&client, args, location.as_deref()).await?,
+    }
+
+    Ok(())
+}
+
+fn config(credentials: Option<&Path>, claude_backend: ClaudeBackend) -> Result<GeminiConfig> {
+    let config = match credentials {
+        Some(path) => GeminiConfig::try_from_service_account_file(path.display().to_string())?,
+        None => GeminiConfig::try_from_service_account_env()
+            .or_else(|_| GeminiConfig::try_from_env_vars())
+            .or_else(|_| GeminiConfig::try_from_api_key_env())
+            .map_err(|_| {
+                anyhow!("no credentials, pass --credentials or set GCP_SERVICE_ACCOUNT, GCP_TOKEN and GCP_PROJECT_ID, or GEMINI_API_KEY")
+            })?,
+    };
+
+    let config = config
+        .with_anthropic_api_key_env()
+        .with_claude_backend(claude_backend);
+    if claude_backend == ClaudeBackend::Anthropic && config.anthropic_api_key().is_none() {
+        return Err(anyhow!(
+            "the anthropic claude backend needs the ANTHROPIC_API_KEY variable"
+        ));
+    }
+    Ok(config)
+}
+
+/// Prints the reply as it is streamed and returns it once complete.
+pub async fn stream_reply(model: &dyn ChatModel, request: ChatRequest) -> Result<ChatResponse> {
+    let mut stream = model.chat_stream(request).await?;
+    let mut stdout = std::io::stdout();
+    let mut response = ChatResponse {
+        message: ChatMessage {
+            role: ChatRole::Assistant,
+            parts: Vec::new(),
+        },
+        finish_reason: None,
+        usage: None,
+    };
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        for part in chunk.parts {
+            match (&part, response.message.parts.last_mut()) {
+                (ChatPart::Text { text }, Some(ChatPart::Text { text: previous })) => {
+                    previous.push_str(text);
+                }
+                _ => response.message.parts.push(part.clone()),
+            }
+
+            match &part {
+                ChatPart::Text { text } => write!(stdout, "{}", text)?,
+                ChatPart::ToolCall {
+                    name, arguments, ..
+                } => writeln!(stdout, "\n[tool call {} {}]", name, arguments)?,
+                _ => {}
+            }
+            stdout.flush()?;
+        }
+
+        if chunk.finish_reason.is_some() {
+            response.finish_reason = chunk.finish_reason;
+        }
+        if chunk.usage.is_some() {
+            response.usage = chunk.usage;
+        }
+    }
+    writeln!(stdout)?;
+
+    Ok(response)
+}
+
+/// Reads files into inline data parts, see [Part::from_path] for the detected types and size limit.
+pub async fn attachments(paths: &[PathBuf]) -> Result<Vec<ChatPart>> {
+    let mut parts = Vec::with_capacity(paths.len());
+    for path in paths {
+        parts.push(attachment(path).await?);
+    }
+    Ok(parts)
+}
+
+pub async fn attachment(path: &Path) -> Result<ChatPart> {
+    let part = Part::from_path(path)
+        .await
+        .with_context(|| format!("failed to attach {}", path.display()))?;
+    Ok(part.into())
+}
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_google_gemini::{
+    client::Client,
+    types::chat::{ChatMessage, ChatPart, ChatRole, ModelId},
+};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::{attachment, attachments, stream_reply, ChatOptions};
+
+const HELP: &str = "\
+/attach <file>   attach a file to the next message
+/system <text>   replace the system prompt
+/model <id>      switch to another model, keeping the conversation
+/reset           forget the conversation
+/history         print the conversation
+/exit            quit, as does ctrl-d";
+
+/// Runs the interactive conversation until the user quits.
+pub async fn run(client: &Client, mut options: ChatOptions, location: Option<&str>) -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let history_file =
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".gemini-cli-history"));
+    if let Some(history_file) = &history_file {
+        // the file does not exist on the first run
+        let _ = editor.load_history(history_file);
+    }
+
+    let mut model = client.chat_model_in(options.model.clone(), location);
+    let mut messages: Vec<ChatMessage> = Vec::new();
+    let mut pending = attachments(&options.attachments).await?;
+
+    println!("chatting with {}, type /help for commands", options.model);
+
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        if let Some(command) = line.strip_prefix('/') {
+            let (command, argument) = command.split_once(' ').unwrap_or((command, ""));
+            let argument = argument.trim();
+
+            match command {
+                "exit" | "quit" => break,
+                "help" => println!("{}", HELP),
+                "reset" => {
+                    messages.clear();
+                    println!("conversation cleared");
+                }
+                "history" => {
+                    for message in &messages {
+                        let role = match message.role {
+                            ChatRole::User => "user",
+                            ChatRole::Assistant => "model",
+                        };
+                        println!("{}: {}", role, message.text());
+                    }
+                }
+                "attach" => match attachment(argument.as_ref()).await {
+                    Ok(part) => {
+                        pending.push(part);
+                        println!("attached {}", argument);
+                    }
+                    Err(e) => eprintln!("{:#}", e),
+                },
+                "system" => {
+                    options.system = Some(argument.to_string()).filter(|s| !s.is_empty());
+                    options.system_file = None;
+                }
+                "model" => match argument.parse::<ModelId>() {
+                    Ok(model_id) => {
+                        model = client.chat_model_in(model_id.clone(), location);
+                        options.model = model_id;
+                        println!("switched to {}", options.model);
+                    }
+                    Err(e) => eprintln!("{}", e),
+                },
+                _ => eprintln!("unknown command /{}, type /help for commands", command),
+            }
+            continue;
+        }
+
+        let mut parts = std::mem::take(&mut pending);
+        parts.push(ChatPart::text(line));
+        messages.push(ChatMessage {
+            role: ChatRole::User,
+            parts,
+        });
+
+        match stream_reply(model.as_ref(), options.request(messages.clone())?).await {
+            Ok(response) => messages.push(response.message),
+            Err(e) => {
+                // drop the unanswered message so it can be sent again, keeping its attachments
+                if let Some(mut message) = messages.pop() {
+                    message.parts.pop();
+                    pending = message.parts;
+                }
+                eprintln!("{:#}", e);
+            }
+        }
+    }
+
+    if let Some(history_file) = &history_file {
+        if let Err(e) = editor.save_history(history_file) {
+            eprintln!("failed to save history: {}", e);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! OpenAI compatible proxy for the Gemini and Claude models on Vertex AI, see [async_google_gemini::proxy].
+//!
+//! Configured through environment variables:
+//! - `GEMINI_PROXY_ADDR`: the address to listen on, defaults to `127.0.0.1:8080`
+//! - `GEMINI_PROXY_API_KEYS`: comma separated keys clients have to send, every request is accepted if unset
+//! - `GCP_SERVICE_ACCOUNT_FILE`, `GCP_SERVICE_ACCOUNT`, `GCP_TOKEN` and `GCP_PROJECT_ID`, or `GEMINI_API_KEY`: the credentials
+//! - `GCP_LOCATION`: the default region
+//! - `CLAUDE_BACKEND`: `vertex` or `anthropic`, the api Claude models are sent to, defaults to `vertex`
+//! - `ANTHROPIC_API_KEY`: the key for the `anthropic` backend
+
+use anyhow::{anyhow, Result};
+use async_google_gemini::{
+    client::Client,
+    config::{ClaudeBackend, GeminiConfig},
+    proxy::Proxy,
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let config = match std::env::var("GCP_SERVICE_ACCOUNT_FILE") {
+        Ok(path) => GeminiConfig::try_from_service_account_file(path)?,
+        Err(_) => GeminiConfig::try_from_service_account_env()
+            .or_else(|_| GeminiConfig::try_from_env_vars())
+            .or_else(|_| GeminiConfig::try_from_api_key_env())
+            .map_err(|_| {
+                anyhow!("no credentials, set GCP_SERVICE_ACCOUNT_FILE, GCP_SERVICE_ACCOUNT, GCP_TOKEN and GCP_PROJECT_ID, or GEMINI_API_KEY")
+            })?,
+    };
+
+    let claude_backend = match std::env::var("CLAUDE_BACKEND") {
+        Ok(backend) => backend.parse::<ClaudeBackend>().map_err(|_| {
+            anyhow!(
+                "CLAUDE_BACKEND must be vertex or anthropic, got {}",
+                backend
+            )
+        })?,
+        Err(_) => ClaudeBackend::Vertex,
+    };
+    let config = config
+        .with_anthropic_api_key_env()
+        .with_claude_backend(claude_backend);
+    if claude_backend == ClaudeBackend::Anthropic && config.anthropic_api_key().is_none() {
+        return Err(anyhow!(
+            "CLAUDE_BACKEND is anthropic, but ANTHROPIC_API_KEY is not set"
+        ));
+    }
+
+    let mut proxy = Proxy::new(Client::new(config)?);
+    for key in std::env::var("GEMINI_PROXY_API_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+    {
+        proxy = proxy.with_api_key(key);
+    }
+
+    let addr = std::env::var("GEMINI_PROXY_ADDR").unwrap_or("127.0.0.1:8080".to_string());
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    eprintln!(
+        "listening on {}, sending claude models to {}",
+        listener.local_addr()?,
+        claude_backend
+    );
+
+    proxy.serve(listener).await?;
+    Ok(())
+}
@@ -0,0 +1,495 @@
+//! Record and replay of api calls, to run tests against real model outputs without network access.
+//!
+//! In [CassetteMode::Record] every request made through the [crate::client::Client] is sent and kept together
+//! with its response, server sent event streams included, until [Cassette::save] writes them to a JSON cassette
+//! file. Authentication headers are redacted. In [CassetteMode::Replay] requests are answered from the cassette and nothing is sent. Replaying
+//! still asks the config for a token, so tests should use [crate::config::GeminiConfig::from_static_token].
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
+use reqwest::{header::HeaderMap, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::TransportError, middleware::RequestContext};
+
+/// Headers which are never written to a cassette.
+const REDACTED_HEADERS: &[&str] = &["authorization", "x-goog-api-key", "x-api-key"];
+/// Headers describing the connection rather than the response, which don't apply to a replayed body.
+const CONNECTION_HEADERS: &[&str] = &["connection", "content-length", "transfer-encoding"];
+
+/// Whether a [Cassette] records or replays calls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CassetteMode {
+    /// Send every request and write it to the cassette with its response.
+    Record,
+    /// Answer every request from the cassette, failing requests without a recorded interaction.
+    Replay,
+}
+
+/// Decides which recorded interaction answers a request.
+/// Requests always have to match the model and api method of the interaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CassetteMatcher {
+    /// Compare the full url, including the project and region.
+    pub url: bool,
+    /// Compare the JSON body.
+    pub body: bool,
+    /// Dotted paths of body fields which are left out of the comparison, e.g. `generationConfig.seed`.
+    pub ignored_fields: Vec<String>,
+}
+
+impl Default for CassetteMatcher {
+    fn default() -> Self {
+        Self {
+            url: true,
+            body: true,
+            ignored_fields: Vec::new(),
+        }
+    }
+}
+
+impl CassetteMatcher {
+    /// Matches on the url and the body.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ignores the url, e.g. so cassettes recorded in one project can be replayed in another.
+    pub fn ignore_url(mut self) -> Self {
+        self.url = false;
+        self
+    }
+
+    /// Ignores the body, so interactions are only matched by the model and api method.
+    pub fn ignore_body(mut self) -> Self {
+        self.body = false;
+        self
+    }
+
+    /// Ignores a body field, given as a dotted path such as `generationConfig.temperature`.
+    pub fn ignore_field(mut self, path: impl Into<String>) -> Self {
+        self.ignored_fields.push(path.into());
+        self
+    }
+
+    fn matches(&self, recorded: &RecordedCall, request: &RecordedCall) -> bool {
+        recorded.model == request.model
+            && recorded.method == request.method
+            && recorded.stream == request.stream
+            && (!self.url || recorded.url == request.url)
+            && (!self.body || self.normalize(&recorded.body) == self.normalize(&request.body))
+    }
+
+    fn normalize(&self, body: &serde_json::Value) -> serde_json::Value {
+        let mut body = body.clone();
+        for path in &self.ignored_fields {
+            remove_field(&mut body, path);
+        }
+        body
+    }
+}
+
+fn remove_field(value: &mut serde_json::Value, path: &str) {
+    let (key, rest) = match path.split_once('.') {
+        Some((key, rest)) => (key, Some(rest)),
+        None => (path, None),
+    };
+
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                remove_field(item, path);
+            }
+        }
+        serde_json::Value::Object(object) => match rest {
+            Some(rest) => {
+                if let Some(value) = object.get_mut(key) {
+                    remove_field(value, rest);
+                }
+            }
+            None => {
+                object.remove(key);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// The request of a recorded interaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub model: String,
+    /// The api method, e.g. `generateContent`.
+    pub method: String,
+    pub url: String,
+    pub stream: bool,
+    pub headers: Vec<(String, String)>,
+    pub body: serde_json::Value,
+}
+
+/// The response of a recorded interaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The body of a regular response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// The data of each server sent event of a stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<String>>,
+}
+
+/// A request and its response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedCall,
+    pub response: RecordedResponse,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    /// Which interactions have been replayed, identical requests are answered in the recorded order.
+    replayed: Vec<bool>,
+    /// Whether interactions were recorded since the cassette was last saved.
+    unsaved: bool,
+}
+
+impl Drop for CassetteState {
+    fn drop(&mut self) {
+        if self.unsaved {
+            tracing::warn!(
+                interactions = self.interactions.len(),
+                "cassette dropped with unsaved interactions, call Cassette::save"
+            );
+        }
+    }
+}
+
+/// A cassette file which records or replays the calls of a [crate::client::Client].
+///
+/// Clones share the same interactions.
+#[derive(Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    matcher: CassetteMatcher,
+    redacted_headers: Vec<String>,
+    state: Arc<Mutex<CassetteState>>,
+}
+
+impl Cassette {
+    fn new(path: PathBuf, mode: CassetteMode, interactions: Vec<Interaction>) -> Self {
+        Self {
+            path,
+            mode,
+            matcher: CassetteMatcher::default(),
+            redacted_headers: REDACTED_HEADERS.iter().map(|h| h.to_string()).collect(),
+            state: Arc::new(Mutex::new(CassetteState {
+                replayed: vec![false; interactions.len()],
+                interactions,
+                unsaved: false,
+            })),
+        }
+    }
+
+    /// Records calls, which replace the previous contents of the file once the cassette is saved.
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Self::new(
+            path.as_ref().to_path_buf(),
+            CassetteMode::Record,
+            Vec::new(),
+        )
+    }
+
+    /// Replays the calls recorded in the file.
+    ///
+    /// # Arguments:
+    /// - `path`: The path of a cassette written in [CassetteMode::Record].
+    pub fn replay(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path.as_ref())?;
+        let file = serde_json::from_str::<CassetteFile>(&contents)?;
+
+        Ok(Self::new(
+            path.as_ref().to_path_buf(),
+            CassetteMode::Replay,
+            file.interactions,
+        ))
+    }
+
+    /// Sets how requests are matched to recorded interactions when replaying.
+    pub fn with_matcher(mut self, matcher: CassetteMatcher) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// Leaves the header out of recorded requests and responses, in addition to the authentication headers.
+    pub fn redact_header(mut self, name: impl Into<String>) -> Self {
+        self.redacted_headers.push(name.into().to_lowercase());
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.lock().unwrap().interactions.clone()
+    }
+
+    /// Writes the recorded interactions to the file, off the async runtime.
+    ///
+    /// Interactions are only kept in memory while recording, so this has to be called once the calls are done.
+    pub async fn save(&self) -> anyhow::Result<()> {
+        let contents = {
+            let mut state = self.state.lock().unwrap();
+            state.unsaved = false;
+            serde_json::to_string_pretty(&CassetteFile {
+                interactions: state.interactions.clone(),
+            })?
+        };
+
+        let path = self.path.clone();
+        let written = tokio::task::spawn_blocking(move || std::fs::write(path, contents)).await?;
+        if written.is_err() {
+            self.state.lock().unwrap().unsaved = true;
+        }
+        Ok(written?)
+    }
+
+    /// Sends or replays a request which already went through the middleware.
+    pub(crate) async fn execute(
+        &self,
+        ctx: &RequestContext,
+        request: RequestBuilder,
+    ) -> Result<reqwest::Response, TransportError> {
+        match self.mode {
+            CassetteMode::Replay => self.replay_response(ctx),
+            CassetteMode::Record => self.record_response(ctx, request).await,
+        }
+    }
+
+    fn call(&self, ctx: &RequestContext, headers: &HeaderMap) -> RecordedCall {
+        RecordedCall {
+            model: ctx
+                .model
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            method: ctx.method.clone(),
+            url: ctx.url.clone(),
+            stream: ctx.stream,
+            headers: self.headers(headers),
+            body: serde_json::from_str(&ctx.body)
+                .unwrap_or_else(|_| serde_json::Value::String(ctx.body.clone())),
+        }
+    }
+
+    fn headers(&self, headers: &HeaderMap) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .filter(|(name, _)| !self.redacted_headers.iter().any(|h| h == name.as_str()))
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn replay_response(&self, ctx: &RequestContext) -> Result<reqwest::Response, TransportError> {
+        let call = self.call(ctx, &HeaderMap::new());
+
+        let response = {
+            let mut state = self.state.lock().unwrap();
+            let matching = state
+                .interactions
+                .iter()
+                .enumerate()
+                .filter(|(_, i)| self.matcher.matches(&i.request, &call))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+
+            // the first interaction which has not been replayed yet, or the last one once all have been
+            let index = matching
+                .iter()
+                .find(|index| !state.replayed[**index])
+                .or(matching.last())
+                .copied()
+                .ok_or_else(|| {
+                    TransportError::Cassette(format!(
+                        "no interaction in {} matches {} {}",
+                        self.path.display(),
+                        ctx.method,
+                        ctx.url
+                    ))
+                })?;
+
+            state.replayed[index] = true;
+            state.interactions[index].response.clone()
+        };
+
+        let mut builder = http::Response::builder().status(response.status);
+        for (name, value) in &response.headers {
+            builder = builder.header(name, value);
+        }
+
+        let body = match (response.events, response.body) {
+            (Some(events), _) => events
+                .iter()
+                .map(|event| format!("data: {}\r\n\r\n", event))
+                .collect(),
+            (None, body) => body.unwrap_or_default(),
+        };
+
+        builder
+            .body(body)
+            .map(reqwest::Response::from)
+            .map_err(|e| TransportError::Cassette(format!("invalid recorded response: {}", e)))
+    }
+
+    async fn record_response(
+        &self,
+        ctx: &RequestContext,
+        request: RequestBuilder,
+    ) -> Result<reqwest::Response, TransportError> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        let call = self.call(ctx, request.headers());
+
+        let res = client.execute(request).await?;
+        let status = res.status();
+        let headers = res.headers().clone();
+
+        let mut builder = http::Response::builder().status(status);
+        for (name, value) in &headers {
+            builder = builder.header(name, value);
+        }
+
+        let recorder = InteractionRecorder {
+            cassette: self.clone(),
+            call: Some(call),
+            response: RecordedResponse {
+                status: status.as_u16(),
+                headers: self
+                    .headers(&headers)
+                    .into_iter()
+                    .filter(|(name, _)| !CONNECTION_HEADERS.contains(&name.as_str()))
+                    .collect(),
+                body: None,
+                events: None,
+            },
+            data: Vec::new(),
+        };
+
+        let body = match ctx.stream && status.is_success() {
+            // the events are recorded as they pass through, once the stream is done
+            true => reqwest::Body::wrap_stream(stream::unfold(
+                (res.bytes_stream(), recorder),
+                |(mut source, mut recorder)| async move {
+                    let chunk = source.next().await?;
+                    match &chunk {
+                        Ok(chunk) => recorder.data.extend_from_slice(chunk),
+                        // a stream which broke off is not recorded
+                        Err(_) => recorder.discard(),
+                    }
+                    Some((chunk, (source, recorder)))
+                },
+            )),
+            false => {
+                let mut recorder = recorder;
+                let data = match res.bytes().await {
+                    Ok(data) => data,
+                    Err(e) => {
+                        recorder.discard();
+                        return Err(e.into());
+                    }
+                };
+                recorder.data.extend_from_slice(&data);
+                reqwest::Body::from(data)
+            }
+        };
+
+        builder
+            .body(body)
+            .map(reqwest::Response::from)
+            .map_err(|e| TransportError::Cassette(format!("invalid response: {}", e)))
+    }
+
+    fn push(&self, interaction: Interaction) {
+        let mut state = self.state.lock().unwrap();
+        state.interactions.push(interaction);
+        state.replayed.push(false);
+        state.unsaved = true;
+    }
+}
+
+/// Collects the body of a response and adds the interaction to the cassette when dropped,
+/// so streams are recorded once they end.
+struct InteractionRecorder {
+    cassette: Cassette,
+    call: Option<RecordedCall>,
+    response: RecordedResponse,
+    data: Vec<u8>,
+}
+
+impl InteractionRecorder {
+    /// Leaves the interaction out of the cassette, e.g. when its body could not be read.
+    fn discard(&mut self) {
+        self.call = None;
+    }
+}
+
+impl Drop for InteractionRecorder {
+    fn drop(&mut self) {
+        let Some(call) = self.call.take() else {
+            return;
+        };
+
+        let data = String::from_utf8_lossy(&self.data).to_string();
+        let mut response = self.response.clone();
+        match call.stream && (200..300).contains(&response.status) {
+            true => response.events = Some(sse_data(&data)),
+            false => response.body = Some(data),
+        }
+
+        self.cassette.push(Interaction {
+            request: call,
+            response,
+            recorded_at: Utc::now(),
+        });
+    }
+}
+
+/// The data of each event of a server sent event stream.
+fn sse_data(stream: &str) -> Vec<String> {
+    stream
+        .replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|event| {
+            let data = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect::<Vec<_>>();
+            (!data.is_empty()).then(|| data.join("\n"))
+        })
+        .collect()
+}
@@ -0,0 +1,481 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    pin::Pin,
+};
+
+use futures::{future::BoxFuture, Stream, StreamExt};
+use serde_json::{json, Value};
+
+use crate::{
+    claude::{
+        conversation::{push_message, RoleSequencePolicy},
+        Claude,
+    },
+    error::ChatError,
+    gemini::Gemini,
+    types::{
+        chat::{
+            tool_use_id, ChatChunk, ChatFinishReason, ChatMessage, ChatPart, ChatRequest,
+            ChatResponse, ChatRole, ChatTool, ChatUsage, ModelId,
+        },
+        claude::{
+            ClaudeMessage, ClaudeModel, ClaudeSystemPrompt, ClaudeTool, ClaudeUsage,
+            RawPredictRequest, RawPredictResponse, StreamRawPredictResponse, ANTHROPIC_VERSION,
+        },
+        content::{
+            BaseModelParams, Content, FunctionDeclaration, FunctionDeclarationSchema,
+            FunctionDeclarationsTool, GenerateContentRequest, GenerateContentResponse,
+            GenerationConfig, Part, SystemInstruction, TextPart, Tool,
+        },
+        gemini::GeminiModel,
+    },
+};
+
+/// Claude requires `max_tokens`, this is used when the request does not set it.
+pub const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<ChatChunk, ChatError>> + Send + 'static>>;
+
+/// A chat model independent of the provider serving it.
+///
+/// Use [crate::client::Client::chat_model] to obtain one from a [ModelId].
+pub trait ChatModel: Send + Sync {
+    /// The model requests are sent to.
+    fn model(&self) -> ModelId;
+
+    /// Creates a chat response
+    fn chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>>;
+
+    /// Create a chat stream response
+    /// partial message deltas will be sent as stream chunks
+    fn chat_stream(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatStream, ChatError>>;
+}
+
+#[derive(Clone)]
+pub struct GeminiChatModel {
+    gemini: Gemini,
+    model: GeminiModel,
+}
+
+impl GeminiChatModel {
+    pub fn new(gemini: Gemini, model: GeminiModel) -> Self {
+        Self { gemini, model }
+    }
+}
+
+impl ChatModel for GeminiChatModel {
+    fn model(&self) -> ModelId {
+        ModelId::Gemini(self.model.clone())
+    }
+
+    fn chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+        Box::pin(async move {
+            let request = gemini_request(request)?;
+            let response = self
+                .gemini
+                .generate_content(self.model.clone(), request)
+                .await?;
+
+            Ok(gemini_response(response))
+        })
+    }
+
+    fn chat_stream(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatStream, ChatError>> {
+        Box::pin(async move {
+            let request = gemini_request(request)?;
+            let stream = self
+                .gemini
+                .stream_generate_content(self.model.clone(), request)
+                .await?;
+
+            let stream = stream.map(|chunk| {
+                let response = gemini_response(chunk?);
+                Ok(ChatChunk {
+                    parts: response.message.parts,
+                    finish_reason: response.finish_reason,
+                    usage: response.usage,
+                })
+            });
+
+            Ok(Box::pin(stream) as ChatStream)
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct ClaudeChatModel {
+    claude: Claude,
+    model: ClaudeModel,
+}
+
+impl ClaudeChatModel {
+    pub fn new(claude: Claude, model: ClaudeModel) -> Self {
+        Self { claude, model }
+    }
+}
+
+impl ChatModel for ClaudeChatModel {
+    fn model(&self) -> ModelId {
+        ModelId::Claude(self.model.clone())
+    }
+
+    fn chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatResponse, ChatError>> {
+        Box::pin(async move {
+            let request = claude_request(request, false)?;
+            let response = self.claude.raw_predict(self.model.clone(), request).await?;
+
+            Ok(claude_response(response))
+        })
+    }
+
+    fn chat_stream(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatStream, ChatError>> {
+        Box::pin(async move {
+            let request = claude_request(request, true)?;
+            let stream = self
+                .claude
+                .stream_raw_predict(self.model.clone(), request)
+                .await?;
+
+            let mut state = ClaudeStreamState::default();
+            let stream = stream.filter_map(move |event| {
+                let chunk = match event {
+                    Ok(event) => state.chunk(event).map(Ok),
+                    Err(e) => Some(Err(e.into())),
+                };
+                futures::future::ready(chunk)
+            });
+
+            Ok(Box::pin(stream) as ChatStream)
+        })
+    }
+}
+
+fn gemini_request(request: ChatRequest) -> Result<GenerateContentRequest, ChatError> {
+    // Gemini matches function responses by name, results coming from Claude only carry the call id.
+    let names = request
+        .messages
+        .iter()
+        .flat_map(|message| &message.parts)
+        .filter_map(|part| match part {
+            ChatPart::ToolCall {
+                id: Some(id), name, ..
+            } => Some((id.clone(), name.clone())),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    let contents = request
+        .messages
+        .into_iter()
+        .map(|mut message| {
+            for part in &mut message.parts {
+                if let ChatPart::ToolResult {
+                    id: Some(id), name, ..
+                } = part
+                {
+                    if name.is_empty() {
+                        *name = names.get(id).cloned().unwrap_or_default();
+                    }
+                }
+            }
+            Content::from(message)
+        })
+        .collect();
+
+    let tools = match request.tools.is_empty() {
+        true => None,
+        false => Some(vec![Tool::FunctionDeclarationsTool(
+            FunctionDeclarationsTool {
+                function_declarations: Some(
+                    request
+                        .tools
+                        .into_iter()
+                        .map(gemini_function_declaration)
+                        .collect::<Result<_, _>>()?,
+                ),
+            },
+        )]),
+    };
+
+    let generation_config = GenerationConfig {
+        max_output_tokens: request.max_tokens,
+        temperature: request.temperature,
+        top_p: request.top_p,
+        stop_sequences: request.stop_sequences,
+        ..Default::default()
+    };
+
+    Ok(GenerateContentRequest {
+        base_model_params: BaseModelParams {
+            generation_config: Some(generation_config),
+            tools,
+            system_instruction: request.system.map(|text| {
+                SystemInstruction::Content(Content {
+                    parts: vec![Part::TextPart(TextPart { text })],
+                    role: "system".to_string(),
+                })
+            }),
+            ..Default::default()
+        },
+        contents,
+        cached_content: None,
+    })
+}
+
+fn gemini_function_declaration(tool: ChatTool) -> Result<FunctionDeclaration, ChatError> {
+    let parameters = match tool.parameters {
+        Some(mut schema) => {
+            uppercase_schema_types(&mut schema);
+            if let Value::Object(object) = &mut schema {
+                object.entry("properties").or_insert_with(|| json!({}));
+            }
+
+            Some(
+                serde_json::from_value::<FunctionDeclarationSchema>(schema).map_err(|e| {
+                    ChatError::Conversion(format!("invalid parameters for {}: {}", tool.name, e))
+                })?,
+            )
+        }
+        None => None,
+    };
+
+    Ok(FunctionDeclaration {
+        name: tool.name,
+        description: tool.description,
+        parameters,
+    })
+}
+
+/// JSON schema uses lower case type names, Gemini expects them in upper case.
+fn uppercase_schema_types(schema: &mut Value) {
+    match schema {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match (key.as_str(), value) {
+                    ("type", Value::String(t)) => *t = t.to_uppercase(),
+                    (_, value) => uppercase_schema_types(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(uppercase_schema_types),
+        _ => {}
+    }
+}
+
+fn gemini_response(response: GenerateContentResponse) -> ChatResponse {
+    let blocked = response
+        .prompt_feedback
+        .as_ref()
+        .is_some_and(|feedback| feedback.block_reason.is_some());
+
+    let candidate = response
+        .candidates
+        .and_then(|candidates| candidates.into_iter().next());
+
+    let (parts, finish_reason) = match candidate {
+        Some(candidate) => (
+            candidate
+                .content
+                .map(|content| ChatMessage::from(content).parts)
+                .unwrap_or_default(),
+            candidate.finish_reason.map(ChatFinishReason::from),
+        ),
+        None => (Vec::new(), None),
+    };
+
+    ChatResponse {
+        message: ChatMessage {
+            role: ChatRole::Assistant,
+            parts,
+        },
+        finish_reason: match blocked {
+            true => Some(ChatFinishReason::ContentFilter),
+            false => finish_reason,
+        },
+        usage: response.usage_metadata.map(ChatUsage::from),
+    }
+}
+
+fn claude_request(request: ChatRequest, stream: bool) -> Result<RawPredictRequest, ChatError> {
+    // Claude matches tool results by call id, calls coming from Gemini only carry a name.
+    // Parallel calls of the same function are answered in order.
+    let mut ids: HashMap<String, VecDeque<String>> = HashMap::new();
+    let mut messages: Vec<ClaudeMessage> = Vec::with_capacity(request.messages.len());
+
+    for mut message in request.messages {
+        for part in message.parts.iter_mut() {
+            match part {
+                ChatPart::ToolCall { id, name, .. } => {
+                    let id = id.get_or_insert_with(tool_use_id);
+                    ids.entry(name.clone()).or_default().push_back(id.clone());
+                }
+                ChatPart::ToolResult { id, name, .. } => {
+                    let pending = ids.entry(name.clone()).or_default();
+                    match id {
+                        Some(id) => pending.retain(|pending| pending != id),
+                        None => *id = pending.pop_front(),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        push_message(
+            &mut messages,
+            ClaudeMessage::try_from(message)?,
+            RoleSequencePolicy::Merge,
+        )?;
+    }
+
+    let tools = match request.tools.is_empty() {
+        true => None,
+        false => Some(
+            request
+                .tools
+                .into_iter()
+                .map(|tool| ClaudeTool {
+                    name: tool.name,
+                    description: tool.description,
+                    input_schema: tool
+                        .parameters
+                        .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+                })
+                .collect(),
+        ),
+    };
+
+    Ok(RawPredictRequest {
+        anthropic_version: ANTHROPIC_VERSION.to_string(),
+        max_tokens: request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        system: ClaudeSystemPrompt::Text(request.system.unwrap_or_default()),
+        stream,
+        messages,
+        top_p: request.top_p,
+        temperature: request.temperature,
+        stop_sequences: request.stop_sequences,
+        tools,
+        tool_choice: None,
+    })
+}
+
+fn claude_response(response: RawPredictResponse) -> ChatResponse {
+    let parts = response
+        .content
+        .into_iter()
+        .filter_map(|block| match ChatPart::try_from(block) {
+            Ok(part) => Some(part),
+            Err(e) => {
+                tracing::warn!(error=?e, "skipping content block");
+                None
+            }
+        })
+        .collect();
+
+    ChatResponse {
+        message: ChatMessage {
+            role: ChatRole::Assistant,
+            parts,
+        },
+        finish_reason: response.stop_reason.map(ChatFinishReason::from),
+        usage: Some(ChatUsage::from(response.usage)),
+    }
+}
+
+struct PendingToolCall {
+    id: Option<String>,
+    name: String,
+    input: Option<Value>,
+    json: String,
+}
+
+/// Turns `stream_raw_predict` events into chunks, holding back tool calls until their input is complete.
+#[derive(Default)]
+struct ClaudeStreamState {
+    input_tokens: u32,
+    tool_calls: HashMap<u32, PendingToolCall>,
+}
+
+impl ClaudeStreamState {
+    fn chunk(&mut self, event: StreamRawPredictResponse) -> Option<ChatChunk> {
+        let parts = match event {
+            StreamRawPredictResponse::MessageStart { message } => {
+                self.input_tokens = message.usage.input_tokens;
+                return None;
+            }
+            StreamRawPredictResponse::ContentBlockStart {
+                index,
+                content_block,
+            } => {
+                if content_block.c_type == "tool_use" {
+                    self.tool_calls.insert(
+                        index.unwrap_or_default(),
+                        PendingToolCall {
+                            id: content_block.id,
+                            name: content_block.name.unwrap_or_default(),
+                            input: content_block.input,
+                            json: String::new(),
+                        },
+                    );
+                    return None;
+                }
+
+                vec![ChatPart::text(content_block.text)]
+            }
+            StreamRawPredictResponse::ContentBlockDelta { index, delta } => {
+                if let Some(fragment) = delta.partial_json {
+                    if let Some(call) = self.tool_calls.get_mut(&index.unwrap_or_default()) {
+                        call.json.push_str(&fragment);
+                    }
+                    return None;
+                }
+
+                vec![ChatPart::text(delta.text)]
+            }
+            StreamRawPredictResponse::ContentBlockStop { index } => {
+                let call = self.tool_calls.remove(&index.unwrap_or_default())?;
+                let arguments = match call.json.is_empty() {
+                    true => call.input.unwrap_or_else(|| json!({})),
+                    false => match serde_json::from_str(&call.json) {
+                        Ok(arguments) => arguments,
+                        Err(e) => {
+                            tracing::error!(error=?e, "failed to parse tool input");
+                            Value::String(call.json)
+                        }
+                    },
+                };
+
+                vec![ChatPart::ToolCall {
+                    id: call.id,
+                    name: call.name,
+                    arguments,
+                }]
+            }
+            StreamRawPredictResponse::MessageDelta { delta, usage, .. } => {
+                return Some(ChatChunk {
+                    parts: Vec::new(),
+                    finish_reason: delta.stop_reason.map(ChatFinishReason::from),
+                    usage: usage.or(delta.usage).map(|usage| {
+                        ChatUsage::from(ClaudeUsage {
+                            input_tokens: self.input_tokens.max(usage.input_tokens),
+                            ..usage
+                        })
+                    }),
+                })
+            }
+            StreamRawPredictResponse::MessageStop | StreamRawPredictResponse::Ping => return None,
+        };
+
+        let parts = parts
+            .into_iter()
+            .filter(|part| !matches!(part, ChatPart::Text { text } if text.is_empty()))
+            .collect::<Vec<_>>();
+
+        match parts.is_empty() {
+            true => None,
+            false => Some(ChatChunk {
+                parts,
+                ..Default::default()
+            }),
+        }
+    }
+}
@@ -1,24 +1,318 @@
+pub mod conversation;
+
 use std::pin::Pin;
 
 use crate::{
-    client::Client,
+    client::{next_within, within, Client},
+    config::ClaudeBackend,
     error::ClaudeError,
-    types::claude::{
-        ClaudeModel, RawPredictErrorResponse, RawPredictRequest, RawPredictResponse,
-        StreamRawPredictResponse,
+    limiter::{estimate_raw_predict_tokens, RatePermit},
+    middleware::{apply_request, notify_event, notify_response, RequestContext},
+    response_cache::{raw_predict_events, CacheScope, ResponseCache},
+    telemetry::{finish_reason, RequestAttributes, Telemetry, SYSTEM_ANTHROPIC},
+    types::{
+        capabilities::Capabilities,
+        chat::ModelId,
+        claude::{
+            ClaudeModel, ClaudeUsage, RawPredictErrorResponse, RawPredictRequest,
+            RawPredictResponse, StreamRawPredictResponse, ANTHROPIC_API_VERSION,
+        },
+        content::RequestOptions,
     },
+    usage::{Tags, UsageScope},
 };
-use futures::{stream::StreamExt, Stream};
-use reqwest_eventsource::{Event, RequestBuilderExt};
+use eventsource_stream::{EventStreamError, Eventsource};
+use futures::{Stream, StreamExt};
+use serde_json::Value;
 use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use self::conversation::Conversation;
+
+/// Claude models are only served from a few regions, this is used unless another location is set.
+pub const DEFAULT_LOCATION: &str = "us-east5";
+
+/// The region rate limits of calls to the Anthropic API are kept under, its quota doesn't depend on a location.
+pub const ANTHROPIC_REGION: &str = "anthropic";
+
+/// The events of a streamed response.
+type EventStream<T> = Pin<Box<dyn Stream<Item = Result<T, ClaudeError>> + Send + 'static>>;
 
-pub struct Claude<'c> {
-    client: &'c Client,
+#[derive(Clone)]
+pub struct Claude {
+    client: Client,
+    location: Option<String>,
+    backend: Option<ClaudeBackend>,
+    tags: Tags,
+    options: RequestOptions,
 }
 
-impl<'c> Claude<'c> {
-    pub fn new(client: &'c Client) -> Self {
-        Self { client }
+impl Claude {
+    pub fn new(client: Client) -> Self {
+        Self {
+            options: client.options().clone(),
+            client,
+            location: None,
+            backend: None,
+            tags: Tags::new(),
+        }
+    }
+
+    /// Applies the options, such as timeouts and custom headers, to calls made through this handle instead of the client defaults
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &RequestOptions {
+        &self.options
+    }
+
+    /// Attaches a tag such as `tenant` or `feature` to the usage recorded for calls made through this handle
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Attaches tags to the usage recorded for calls made through this handle
+    pub fn with_tags<K: Into<String>, V: Into<String>>(
+        mut self,
+        tags: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.tags
+            .extend(tags.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    pub fn tags(&self) -> &Tags {
+        &self.tags
+    }
+
+    /// Sends requests to the given region instead of [DEFAULT_LOCATION]
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    /// The region requests are sent to
+    pub fn location(&self) -> &str {
+        self.location.as_deref().unwrap_or(DEFAULT_LOCATION)
+    }
+
+    /// The region the quota of a call is counted in, the location on Vertex AI and [ANTHROPIC_REGION] on the Anthropic API
+    pub fn region(&self) -> &str {
+        match self.backend() {
+            ClaudeBackend::Vertex => self.location(),
+            ClaudeBackend::Anthropic => ANTHROPIC_REGION,
+        }
+    }
+
+    /// Sends requests to the given api instead of the one picked by the config, see [crate::config::GeminiConfig::claude_backend]
+    pub fn with_backend(mut self, backend: ClaudeBackend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// The api requests are sent to
+    pub fn backend(&self) -> ClaudeBackend {
+        self.backend
+            .unwrap_or_else(|| self.client.config().claude_backend())
+    }
+
+    /// Starts a multi-turn conversation which keeps track of the message history
+    pub fn conversation(&self, model: ClaudeModel, max_tokens: u32) -> Conversation {
+        Conversation::new(self.clone(), model, max_tokens)
+    }
+
+    /// The usage recorder of the client together with the model and tags of a call
+    fn usage_scope(&self, model: &ClaudeModel) -> Option<UsageScope> {
+        Some(UsageScope {
+            recorder: self.client.usage_recorder()?.clone(),
+            model: model.clone().into(),
+            tags: self.tags.clone(),
+        })
+    }
+
+    /// The response cache of the client together with the key and cache mode of a call
+    fn cache_scope(&self, model: &ClaudeModel, body: &Value) -> Option<CacheScope> {
+        Some(CacheScope {
+            cache: self.client.response_cache()?.clone(),
+            key: ResponseCache::key(
+                &self.backend().to_string(),
+                self.client.config().project_id(),
+                self.location(),
+                model,
+                body,
+            ),
+            mode: self.options.cache_mode.unwrap_or_default(),
+        })
+    }
+
+    fn check_budget(&self) -> Result<(), ClaudeError> {
+        let Some(recorder) = self.client.usage_recorder() else {
+            return Ok(());
+        };
+
+        recorder.check_budget(&self.tags).map_err(|e| {
+            tracing::error!(error=%e, "usage budget exceeded");
+            ClaudeError::BudgetExceeded(e)
+        })
+    }
+
+    /// Takes the quota for the request from the rate limiter of the client, waiting if configured to
+    async fn acquire_permit(
+        &self,
+        model: &ClaudeModel,
+        request: &RawPredictRequest,
+    ) -> Result<RatePermit, ClaudeError> {
+        let Some(limiter) = self.client.rate_limiter() else {
+            return Ok(RatePermit::default());
+        };
+
+        let model_id = ModelId::from(model.clone());
+        let tokens = match limiter.limits_tokens(&model_id, self.region()) {
+            true => estimate_raw_predict_tokens(request),
+            false => 0,
+        };
+
+        limiter
+            .acquire(&model_id, self.region(), tokens)
+            .await
+            .map_err(|e| {
+                tracing::warn!(error=%e, "rate limit reached");
+                ClaudeError::RateLimitError(e)
+            })
+    }
+
+    fn url(&self, model: &ClaudeModel, method: &str) -> String {
+        match self.backend() {
+            ClaudeBackend::Vertex => format!(
+                "{}/v1/projects/{}/locations/{}/publishers/anthropic/models/{}:{}",
+                self.client.endpoint(self.location()),
+                self.client.config().project_id(),
+                self.location(),
+                model,
+                method,
+            ),
+            ClaudeBackend::Anthropic => format!("{}/v1/messages", self.client.anthropic_endpoint()),
+        }
+    }
+
+    /// The body of a request, the Anthropic API takes the model in the body and the version as a header
+    fn body(&self, model: &ClaudeModel, mut body: Value) -> Result<String, ClaudeError> {
+        if let (ClaudeBackend::Anthropic, Some(body)) = (self.backend(), body.as_object_mut()) {
+            body.remove("anthropic_version");
+            body.insert("model".to_string(), Value::String(model.anthropic_id()));
+        }
+
+        let result = serde_json::to_string(&body);
+
+        result.map_err(|e| {
+            tracing::error!(error=?e, "failed to serialize request");
+            ClaudeError::ParseError(format!("failed to serialize request: {}", e))
+        })
+    }
+
+    /// The headers authenticating a request
+    async fn auth_headers(&self) -> Result<Vec<(&'static str, String)>, ClaudeError> {
+        match self.backend() {
+            ClaudeBackend::Vertex => {
+                let token = self.client.config().token().await.map_err(|e| {
+                    tracing::error!(error=?e, "failed to get authentication token");
+                    ClaudeError::AuthenticationError(e.to_string())
+                })?;
+                Ok(vec![("Authorization", format!("Bearer {}", token))])
+            }
+            ClaudeBackend::Anthropic => {
+                let key = self.client.config().anthropic_api_key().ok_or_else(|| {
+                    tracing::error!("no anthropic api key configured");
+                    ClaudeError::AuthenticationError("no anthropic api key configured".to_string())
+                })?;
+                Ok(vec![
+                    ("x-api-key", key.to_string()),
+                    ("anthropic-version", ANTHROPIC_API_VERSION.to_string()),
+                ])
+            }
+        }
+    }
+
+    /// Sends a request through the middleware of the client
+    async fn send(
+        &self,
+        model: &ClaudeModel,
+        stream: bool,
+        body: Value,
+    ) -> Result<(reqwest::Response, RequestContext), ClaudeError> {
+        let method = match self.backend() {
+            ClaudeBackend::Vertex => "streamRawPredict",
+            ClaudeBackend::Anthropic => "messages",
+        };
+        // the Anthropic API streams whenever the body asks for it
+        let url = match (stream, self.backend()) {
+            (true, ClaudeBackend::Vertex) => self.url(model, &format!("{}?alt=sse", method)),
+            _ => self.url(model, method),
+        };
+
+        let body = self.body(model, body)?;
+        let headers = self.auth_headers().await?;
+
+        let ctx = RequestContext {
+            model: Some(model.clone().into()),
+            method: method.to_string(),
+            url,
+            region: self.region().to_string(),
+            stream,
+            body,
+            tags: self.tags.clone(),
+        };
+
+        let request = headers.into_iter().fold(
+            self.client
+                .http_client()
+                .post(&ctx.url)
+                .header("content-type", "application/json; charset=utf-8")
+                .body(ctx.body.clone()),
+            |request, (name, value)| request.header(name, value),
+        );
+        // the body of a stream is bounded by the idle timeout instead
+        let request = match stream {
+            true => self.options.apply_headers(request),
+            false => self.options.apply(request),
+        };
+
+        let request = apply_request(self.client.middleware(), &ctx, request);
+        let response = within(
+            self.client.execute(&ctx, request),
+            self.options.timeout().filter(|_| stream),
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!(timeout=?self.options.timeout(), "no response within the timeout");
+            ClaudeError::Timeout(e.to_string())
+        })?;
+        match response {
+            Ok(res) => Ok((res, ctx)),
+            Err(e) => {
+                tracing::error!(error=?e, "failed to send request to anthropic");
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Starts the telemetry of a call, see [crate::telemetry]
+    fn telemetry(&self, model: &ClaudeModel, request: &RawPredictRequest) -> Telemetry {
+        let telemetry = Telemetry::start(
+            SYSTEM_ANTHROPIC,
+            model,
+            RequestAttributes {
+                max_tokens: Some(request.max_tokens),
+                temperature: request.temperature,
+                top_p: request.top_p,
+            },
+            self.client.capture_content(),
+        );
+        telemetry.prompt(request, self.client.middleware());
+        telemetry
     }
 
     /// Creates a chat response
@@ -27,101 +321,238 @@ impl<'c> Claude<'c> {
         model: ClaudeModel,
         request: RawPredictRequest,
     ) -> Result<RawPredictResponse, ClaudeError> {
-        let url = format!("https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/anthropic/models/{}:streamRawPredict",
-            "us-east5".to_string(),
-            self.client.config.project_id(),
-            "us-east5".to_string(),
-            model.to_string(),
-        );
+        let body = request_body(&request)?;
+        let (response, _) = self.raw_predict_body(model, request, body).await?;
+        Ok(response)
+    }
 
-        let client = self.client.http_client.clone();
+    /// Creates a chat response for a Messages api body, e.g. one received by a proxy.
+    /// Fields [RawPredictRequest] doesn't know, such as `top_k` or `thinking`, are sent as they are,
+    /// and the response is returned as it was received.
+    pub async fn raw_predict_json(
+        &self,
+        model: ClaudeModel,
+        body: Value,
+    ) -> Result<Value, ClaudeError> {
+        let request = parse_request(&body)?;
+        let (_, response) = self.raw_predict_body(model, request, body).await?;
+        Ok(response)
+    }
 
-        let token = self.client.config.token().await.unwrap();
+    async fn raw_predict_body(
+        &self,
+        model: ClaudeModel,
+        request: RawPredictRequest,
+        body: Value,
+    ) -> Result<(RawPredictResponse, Value), ClaudeError> {
+        let telemetry = self.telemetry(&model, &request);
+        let result = self
+            .raw_predict_inner(model, request, body)
+            .instrument(telemetry.span().clone())
+            .await;
 
-        let res = match client
-            .post(&url)
-            .header("content-type", "application/json; charset=utf-8")
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&request)
-            .send()
-            .await
-        {
-            Ok(res) => res,
-            Err(e) => {
-                tracing::error!(error=?e, "failed to send request to anthropic");
-                return Err(e.into());
-            }
-        };
+        match &result {
+            Ok((response, _)) => record_response(&telemetry, response),
+            Err(e) => telemetry.error(e),
+        }
+        result
+    }
 
-        let json = res.json::<serde_json::Value>().await.map_err(|e| {
-            tracing::error!(error=?e, "failed to parse response from anthropic");
-            ClaudeError::ParseError(e.to_string())
+    async fn raw_predict_inner(
+        &self,
+        model: ClaudeModel,
+        request: RawPredictRequest,
+        body: Value,
+    ) -> Result<(RawPredictResponse, Value), ClaudeError> {
+        check_capabilities(&model, &request)?;
+        let cache_scope = self.cache_scope(&model, &body);
+        if let Some(response) = cache_scope.as_ref().and_then(cached_response) {
+            return Ok(response);
+        }
+        self.check_budget()?;
+        let permit = self.acquire_permit(&model, &request).await?;
+
+        let (res, ctx) = self.send(&model, false, body).await?;
+
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.text().await.map_err(|e| {
+            tracing::error!(error=?e, "failed to read response from anthropic");
+            ClaudeError::from(e)
         })?;
 
-        let response = serde_json::from_value::<RawPredictResponse>(json)
-            .map_err(|e| ClaudeError::ParseError(format!("failed to parse response: {}", e)))?;
+        notify_response(
+            self.client.middleware(),
+            &ctx,
+            status,
+            &headers,
+            Some(&body),
+        );
 
-        Ok(response)
+        if !status.is_success() {
+            let error = ClaudeError::from_response(status.as_u16(), &body);
+            tracing::error!(error=?error, status=%status, "raw predict failed");
+            return Err(error);
+        }
+
+        let (response, raw) = serde_json::from_str::<Value>(&body)
+            .and_then(|raw| {
+                Ok((
+                    serde_json::from_value::<RawPredictResponse>(raw.clone())?,
+                    raw,
+                ))
+            })
+            .map_err(|e| {
+                tracing::error!(error=?e, "failed to parse response from anthropic");
+                ClaudeError::ParseError(format!("failed to parse response: {}", e))
+            })?;
+
+        permit.reconcile(response.usage.input_tokens);
+        if let Some(scope) = self.usage_scope(&model) {
+            scope.record(&response.usage);
+        }
+        if let Some(cache_scope) = &cache_scope {
+            cache_scope.insert(&raw);
+        }
+
+        Ok((response, raw))
     }
+
     /// Create a chat stream response
     /// partial message deltas will be sent as stream chunks
     pub async fn stream_raw_predict(
         &self,
         model: ClaudeModel,
         request: RawPredictRequest,
-    ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<StreamRawPredictResponse, ClaudeError>> + Send + 'static>>,
-        ClaudeError,
-    > {
-        let url = format!("https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/anthropic/models/{}:streamRawPredict?alt=sse",
-            "us-east5".to_string(),
-            self.client.config.project_id(),
-            "us-east5".to_string(),
-            model.to_string(),
-        );
+    ) -> Result<EventStream<StreamRawPredictResponse>, ClaudeError> {
+        let body = request_body(&request)?;
+        let events = self.stream_raw_predict_body(model, request, body).await?;
+        Ok(Box::pin(events.map(|event| event.map(|(event, _)| event))))
+    }
 
-        let client = self.client.http_client.clone();
+    /// Create a chat stream response for a Messages api body, e.g. one received by a proxy.
+    /// The body is sent as it is, see [Claude::raw_predict_json], and each event is returned as it was received.
+    pub async fn stream_raw_predict_json(
+        &self,
+        model: ClaudeModel,
+        body: Value,
+    ) -> Result<EventStream<Value>, ClaudeError> {
+        let request = parse_request(&body)?;
+        let events = self.stream_raw_predict_body(model, request, body).await?;
+        Ok(Box::pin(events.map(|event| event.map(|(_, raw)| raw))))
+    }
 
-        let token = self.client.config.token().await.map_err(|e| {
-            tracing::error!(error=?e, "failed to get authentication token");
-            ClaudeError::AuthenticationError(e.to_string())
-        })?;
+    async fn stream_raw_predict_body(
+        &self,
+        model: ClaudeModel,
+        request: RawPredictRequest,
+        body: Value,
+    ) -> Result<EventStream<(StreamRawPredictResponse, Value)>, ClaudeError> {
+        let telemetry = self.telemetry(&model, &request);
+        let result = self
+            .stream_raw_predict_inner(model, request, body, &telemetry)
+            .instrument(telemetry.span().clone())
+            .await;
+
+        if let Err(e) = &result {
+            telemetry.error(e);
+        }
+        result
+    }
+
+    async fn stream_raw_predict_inner(
+        &self,
+        model: ClaudeModel,
+        request: RawPredictRequest,
+        body: Value,
+        telemetry: &Telemetry,
+    ) -> Result<EventStream<(StreamRawPredictResponse, Value)>, ClaudeError> {
+        check_capabilities(&model, &request)?;
+        let cache_scope = self.cache_scope(&model, &body);
+        if let Some((response, _)) = cache_scope.as_ref().and_then(cached_response) {
+            record_response(telemetry, &response);
+            let events = raw_predict_events(response).into_iter().map(|event| {
+                let raw = serde_json::to_value(&event).unwrap_or_default();
+                Ok((event, raw))
+            });
+            return Ok(Box::pin(futures::stream::iter(events)));
+        }
+        self.check_budget()?;
+        let permit = self.acquire_permit(&model, &request).await?;
+
+        let (res, ctx) = self.send(&model, true, body).await?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let headers = res.headers().clone();
+            let body = res.text().await.unwrap_or_default();
+            notify_response(
+                self.client.middleware(),
+                &ctx,
+                status,
+                &headers,
+                Some(&body),
+            );
+            let error = ClaudeError::from_response(status.as_u16(), &body);
+            tracing::error!(error=?error, status=%status, "stream raw predict failed");
+            return Err(error);
+        }
+        notify_response(self.client.middleware(), &ctx, status, res.headers(), None);
 
         let (wx, rx) = mpsc::unbounded_channel();
-        let api_key = token;
+        let usage_scope = self.usage_scope(&model);
+        let middleware = self.client.middleware().to_vec();
+        let idle_timeout = self.options.stream_idle_timeout();
+        let telemetry = telemetry.clone();
+        let span = telemetry.span().clone();
 
         tokio::spawn(async move {
-            let mut source = match client
-                .post(&url)
-                .header("content-type", "application/json; charset=utf-8")
-                .header("Authorization", format!("Bearer {}", api_key))
-                .json(&request)
-                .eventsource()
-            {
-                Ok(res) => res,
-
-                Err(e) => {
-                    tracing::error!(error=?e, "failed to send request to anthropic");
-                    return;
-                }
-            };
+            let mut source = res.bytes_stream().eventsource();
+            // input tokens are reported by message_start and output tokens by message_delta,
+            // the usage is recorded once the stream ends
+            let mut usage: Option<ClaudeUsage> = None;
+            // the events folded into the message of message_start, for the telemetry
+            let mut response: Option<RawPredictResponse> = None;
+            let mut first_event = true;
 
-            while let Some(sse_event) = source.next().await {
-                let event = match sse_event {
-                    Ok(e) => match e {
-                        Event::Message(m) => m,
-                        Event::Open => {
-                            tracing::trace!("received open event");
-                            continue;
+            loop {
+                let sse_event = match next_within(&mut source, idle_timeout).await {
+                    Ok(Some(sse_event)) => sse_event,
+                    Ok(None) => break,
+                    Err(_) => {
+                        tracing::error!(idle_timeout=?idle_timeout, "stream idle timeout elapsed");
+                        let error = ClaudeError::Timeout(
+                            "no stream event received within the idle timeout".to_string(),
+                        );
+                        telemetry.error(&error);
+                        if let Err(send_error) = wx.send(Err(error)) {
+                            tracing::error!(error=?send_error, "failed to send error message to stream");
                         }
-                    },
+                        return;
+                    }
+                };
+
+                let event = match sse_event {
+                    Ok(e) => e,
                     Err(e) => {
-                        tracing::error!(error=?e, "failed to send request to anthropic");
+                        tracing::error!(error=?e, "failed to read chunk from anthropic");
+                        let error = match e {
+                            EventStreamError::Transport(e) => ClaudeError::from(e),
+                            e => ClaudeError::ParseError(e.to_string()),
+                        };
+                        telemetry.error(&error);
+                        if let Err(send_error) = wx.send(Err(error)) {
+                            tracing::error!(error=?send_error, "failed to send error message to stream");
+                        }
                         return;
                     }
                 };
 
                 let message = event.data;
+                notify_event(&middleware, &ctx, &message);
+                if std::mem::take(&mut first_event) {
+                    telemetry.first_token();
+                }
 
                 let res = match serde_json::from_str::<StreamRawPredictResponse>(&message) {
                     Ok(c) => c,
@@ -129,7 +560,9 @@ impl<'c> Claude<'c> {
                         match serde_json::from_str::<RawPredictErrorResponse>(&message) {
                             Ok(c) => {
                                 tracing::error!(error=?c, "stream raw predict failed");
-                                if let Err(send_error) = wx.send(Err(c.into())) {
+                                let error = ClaudeError::from(c);
+                                telemetry.error(&error);
+                                if let Err(send_error) = wx.send(Err(error)) {
                                     tracing::error!(
                                         error=?send_error,
                                         "failed to send error message to stream"
@@ -140,9 +573,9 @@ impl<'c> Claude<'c> {
                             }
                             Err(_) => {
                                 tracing::error!(error=?parse_error, "failed to parse error response from claude");
-                                if let Err(send_error) =
-                                    wx.send(Err(ClaudeError::ParseError(parse_error.to_string())))
-                                {
+                                let error = ClaudeError::ParseError(parse_error.to_string());
+                                telemetry.error(&error);
+                                if let Err(send_error) = wx.send(Err(error)) {
                                     tracing::error!(error=?send_error, "failed to send error message to stream");
                                     break;
                                 }
@@ -152,24 +585,126 @@ impl<'c> Claude<'c> {
                     }
                 };
 
-                let is_stop = match res {
-                    StreamRawPredictResponse::MessageStop => true,
-                    _ => false,
-                };
+                match &res {
+                    StreamRawPredictResponse::MessageStart { message } => {
+                        usage = Some(message.usage.clone());
+                    }
+                    StreamRawPredictResponse::MessageDelta {
+                        delta,
+                        usage: delta_usage,
+                        ..
+                    } => {
+                        if let Some(delta_usage) = delta_usage.as_ref().or(delta.usage.as_ref()) {
+                            usage
+                                .get_or_insert_with(Default::default)
+                                .apply(delta_usage);
+                        }
+                    }
+                    _ => {}
+                }
+                match (&mut response, &res) {
+                    (_, StreamRawPredictResponse::MessageStart { message }) => {
+                        response = Some(message.clone());
+                    }
+                    (Some(response), event) => response.apply(event),
+                    (None, _) => {}
+                }
 
-                if let Err(send_error) = wx.send(Ok(res)) {
+                let is_stop = matches!(res, StreamRawPredictResponse::MessageStop);
+
+                // the event as it was received, with the fields the typed event leaves out
+                let raw = serde_json::from_str::<Value>(&message).unwrap_or_default();
+                if let Err(send_error) = wx.send(Ok((res, raw))) {
                     tracing::error!(error=?send_error, "failed to send response to stream");
                     break;
                 }
 
                 if is_stop {
+                    if let (Some(cache_scope), Some(response)) = (&cache_scope, &response) {
+                        cache_scope.insert(response);
+                    }
                     break;
                 }
             }
-        });
+
+            if let Some(usage) = &usage {
+                permit.reconcile(usage.input_tokens);
+                if let Some(scope) = usage_scope {
+                    scope.record(usage);
+                }
+            }
+            if let Some(response) = &mut response {
+                if let Some(usage) = usage {
+                    response.usage = usage;
+                }
+                record_response(&telemetry, response);
+            }
+        }
+        .instrument(span));
 
         Ok(Box::pin(
             tokio_stream::wrappers::UnboundedReceiverStream::new(rx),
         ))
     }
 }
+
+/// The JSON body of a typed request
+fn request_body(request: &RawPredictRequest) -> Result<Value, ClaudeError> {
+    serde_json::to_value(request).map_err(|e| {
+        tracing::error!(error=?e, "failed to serialize request");
+        ClaudeError::ParseError(format!("failed to serialize request: {}", e))
+    })
+}
+
+/// The typed request of a JSON body, used for the capability checks, rate limits and telemetry of the call
+fn parse_request(body: &Value) -> Result<RawPredictRequest, ClaudeError> {
+    serde_json::from_value(body.clone()).map_err(|e| {
+        tracing::error!(error=?e, "invalid request body");
+        ClaudeError::InvalidRequestError(format!("invalid request: {}", e))
+    })
+}
+
+/// A cached response, which is stored as it was received
+fn cached_response(scope: &CacheScope) -> Option<(RawPredictResponse, Value)> {
+    let raw = scope.get::<Value>()?;
+    let response = serde_json::from_value(raw.clone()).ok()?;
+    Some((response, raw))
+}
+
+/// Records the stop reason, token usage and completion of a successful call
+fn record_response(telemetry: &Telemetry, response: &RawPredictResponse) {
+    let finish_reasons = response
+        .stop_reason
+        .as_ref()
+        .and_then(finish_reason)
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    telemetry.response(
+        &finish_reasons,
+        Some(response.usage.input_tokens),
+        Some(response.usage.output_tokens),
+    );
+    telemetry.completion(|| {
+        Some(
+            response
+                .content
+                .iter()
+                .filter(|block| block.c_type == "text")
+                .map(|block| block.text.as_str())
+                .collect(),
+        )
+    });
+}
+
+/// Rejects requests using features the model is known not to support, before they are sent
+fn check_capabilities(model: &ClaudeModel, request: &RawPredictRequest) -> Result<(), ClaudeError> {
+    let Some(capabilities) = model.capabilities() else {
+        return Ok(());
+    };
+
+    capabilities.check_raw_predict(request).map_err(|e| {
+        tracing::error!(error=%e, model=%model, "request not supported by model");
+        ClaudeError::UnsupportedCapability(e)
+    })
+}
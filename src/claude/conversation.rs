@@ -0,0 +1,307 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    claude::Claude,
+    error::ClaudeError,
+    types::claude::{
+        ClaudeMessage, ClaudeMessageContent, ClaudeModel, ClaudeSystemPrompt, RawPredictRequest,
+        RawPredictResponse, StreamRawPredictResponse, ANTHROPIC_VERSION,
+    },
+};
+
+const USER_ROLE: &str = "user";
+const ASSISTANT_ROLE: &str = "assistant";
+
+/// How a [Conversation] treats two consecutive messages with the same role.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoleSequencePolicy {
+    /// Join the messages into a single turn.
+    /// Text is separated by a blank line, any other content is concatenated as content blocks.
+    #[default]
+    Merge,
+    /// Refuse the message with [ClaudeError::InvalidRequestError].
+    Reject,
+}
+
+/// A stateful multi-turn conversation with a Claude model.
+///
+/// Claude requires messages to start with a user turn and to alternate between
+/// `user` and `assistant`. The conversation enforces this before anything is sent,
+/// and only appends the user turn and the assistant reply once a response with content has been received.
+pub struct Conversation {
+    claude: Claude,
+    model: ClaudeModel,
+    system: ClaudeSystemPrompt,
+    messages: Vec<ClaudeMessage>,
+    max_tokens: u32,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    policy: RoleSequencePolicy,
+}
+
+impl Conversation {
+    pub fn new(claude: Claude, model: ClaudeModel, max_tokens: u32) -> Self {
+        Self {
+            claude,
+            model,
+            system: ClaudeSystemPrompt::Text(String::new()),
+            messages: Vec::new(),
+            max_tokens,
+            temperature: None,
+            top_p: None,
+            policy: RoleSequencePolicy::default(),
+        }
+    }
+
+    pub fn with_system(mut self, system: ClaudeSystemPrompt) -> Self {
+        self.system = system;
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: RoleSequencePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Seeds the conversation with existing messages, normalized according to the [RoleSequencePolicy].
+    pub fn with_history(mut self, messages: Vec<ClaudeMessage>) -> Result<Self, ClaudeError> {
+        self.restore(messages)?;
+        Ok(self)
+    }
+
+    pub fn model(&self) -> &ClaudeModel {
+        &self.model
+    }
+
+    pub fn system(&self) -> &ClaudeSystemPrompt {
+        &self.system
+    }
+
+    /// The messages exchanged so far.
+    pub fn messages(&self) -> &[ClaudeMessage] {
+        &self.messages
+    }
+
+    /// Returns a copy of the current messages which can later be handed to [Conversation::restore].
+    pub fn snapshot(&self) -> Vec<ClaudeMessage> {
+        self.messages.clone()
+    }
+
+    /// Replaces the current messages, normalized according to the [RoleSequencePolicy].
+    pub fn restore(&mut self, messages: Vec<ClaudeMessage>) -> Result<(), ClaudeError> {
+        let mut normalized = Vec::with_capacity(messages.len());
+        for message in messages {
+            push_message(&mut normalized, message, self.policy)?;
+        }
+
+        self.messages = normalized;
+        Ok(())
+    }
+
+    /// Appends a message to the history without sending it.
+    pub fn push(&mut self, message: ClaudeMessage) -> Result<(), ClaudeError> {
+        push_message(&mut self.messages, message, self.policy)
+    }
+
+    /// Sends a user message and waits for the complete reply.
+    pub async fn send(
+        &mut self,
+        content: impl Into<ClaudeMessageContent>,
+    ) -> Result<RawPredictResponse, ClaudeError> {
+        let messages = self.pending(content.into())?;
+
+        let response = self
+            .claude
+            .raw_predict(self.model.clone(), self.request(messages.clone(), false))
+            .await?;
+
+        self.commit(messages, &response);
+
+        Ok(response)
+    }
+
+    /// Sends a user message and streams the reply.
+    /// The exchange is appended to the history once the stream has been fully consumed without errors.
+    pub async fn send_stream<'s>(
+        &'s mut self,
+        content: impl Into<ClaudeMessageContent>,
+    ) -> Result<ConversationStream<'s>, ClaudeError> {
+        let messages = self.pending(content.into())?;
+
+        let inner = self
+            .claude
+            .stream_raw_predict(self.model.clone(), self.request(messages.clone(), true))
+            .await?;
+
+        Ok(ConversationStream {
+            conversation: self,
+            inner,
+            messages: Some(messages),
+            response: None,
+            failed: false,
+        })
+    }
+
+    fn pending(&self, content: ClaudeMessageContent) -> Result<Vec<ClaudeMessage>, ClaudeError> {
+        let mut messages = self.messages.clone();
+        push_message(
+            &mut messages,
+            ClaudeMessage {
+                role: USER_ROLE.to_string(),
+                content,
+            },
+            self.policy,
+        )?;
+
+        Ok(messages)
+    }
+
+    fn request(&self, messages: Vec<ClaudeMessage>, stream: bool) -> RawPredictRequest {
+        RawPredictRequest {
+            anthropic_version: ANTHROPIC_VERSION.to_string(),
+            max_tokens: self.max_tokens,
+            system: self.system.clone(),
+            stream,
+            messages,
+            top_p: self.top_p,
+            temperature: self.temperature,
+            stop_sequences: None,
+            tools: None,
+            tool_choice: None,
+        }
+    }
+
+    fn commit(&mut self, mut messages: Vec<ClaudeMessage>, response: &RawPredictResponse) {
+        if response.content.is_empty() {
+            tracing::warn!("claude returned no content, conversation was not updated");
+            return;
+        }
+
+        messages.push(ClaudeMessage {
+            role: ASSISTANT_ROLE.to_string(),
+            content: ClaudeMessageContent::Blocks(response.content.clone()),
+        });
+        self.messages = messages;
+    }
+}
+
+pub(crate) fn push_message(
+    messages: &mut Vec<ClaudeMessage>,
+    message: ClaudeMessage,
+    policy: RoleSequencePolicy,
+) -> Result<(), ClaudeError> {
+    if message.role != USER_ROLE && message.role != ASSISTANT_ROLE {
+        return Err(ClaudeError::InvalidRequestError(format!(
+            "unknown message role {}",
+            message.role
+        )));
+    }
+
+    let last = match messages.last_mut() {
+        Some(last) => last,
+        None if message.role == USER_ROLE => {
+            messages.push(message);
+            return Ok(());
+        }
+        None => {
+            return Err(ClaudeError::InvalidRequestError(
+                "the first message must use the user role".to_string(),
+            ))
+        }
+    };
+
+    if last.role != message.role {
+        messages.push(message);
+        return Ok(());
+    }
+
+    match policy {
+        RoleSequencePolicy::Merge => {
+            last.content = match (
+                std::mem::replace(&mut last.content, Vec::new().into()),
+                message.content,
+            ) {
+                (ClaudeMessageContent::Text(mut text), ClaudeMessageContent::Text(next)) => {
+                    text.push_str("\n\n");
+                    text.push_str(&next);
+                    ClaudeMessageContent::Text(text)
+                }
+                (content, next) => {
+                    let mut blocks = content.into_blocks();
+                    blocks.extend(next.into_blocks());
+                    ClaudeMessageContent::Blocks(blocks)
+                }
+            };
+            Ok(())
+        }
+        RoleSequencePolicy::Reject => Err(ClaudeError::InvalidRequestError(format!(
+            "roles must alternate between user and assistant, found consecutive {} messages",
+            message.role
+        ))),
+    }
+}
+
+/// Stream returned by [Conversation::send_stream].
+pub struct ConversationStream<'s> {
+    conversation: &'s mut Conversation,
+    inner: Pin<Box<dyn Stream<Item = Result<StreamRawPredictResponse, ClaudeError>> + Send>>,
+    messages: Option<Vec<ClaudeMessage>>,
+    response: Option<RawPredictResponse>,
+    failed: bool,
+}
+
+impl ConversationStream<'_> {
+    /// The response rebuilt from the events received so far.
+    pub fn response(&self) -> Option<&RawPredictResponse> {
+        self.response.as_ref()
+    }
+}
+
+impl Stream for ConversationStream<'_> {
+    type Item = Result<StreamRawPredictResponse, ClaudeError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        match this.inner.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(event))) => {
+                match (&mut this.response, &event) {
+                    (None, StreamRawPredictResponse::MessageStart { message }) => {
+                        this.response = Some(message.clone())
+                    }
+                    (Some(response), event) => response.apply(event),
+                    _ => {}
+                }
+                Poll::Ready(Some(Ok(event)))
+            }
+            Poll::Ready(Some(Err(e))) => {
+                this.failed = true;
+                Poll::Ready(Some(Err(e)))
+            }
+            Poll::Ready(None) => {
+                if let (Some(messages), Some(response)) = (this.messages.take(), &this.response) {
+                    if !this.failed {
+                        this.conversation.commit(messages, response);
+                    }
+                }
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -1,23 +1,319 @@
-use crate::{claude::Claude, config::GeminiConfig, error::ClientError, gemini::Gemini};
+use std::{future::Future, sync::Arc, time::Duration};
 
+use futures::{Stream, StreamExt};
+
+use crate::{
+    batch::Batch,
+    cassette::Cassette,
+    chat::{ChatModel, ClaudeChatModel, GeminiChatModel},
+    claude::Claude,
+    config::{GeminiBackend, GeminiConfig},
+    error::{ClientError, TransportError},
+    gemini::Gemini,
+    imagen::Imagen,
+    limiter::RateLimiter,
+    middleware::{Middleware, RequestContext},
+    response_cache::ResponseCache,
+    router::{RouteTarget, Router},
+    types::{chat::ModelId, content::RequestOptions},
+    usage::UsageRecorder,
+};
+
+/// Shared state of a [Client] and the handles created from it.
+#[derive(Clone)]
+struct ClientInner {
+    http_client: reqwest::Client,
+    config: GeminiConfig,
+    usage_recorder: Option<UsageRecorder>,
+    rate_limiter: Option<RateLimiter>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    options: RequestOptions,
+    capture_content: bool,
+    base_url: Option<String>,
+    cassette: Option<Cassette>,
+    response_cache: Option<ResponseCache>,
+}
+
+/// Entry point for the Gemini and Claude apis.
+///
+/// Cloning is cheap, clones share the http client, configuration, usage recorder and rate limiter.
+#[derive(Clone)]
 pub struct Client {
-    pub http_client: reqwest::Client,
-    pub config: GeminiConfig,
+    inner: Arc<ClientInner>,
 }
 
 impl Client {
     pub fn new(config: GeminiConfig) -> Result<Self, ClientError> {
-        Ok(Self {
-            http_client: reqwest::Client::new(),
-            config,
-        })
+        Self::builder(config).build()
+    }
+
+    pub fn builder(config: GeminiConfig) -> ClientBuilder {
+        ClientBuilder::new(config)
+    }
+
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.inner.http_client
+    }
+
+    pub fn config(&self) -> &GeminiConfig {
+        &self.inner.config
+    }
+
+    /// Records the token usage of every call, if set.
+    pub fn usage_recorder(&self) -> Option<&UsageRecorder> {
+        self.inner.usage_recorder.as_ref()
+    }
+
+    /// Limits the calls per model and region, if set.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.inner.rate_limiter.as_ref()
+    }
+
+    /// Hooks called for every request, in order.
+    pub fn middleware(&self) -> &[Arc<dyn Middleware>] {
+        &self.inner.middleware
+    }
+
+    /// Options applied to every call, unless a handle overrides them with `with_options`.
+    pub fn options(&self) -> &RequestOptions {
+        &self.inner.options
+    }
+
+    /// Whether prompts and completions are emitted as tracing events.
+    pub fn capture_content(&self) -> bool {
+        self.inner.capture_content
+    }
+
+    /// The base url of the api in the given region, the Developer API is not regional
+    pub fn endpoint(&self, location: &str) -> String {
+        match (&self.inner.base_url, self.inner.config.backend()) {
+            (Some(base_url), _) => base_url.trim_end_matches('/').to_string(),
+            (None, GeminiBackend::Vertex) => {
+                format!("https://{}-aiplatform.googleapis.com", location)
+            }
+            (None, GeminiBackend::DeveloperApi) => {
+                "https://generativelanguage.googleapis.com".to_string()
+            }
+        }
+    }
+
+    /// The Anthropic API, or the base url if one is set
+    pub fn anthropic_endpoint(&self) -> String {
+        match &self.inner.base_url {
+            Some(base_url) => base_url.trim_end_matches('/').to_string(),
+            None => "https://api.anthropic.com".to_string(),
+        }
+    }
+
+    /// Caches the responses of calls, if set.
+    pub fn response_cache(&self) -> Option<&ResponseCache> {
+        self.inner.response_cache.as_ref()
+    }
+
+    /// Records or replays every call, if set.
+    pub fn cassette(&self) -> Option<&Cassette> {
+        self.inner.cassette.as_ref()
+    }
+
+    /// Sends a request which went through the middleware, or replays it from the cassette
+    pub(crate) async fn execute(
+        &self,
+        ctx: &RequestContext,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, TransportError> {
+        match &self.inner.cassette {
+            Some(cassette) => cassette.execute(ctx, request).await,
+            None => Ok(request.send().await?),
+        }
+    }
+
+    /// Adds a middleware which sees every request made through this client and its response
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        Arc::make_mut(&mut self.inner)
+            .middleware
+            .push(Arc::new(middleware));
+        self
+    }
+
+    /// Limits the calls made through this client per model and region
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        Arc::make_mut(&mut self.inner).rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Caches the responses of calls made through this client
+    pub fn with_response_cache(mut self, cache: ResponseCache) -> Self {
+        Arc::make_mut(&mut self.inner).response_cache = Some(cache);
+        self
+    }
+
+    /// Records the token usage of every call made through this client
+    pub fn with_usage_recorder(mut self, recorder: UsageRecorder) -> Self {
+        Arc::make_mut(&mut self.inner).usage_recorder = Some(recorder);
+        self
     }
 
     pub fn gemini(&self) -> Gemini {
-        Gemini::new(self)
+        Gemini::new(self.clone())
     }
 
     pub fn claude(&self) -> Claude {
-        Claude::new(self)
+        Claude::new(self.clone())
+    }
+
+    /// Returns a handle for Vertex AI batch prediction jobs
+    pub fn batch(&self) -> Batch {
+        Batch::new(self.clone())
+    }
+
+    /// Returns a handle for image generation with the Imagen models
+    pub fn imagen(&self) -> Imagen {
+        Imagen::new(self.clone())
+    }
+
+    /// Returns a provider independent [ChatModel] for the given model
+    pub fn chat_model(&self, model: ModelId) -> Box<dyn ChatModel> {
+        match model {
+            ModelId::Gemini(model) => Box::new(GeminiChatModel::new(self.gemini(), model)),
+            ModelId::Claude(model) => Box::new(ClaudeChatModel::new(self.claude(), model)),
+        }
+    }
+
+    /// Returns a [ChatModel] sending requests to the given location, or the provider's default location if none is given
+    pub fn chat_model_in(&self, model: ModelId, location: Option<&str>) -> Box<dyn ChatModel> {
+        match (model, location) {
+            (ModelId::Gemini(model), Some(location)) => Box::new(GeminiChatModel::new(
+                self.gemini().with_location(location),
+                model,
+            )),
+            (ModelId::Claude(model), Some(location)) => Box::new(ClaudeChatModel::new(
+                self.claude().with_location(location),
+                model,
+            )),
+            (model, None) => self.chat_model(model),
+        }
+    }
+
+    /// Returns a [Router] which falls back through the given targets in order
+    pub fn router(&self, targets: Vec<RouteTarget>) -> Router {
+        Router::new(self.clone(), targets)
+    }
+}
+
+/// Builds a [Client], e.g. with a preconfigured [reqwest::Client] for proxies, custom root certificates or pool sizes.
+pub struct ClientBuilder {
+    config: GeminiConfig,
+    http_client: Option<reqwest::Client>,
+    usage_recorder: Option<UsageRecorder>,
+    rate_limiter: Option<RateLimiter>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    options: RequestOptions,
+    capture_content: bool,
+    base_url: Option<String>,
+    cassette: Option<Cassette>,
+    response_cache: Option<ResponseCache>,
+}
+
+impl ClientBuilder {
+    pub fn new(config: GeminiConfig) -> Self {
+        Self {
+            config,
+            http_client: None,
+            usage_recorder: None,
+            rate_limiter: None,
+            middleware: Vec::new(),
+            options: RequestOptions::default(),
+            capture_content: false,
+            base_url: None,
+            cassette: None,
+            response_cache: None,
+        }
+    }
+
+    /// Sends requests with the given http client instead of a default one
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    pub fn usage_recorder(mut self, recorder: UsageRecorder) -> Self {
+        self.usage_recorder = Some(recorder);
+        self
+    }
+
+    pub fn rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
     }
+
+    pub fn middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// The default options of every call, such as timeouts and custom headers
+    pub fn options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Emits prompts and completions as `gen_ai.content.*` tracing events, off by default as they may contain sensitive data
+    pub fn capture_content(mut self, capture_content: bool) -> Self {
+        self.capture_content = capture_content;
+        self
+    }
+
+    /// Sends requests to the given url instead of the regional Vertex AI endpoint, e.g. a proxy or a [crate::testing::MockServer]
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Answers repeated calls from the cache, see [crate::response_cache]
+    pub fn response_cache(mut self, cache: ResponseCache) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// Records every call to the cassette, or answers every call from it without network access
+    pub fn cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, ClientError> {
+        Ok(Client {
+            inner: Arc::new(ClientInner {
+                http_client: self.http_client.unwrap_or_default(),
+                config: self.config,
+                usage_recorder: self.usage_recorder,
+                rate_limiter: self.rate_limiter,
+                middleware: self.middleware,
+                options: self.options,
+                capture_content: self.capture_content,
+                base_url: self.base_url,
+                cassette: self.cassette,
+                response_cache: self.response_cache,
+            }),
+        })
+    }
+}
+
+/// Waits for the future, failing if it takes longer than the timeout
+pub(crate) async fn within<F: Future>(
+    future: F,
+    timeout: Option<Duration>,
+) -> Result<F::Output, tokio::time::error::Elapsed> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await,
+        None => Ok(future.await),
+    }
+}
+
+/// Waits for the next item of a stream, failing if it takes longer than the idle timeout
+pub(crate) async fn next_within<S: Stream + Unpin>(
+    stream: &mut S,
+    idle_timeout: Option<Duration>,
+) -> Result<Option<S::Item>, tokio::time::error::Elapsed> {
+    within(stream.next(), idle_timeout).await
 }
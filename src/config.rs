@@ -9,15 +9,44 @@ use crate::error::ClientError;
 pub enum ConfigSource {
     ServiceAccount { account: Arc<CustomServiceAccount> },
     Environment { token: String },
+    Static { token: String },
+    ApiKey { key: String },
+}
+
+/// The api Gemini requests are sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GeminiBackend {
+    /// Vertex AI, authenticated with OAuth tokens for a Google Cloud project.
+    #[default]
+    Vertex,
+    /// The Gemini Developer API at `generativelanguage.googleapis.com`, authenticated with an api key.
+    /// Batch jobs are only available on Vertex AI, Claude models through the Anthropic API, see [GeminiConfig::with_claude_backend].
+    DeveloperApi,
+}
+
+/// The api Claude requests are sent to, parsed from and displayed as `vertex` or `anthropic`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, strum_macros::Display, strum_macros::EnumString,
+)]
+#[strum(serialize_all = "lowercase")]
+pub enum ClaudeBackend {
+    /// Vertex AI, with the model in the url and the Google Cloud credentials of the config.
+    #[default]
+    Vertex,
+    /// The Anthropic API at `api.anthropic.com`, with the model in the body and an Anthropic api key.
+    Anthropic,
 }
 
 const SCOPES: &[&str] = &["https://www.googleapis.com/auth/cloud-platform"];
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GeminiConfig {
     config_source: ConfigSource,
     location: String,
     project_id: String,
+    backend: GeminiBackend,
+    anthropic_api_key: Option<String>,
+    claude_backend: ClaudeBackend,
 }
 
 impl GeminiConfig {
@@ -38,6 +67,9 @@ impl GeminiConfig {
             location: std::env::var("GCP_LOCATION").unwrap_or("us-central1".to_string()),
             project_id: std::env::var("GCP_PROJECT_ID")
                 .map_err(|_| ClientError::MissingEnvVar("GCP_PROJECT_ID".to_string()))?,
+            backend: GeminiBackend::Vertex,
+            anthropic_api_key: None,
+            claude_backend: ClaudeBackend::Vertex,
         })
     }
 
@@ -60,6 +92,9 @@ impl GeminiConfig {
             },
             location: std::env::var("GCP_LOCATION").unwrap_or("us-central1".to_string()),
             project_id,
+            backend: GeminiBackend::Vertex,
+            anthropic_api_key: None,
+            claude_backend: ClaudeBackend::Vertex,
         })
     }
 
@@ -82,9 +117,83 @@ impl GeminiConfig {
             },
             location: std::env::var("GCP_LOCATION").unwrap_or("us-central1".to_string()),
             project_id,
+            backend: GeminiBackend::Vertex,
+            anthropic_api_key: None,
+            claude_backend: ClaudeBackend::Vertex,
         })
     }
 
+    /// Creates a [GeminiConfig] which authenticates every request with the given token, e.g. for tests.
+    /// The location defaults to `us-central1`.
+    pub fn from_static_token(project_id: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            config_source: ConfigSource::Static {
+                token: token.into(),
+            },
+            location: "us-central1".to_string(),
+            project_id: project_id.into(),
+            backend: GeminiBackend::Vertex,
+            anthropic_api_key: None,
+            claude_backend: ClaudeBackend::Vertex,
+        }
+    }
+
+    /// Creates a [GeminiConfig] for the Gemini Developer API, which authenticates with an api key instead of a project.
+    pub fn from_api_key(api_key: impl Into<String>) -> Self {
+        Self {
+            config_source: ConfigSource::ApiKey {
+                key: api_key.into(),
+            },
+            location: "us-central1".to_string(),
+            project_id: String::new(),
+            backend: GeminiBackend::DeveloperApi,
+            anthropic_api_key: None,
+            claude_backend: ClaudeBackend::Vertex,
+        }
+    }
+
+    /// Attempts to load a Gemini Developer API [GeminiConfig] from the `GEMINI_API_KEY` or `GOOGLE_API_KEY` environment variable.
+    pub fn try_from_api_key_env() -> Result<Self, ClientError> {
+        std::env::var("GEMINI_API_KEY")
+            .or_else(|_| std::env::var("GOOGLE_API_KEY"))
+            .map(Self::from_api_key)
+            .map_err(|_| ClientError::MissingEnvVar("GEMINI_API_KEY".to_string()))
+    }
+
+    /// Sets the key for the Anthropic API, which is only used once [ClaudeBackend::Anthropic] is chosen
+    pub fn with_anthropic_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.anthropic_api_key = Some(api_key.into());
+        self
+    }
+
+    /// Sets the key for the Anthropic API from the `ANTHROPIC_API_KEY` environment variable, if it is set
+    pub fn with_anthropic_api_key_env(self) -> Self {
+        match std::env::var("ANTHROPIC_API_KEY") {
+            Ok(key) => self.with_anthropic_api_key(key),
+            Err(_) => self,
+        }
+    }
+
+    pub fn anthropic_api_key(&self) -> Option<&str> {
+        self.anthropic_api_key.as_deref()
+    }
+
+    /// Sends Claude requests to the given api, Vertex AI unless chosen otherwise.
+    /// The Anthropic API also needs a key, see [GeminiConfig::with_anthropic_api_key].
+    pub fn with_claude_backend(mut self, backend: ClaudeBackend) -> Self {
+        self.claude_backend = backend;
+        self
+    }
+
+    /// The api Claude requests are sent to
+    pub fn claude_backend(&self) -> ClaudeBackend {
+        self.claude_backend
+    }
+
+    pub fn backend(&self) -> GeminiBackend {
+        self.backend
+    }
+
     pub fn location(&self) -> &str {
         self.location.as_str()
     }
@@ -93,13 +202,27 @@ impl GeminiConfig {
         self.project_id.as_str()
     }
 
+    /// The OAuth token for Vertex AI, api key configs don't have one
     pub async fn token(&self) -> Result<String> {
         match &self.config_source {
             ConfigSource::ServiceAccount { account } => {
-                let token = account.token(&SCOPES).await?;
+                let token = account.token(SCOPES).await?;
                 Ok(token.as_str().to_string())
             }
-            ConfigSource::Environment { token, .. } => Ok(token.to_owned()),
+            ConfigSource::Environment { token, .. } | ConfigSource::Static { token } => {
+                Ok(token.to_owned())
+            }
+            ConfigSource::ApiKey { .. } => Err(anyhow!(
+                "an api key only authenticates with the Gemini Developer API"
+            )),
+        }
+    }
+
+    /// The header authenticating a request, the api key or the OAuth token as a bearer token
+    pub async fn auth_header(&self) -> Result<(&'static str, String)> {
+        match &self.config_source {
+            ConfigSource::ApiKey { key } => Ok(("x-goog-api-key", key.to_owned())),
+            _ => Ok(("Authorization", format!("Bearer {}", self.token().await?))),
         }
     }
 }
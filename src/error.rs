@@ -27,19 +27,34 @@ pub enum GeminiError {
     ParseError(String),
     #[error("Failed to generate authentication token {0}")]
     AuthenticationError(String),
+    #[error("The prompt was blocked: {0}")]
+    PromptBlocked(String),
+    #[error("The response was blocked: {0}")]
+    ResponseBlocked(String),
+    #[error("The model does not support the request: {0}")]
+    UnsupportedCapability(String),
+    #[error("Usage budget exceeded: {0}")]
+    BudgetExceeded(String),
+    #[error("Client side rate limit reached: {0}")]
+    RateLimited(String),
+    #[error("Cassette error: {0}")]
+    Cassette(String),
+    #[error("Live session failed: {0}")]
+    Live(String),
 }
 
 impl From<usize> for GeminiError {
     fn from(u: usize) -> Self {
         match u {
             400 => Self::InvalidArgument,
-            403 => Self::PermissionDenied,
+            401 | 403 => Self::PermissionDenied,
             404 => Self::NotFound,
             429 => Self::ResourceExhausted,
             499 => Self::Cancelled,
             500 => Self::Internal,
             503 => Self::Unavailable,
             504 => Self::DeadlineExceeded,
+            u if u < 500 => Self::InvalidArgument,
             _ => Self::Internal,
         }
     }
@@ -47,6 +62,10 @@ impl From<usize> for GeminiError {
 
 impl From<reqwest::Error> for GeminiError {
     fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return Self::DeadlineExceeded;
+        }
+
         let mut error = Self::Internal;
         if let Some(e) = e.status() {
             error = GeminiError::from(e.as_u16() as usize)
@@ -56,12 +75,51 @@ impl From<reqwest::Error> for GeminiError {
     }
 }
 
+impl From<TransportError> for GeminiError {
+    fn from(e: TransportError) -> Self {
+        match e {
+            TransportError::Http(e) => e.into(),
+            TransportError::Cassette(e) => GeminiError::Cassette(e),
+        }
+    }
+}
+
 impl From<GenerateContentErrorResponse> for GeminiError {
     fn from(e: GenerateContentErrorResponse) -> Self {
         GeminiError::from(e.error.code)
     }
 }
 
+impl GeminiError {
+    /// Builds the error for a non-success response from its status code and body.
+    pub(crate) fn from_response(status: u16, body: &str) -> Self {
+        if let Ok(e) = serde_json::from_str::<GenerateContentErrorResponse>(body) {
+            return e.into();
+        }
+
+        // streaming endpoints wrap the error in an array
+        if let Ok(mut e) = serde_json::from_str::<Vec<GenerateContentErrorResponse>>(body) {
+            if let Some(e) = e.pop() {
+                return e.into();
+            }
+        }
+
+        GeminiError::from(status as usize)
+    }
+
+    /// Whether the request may succeed when retried later or against another model or region.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            GeminiError::ResourceExhausted
+                | GeminiError::RateLimited(_)
+                | GeminiError::Internal
+                | GeminiError::Unavailable
+                | GeminiError::DeadlineExceeded
+        )
+    }
+}
+
 #[derive(Debug, thiserror::Error, strum_macros::EnumString)]
 pub enum ClaudeError {
     #[error("There was an issue with the format or content of your request {0}")]
@@ -94,11 +152,23 @@ pub enum ClaudeError {
     #[error("failed to parse response from anthropic {0}")]
     #[strum(serialize = "parse_error")]
     ParseError(String),
+    #[error("The model does not support the request: {0}")]
+    #[strum(serialize = "unsupported_capability")]
+    UnsupportedCapability(String),
+    #[error("Usage budget exceeded: {0}")]
+    #[strum(serialize = "budget_exceeded")]
+    BudgetExceeded(String),
+    #[error("The request timed out {0}")]
+    #[strum(serialize = "timeout_error")]
+    Timeout(String),
+    #[error("Cassette error: {0}")]
+    #[strum(serialize = "cassette_error")]
+    Cassette(String),
 }
 
 impl From<RawPredictErrorResponse> for ClaudeError {
     fn from(e: RawPredictErrorResponse) -> Self {
-        match e.e_type.as_str() {
+        match e.error.e_type.as_str() {
             "invalid_request_error" => ClaudeError::InvalidRequestError(e.error.message),
             "authentication_error" => ClaudeError::AuthenticationError(e.error.message),
             "permission_error" => ClaudeError::PermissionError(e.error.message),
@@ -112,8 +182,185 @@ impl From<RawPredictErrorResponse> for ClaudeError {
     }
 }
 
+impl ClaudeError {
+    /// Vertex reports some errors, e.g. quota errors, in the google format instead of the anthropic one.
+    pub(crate) fn from_response(status: u16, body: &str) -> Self {
+        if let Ok(e) = serde_json::from_str::<RawPredictErrorResponse>(body) {
+            return e.into();
+        }
+
+        let message = match serde_json::from_str::<GenerateContentErrorResponse>(body) {
+            Ok(e) => e.error.message,
+            Err(_) => body.to_string(),
+        };
+
+        match status {
+            400 => ClaudeError::InvalidRequestError(message),
+            401 => ClaudeError::AuthenticationError(message),
+            403 => ClaudeError::PermissionError(message),
+            404 => ClaudeError::NotFoundError(message),
+            413 => ClaudeError::RequestTooLarge(message),
+            429 => ClaudeError::RateLimitError(message),
+            500 => ClaudeError::ApiError(message),
+            503 | 529 => ClaudeError::OverloadedError(message),
+            status if status < 500 => ClaudeError::InvalidRequestError(message),
+            _ => ClaudeError::Internal(message),
+        }
+    }
+
+    /// Whether the request may succeed when retried.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ClaudeError::RateLimitError(_)
+                | ClaudeError::ApiError(_)
+                | ClaudeError::OverloadedError(_)
+                | ClaudeError::Internal(_)
+                | ClaudeError::Timeout(_)
+        )
+    }
+}
+
 impl From<reqwest::Error> for ClaudeError {
     fn from(e: reqwest::Error) -> Self {
-        ClaudeError::Internal(e.to_string())
+        match e.is_timeout() {
+            true => ClaudeError::Timeout(e.to_string()),
+            false => ClaudeError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<TransportError> for ClaudeError {
+    fn from(e: TransportError) -> Self {
+        match e {
+            TransportError::Http(e) => e.into(),
+            TransportError::Cassette(e) => ClaudeError::Cassette(e),
+        }
+    }
+}
+
+/// Failure to get a response for a request, before its status is known.
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("{0}")]
+    Cassette(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BatchError {
+    #[error("Batch request failed with status {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("Failed to parse response: {0}")]
+    ParseError(String),
+    #[error("Failed to generate authentication token {0}")]
+    AuthenticationError(String),
+    #[error("Batch job {0} did not finish in time")]
+    Timeout(String),
+    #[error("Cassette error: {0}")]
+    Cassette(String),
+}
+
+impl From<TransportError> for BatchError {
+    fn from(e: TransportError) -> Self {
+        match e {
+            TransportError::Http(e) => e.into(),
+            TransportError::Cassette(e) => BatchError::Cassette(e),
+        }
+    }
+}
+
+impl BatchError {
+    /// Keeps the message of a google error body, or else the whole body.
+    pub(crate) fn from_response(status: u16, body: &str) -> Self {
+        let message = match serde_json::from_str::<GenerateContentErrorResponse>(body) {
+            Ok(e) => e.error.message,
+            Err(_) => body.to_string(),
+        };
+
+        BatchError::Api { status, message }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImagenError {
+    #[error("Imagen request failed with status {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("Failed to parse response: {0}")]
+    ParseError(String),
+    #[error("Failed to generate authentication token {0}")]
+    AuthenticationError(String),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Cassette error: {0}")]
+    Cassette(String),
+}
+
+impl From<TransportError> for ImagenError {
+    fn from(e: TransportError) -> Self {
+        match e {
+            TransportError::Http(e) => e.into(),
+            TransportError::Cassette(e) => ImagenError::Cassette(e),
+        }
+    }
+}
+
+impl ImagenError {
+    /// Keeps the message of a google error body, or else the whole body.
+    pub(crate) fn from_response(status: u16, body: &str) -> Self {
+        let message = match serde_json::from_str::<GenerateContentErrorResponse>(body) {
+            Ok(e) => e.error.message,
+            Err(_) => body.to_string(),
+        };
+
+        ImagenError::Api { status, message }
+    }
+}
+
+/// Failure to turn media into a [crate::types::content::Part].
+#[derive(Debug, thiserror::Error)]
+pub enum MediaError {
+    #[error("Failed to read media: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to detect the MIME type of the media")]
+    UnknownMimeType,
+    #[error("Expected {expected} media, got {mime_type}")]
+    UnexpectedMimeType {
+        expected: &'static str,
+        mime_type: String,
+    },
+    #[error("Inline data of {size} bytes exceeds the limit of {limit} bytes, upload it and pass its uri instead")]
+    TooLarge { size: usize, limit: usize },
+    #[error("{model} does not support {mime_type} input")]
+    Unsupported { model: String, mime_type: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChatError {
+    #[error(transparent)]
+    Gemini(#[from] GeminiError),
+    #[error(transparent)]
+    Claude(#[from] ClaudeError),
+    #[error("Failed to convert message: {0}")]
+    Conversion(String),
+    #[error("Unknown model {0}")]
+    UnknownModel(String),
+    #[error("No route targets configured")]
+    NoRouteTargets,
+}
+
+impl ChatError {
+    /// Whether the provider error may succeed when retried, other errors never do.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ChatError::Gemini(e) => e.is_retryable(),
+            ChatError::Claude(e) => e.is_retryable(),
+            _ => false,
+        }
     }
 }
@@ -1,63 +1,530 @@
+pub mod chat;
+#[cfg(feature = "live")]
+pub mod live;
+
 use std::pin::Pin;
 
-use crate::{client::Client, error::GeminiError, types::content::GenerateContentErrorResponse};
-use futures::{stream::StreamExt, Stream};
-use reqwest_eventsource::{Event, RequestBuilderExt};
+use crate::{
+    client::{next_within, within, Client},
+    config::GeminiBackend,
+    error::GeminiError,
+    limiter::{estimate_generate_content_tokens, RatePermit, TokenEstimate},
+    middleware::{apply_request, notify_event, notify_response, RequestContext},
+    response_cache::{generate_content_chunks, CacheScope, ResponseCache},
+    telemetry::{finish_reason, RequestAttributes, Telemetry, SYSTEM_GEMINI, SYSTEM_VERTEX_AI},
+    types::{
+        chat::ModelId,
+        content::{GenerateContentErrorResponse, RequestOptions},
+    },
+    usage::{Tags, UsageScope},
+};
+use eventsource_stream::{EventStreamError, Eventsource};
+use futures::Stream;
+use serde::Serialize;
+use serde_json::{json, Value};
 use tokio::sync::mpsc;
+use tracing::Instrument;
 
 use crate::types::{
-    content::{GenerateContentRequest, GenerateContentResponse},
-    gemini::GeminiModel,
+    capabilities::Capabilities,
+    content::{
+        CountTokensRequest, CountTokensResponse, GenerateContentRequest, GenerateContentResponse,
+        StartChatParams,
+    },
+    gemini::{DeveloperModelList, GeminiModel, ListModelsResponse, PublisherModelList},
 };
 
-pub struct Gemini<'c> {
-    client: &'c Client,
+use self::chat::ChatSession;
+
+#[derive(Clone)]
+pub struct Gemini {
+    client: Client,
+    location: Option<String>,
+    tags: Tags,
+    options: RequestOptions,
 }
 
-impl<'c> Gemini<'c> {
-    pub fn new(client: &'c Client) -> Self {
-        Self { client }
+impl Gemini {
+    pub fn new(client: Client) -> Self {
+        Self {
+            options: client.options().clone(),
+            client,
+            location: None,
+            tags: Tags::new(),
+        }
     }
 
-    /// Creates a chat response
-    pub async fn generate_content(
+    /// Applies the options, such as timeouts and custom headers, to calls made through this handle instead of the client defaults
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &RequestOptions {
+        &self.options
+    }
+
+    /// Attaches a tag such as `tenant` or `feature` to the usage recorded for calls made through this handle
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Attaches tags to the usage recorded for calls made through this handle
+    pub fn with_tags<K: Into<String>, V: Into<String>>(
+        mut self,
+        tags: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.tags
+            .extend(tags.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    pub fn tags(&self) -> &Tags {
+        &self.tags
+    }
+
+    /// Sends requests to the given region instead of the location of the [crate::config::GeminiConfig]
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    /// The region requests are sent to
+    pub fn location(&self) -> &str {
+        self.location
+            .as_deref()
+            .unwrap_or(self.client.config().location())
+    }
+
+    /// Starts a multi-turn chat session which keeps track of the conversation history
+    pub fn start_chat(&self, model: GeminiModel, params: StartChatParams) -> ChatSession {
+        ChatSession::new(self.clone(), model, params)
+    }
+
+    /// Opens a realtime session with the Live api, see [live]
+    #[cfg(feature = "live")]
+    pub async fn live(
         &self,
         model: GeminiModel,
-        request: GenerateContentRequest,
-    ) -> Result<GenerateContentResponse, GeminiError> {
-        let url = format!("https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/google/models/{}:generateContent",
-            self.client.config.location(),
-            self.client.config.project_id(),
-            self.client.config.location(),
-            model.to_string(),
-        );
+        setup: crate::types::live::LiveSetup,
+    ) -> Result<live::LiveSession, GeminiError> {
+        live::LiveSession::connect(self, model, setup).await
+    }
 
-        let client = self.client.http_client.clone();
+    /// The usage recorder of the client together with the model and tags of a call
+    fn usage_scope(&self, model: &GeminiModel) -> Option<UsageScope> {
+        Some(UsageScope {
+            recorder: self.client.usage_recorder()?.clone(),
+            model: model.clone().into(),
+            tags: self.tags.clone(),
+        })
+    }
 
-        let token = self.client.config.token().await.unwrap();
+    /// The response cache of the client together with the key and cache mode of a call
+    fn cache_scope(
+        &self,
+        model: &GeminiModel,
+        request: &GenerateContentRequest,
+    ) -> Option<CacheScope> {
+        Some(CacheScope {
+            cache: self.client.response_cache()?.clone(),
+            key: ResponseCache::key(
+                match self.client.config().backend() {
+                    GeminiBackend::Vertex => "vertex",
+                    GeminiBackend::DeveloperApi => "developer_api",
+                },
+                self.client.config().project_id(),
+                self.region(model),
+                model,
+                request,
+            ),
+            mode: self.options.cache_mode.unwrap_or_default(),
+        })
+    }
 
-        let res = match client
-            .post(&url)
-            .header("content-type", "application/json; charset=utf-8")
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&request)
-            .send()
+    fn check_budget(&self) -> Result<(), GeminiError> {
+        let Some(recorder) = self.client.usage_recorder() else {
+            return Ok(());
+        };
+
+        recorder.check_budget(&self.tags).map_err(|e| {
+            tracing::error!(error=%e, "usage budget exceeded");
+            GeminiError::BudgetExceeded(e)
+        })
+    }
+
+    /// The region calls to the model are sent to
+    fn region<'m>(&'m self, model: &'m GeminiModel) -> &'m str {
+        model.resource_location().unwrap_or(self.location())
+    }
+
+    /// Takes the quota for the request from the rate limiter of the client, waiting if configured to
+    async fn acquire_permit(
+        &self,
+        model: &GeminiModel,
+        request: &GenerateContentRequest,
+    ) -> Result<RatePermit, GeminiError> {
+        let Some(limiter) = self.client.rate_limiter() else {
+            return Ok(RatePermit::default());
+        };
+
+        let model_id = ModelId::from(model.clone());
+        let region = self.region(model);
+        let tokens = match (
+            limiter.limits_tokens(&model_id, region),
+            limiter.token_estimate(),
+        ) {
+            (false, _) => 0,
+            (true, TokenEstimate::Heuristic) => estimate_generate_content_tokens(request),
+            (true, TokenEstimate::CountTokens) => {
+                self.count_tokens(model.clone(), request)
+                    .await?
+                    .total_tokens
+            }
+        };
+
+        limiter
+            .acquire(&model_id, region, tokens)
             .await
-        {
-            Ok(res) => res,
+            .map_err(|e| {
+                tracing::warn!(error=%e, "rate limit reached");
+                GeminiError::RateLimited(e)
+            })
+    }
+
+    fn url(&self, model: &GeminiModel, method: &str) -> String {
+        if self.client.config().backend() == GeminiBackend::DeveloperApi {
+            return format!(
+                "{}/v1beta/{}:{}",
+                self.client.endpoint(self.location()),
+                developer_api_model(model),
+                method,
+            );
+        }
+
+        // tuned models are addressed by their full resource name in the region they are deployed to
+        if let Some(resource_name) = model.resource_name() {
+            return format!(
+                "{}/v1/{}:{}",
+                self.client.endpoint(self.region(model)),
+                resource_name,
+                method,
+            );
+        }
+
+        format!(
+            "{}/v1/projects/{}/locations/{}/publishers/google/models/{}:{}",
+            self.client.endpoint(self.location()),
+            self.client.config().project_id(),
+            self.location(),
+            model,
+            method,
+        )
+    }
+
+    /// Sends a request through the middleware of the client
+    async fn send(
+        &self,
+        model: &GeminiModel,
+        method: &str,
+        stream: bool,
+        request: &impl Serialize,
+    ) -> Result<(reqwest::Response, RequestContext), GeminiError> {
+        let url = match stream {
+            true => self.url(model, &format!("{}?alt=sse", method)),
+            false => self.url(model, method),
+        };
+
+        let body = serde_json::to_value(request)
+            .map(|body| match self.client.config().backend() {
+                GeminiBackend::Vertex => body,
+                GeminiBackend::DeveloperApi => self.developer_api_body(model, method, body),
+            })
+            .and_then(|body| serde_json::to_string(&body))
+            .map_err(|e| {
+                tracing::error!(error=?e, "failed to serialize request");
+                GeminiError::ParseError(format!("failed to serialize request: {}", e))
+            })?;
+
+        let (auth_header, auth_value) = self.auth_header().await?;
+
+        let ctx = RequestContext {
+            model: Some(model.clone().into()),
+            method: method.to_string(),
+            url,
+            region: self.region(model).to_string(),
+            stream,
+            body,
+            tags: self.tags.clone(),
+        };
+
+        let request = self
+            .client
+            .http_client()
+            .post(&ctx.url)
+            .header("content-type", "application/json; charset=utf-8")
+            .header(auth_header, auth_value)
+            .body(ctx.body.clone());
+        // the body of a stream is bounded by the idle timeout instead
+        let request = match stream {
+            true => self.options.apply_headers(request),
+            false => self.options.apply(request),
+        };
+
+        let request = apply_request(self.client.middleware(), &ctx, request);
+        let response = within(
+            self.client.execute(&ctx, request),
+            self.options.timeout().filter(|_| stream),
+        )
+        .await
+        .map_err(|_| {
+            tracing::error!(timeout=?self.options.timeout(), "no response within the timeout");
+            GeminiError::DeadlineExceeded
+        })?;
+        match response {
+            Ok(res) => Ok((res, ctx)),
             Err(e) => {
                 tracing::error!(error=?e, "failed to send request to google vertex");
-                return Err(e.into());
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn auth_header(&self) -> Result<(&'static str, String), GeminiError> {
+        self.client.config().auth_header().await.map_err(|e| {
+            tracing::error!(error=?e, "failed to get authentication token");
+            GeminiError::AuthenticationError(e.to_string())
+        })
+    }
+
+    /// Adapts a request body to the small differences of the Developer API
+    fn developer_api_body(&self, model: &GeminiModel, method: &str, mut body: Value) -> Value {
+        if let Some(instruction) = body.get_mut("systemInstruction") {
+            // the Developer API only accepts the content form, without a role
+            match instruction {
+                Value::String(text) => *instruction = json!({ "parts": [{ "text": text }] }),
+                Value::Object(content) => {
+                    content.remove("role");
+                }
+                _ => {}
+            }
+        }
+
+        // uploaded files may be referenced by their name, e.g. `files/abc`, the api wants the uri
+        let files_url = format!("{}/v1beta/", self.client.endpoint(self.location()));
+        for content in body
+            .get_mut("contents")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten()
+        {
+            for part in content
+                .get_mut("parts")
+                .and_then(Value::as_array_mut)
+                .into_iter()
+                .flatten()
+            {
+                if let Some(Value::String(uri)) = part.pointer_mut("/fileData/fileUri") {
+                    if uri.starts_with("files/") {
+                        uri.insert_str(0, &files_url);
+                    }
+                }
+            }
+        }
+
+        // count tokens takes the whole generate content request instead of its fields
+        if method == "countTokens" {
+            if let Value::Object(request) = &mut body {
+                request.insert("model".to_string(), json!(developer_api_model(model)));
+            }
+            return json!({ "generateContentRequest": body });
+        }
+
+        body
+    }
+
+    /// Lists a page of the Gemini models available to the backend
+    pub async fn list_models(
+        &self,
+        page_size: Option<u32>,
+        page_token: Option<&str>,
+    ) -> Result<ListModelsResponse, GeminiError> {
+        let backend = self.client.config().backend();
+        let url = match backend {
+            GeminiBackend::Vertex => format!(
+                "{}/v1beta1/publishers/google/models",
+                self.client.endpoint(self.location())
+            ),
+            GeminiBackend::DeveloperApi => {
+                format!("{}/v1beta/models", self.client.endpoint(self.location()))
+            }
+        };
+        let query = [
+            page_size.map(|size| ("pageSize", size.to_string())),
+            page_token.map(|token| ("pageToken", token.to_string())),
+        ];
+        let query = query.into_iter().flatten().collect::<Vec<_>>();
+
+        let (auth_header, auth_value) = self.auth_header().await?;
+
+        let ctx = RequestContext {
+            model: None,
+            method: "models.list".to_string(),
+            url,
+            region: self.location().to_string(),
+            stream: false,
+            body: String::new(),
+            tags: self.tags.clone(),
+        };
+
+        let request = self
+            .client
+            .http_client()
+            .get(&ctx.url)
+            .query(&query)
+            .header(auth_header, auth_value);
+        let request = self.options.apply(request);
+
+        let request = apply_request(self.client.middleware(), &ctx, request);
+        let res = self.client.execute(&ctx, request).await.map_err(|e| {
+            tracing::error!(error=?e, "failed to send request to google");
+            GeminiError::from(e)
+        })?;
+        let body = self.read_body(&ctx, res).await?;
+
+        let parsed = match backend {
+            GeminiBackend::Vertex => {
+                serde_json::from_str::<PublisherModelList>(&body).map(ListModelsResponse::from)
+            }
+            GeminiBackend::DeveloperApi => {
+                serde_json::from_str::<DeveloperModelList>(&body).map(ListModelsResponse::from)
             }
         };
+        parsed.map_err(|e| {
+            tracing::error!(error=?e, "failed to parse models");
+            GeminiError::ParseError(format!("failed to parse response: {}", e))
+        })
+    }
+
+    /// Reads the body of a response, returning an error for non-success responses
+    async fn read_body(
+        &self,
+        ctx: &RequestContext,
+        res: reqwest::Response,
+    ) -> Result<String, GeminiError> {
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.text().await.map_err(|e| {
+            tracing::error!(error=?e, "failed to read response from google vertex");
+            GeminiError::from(e)
+        })?;
+
+        notify_response(self.client.middleware(), ctx, status, &headers, Some(&body));
+
+        if !status.is_success() {
+            let error = GeminiError::from_response(status.as_u16(), &body);
+            tracing::error!(error=?error, status=%status, method=%ctx.method, "request failed");
+            return Err(error);
+        }
+
+        Ok(body)
+    }
+
+    /// Counts the tokens of a request without generating a response
+    pub async fn count_tokens(
+        &self,
+        model: GeminiModel,
+        request: &GenerateContentRequest,
+    ) -> Result<CountTokensResponse, GeminiError> {
+        let (res, ctx) = self
+            .send(
+                &model,
+                "countTokens",
+                false,
+                &CountTokensRequest::from(request),
+            )
+            .await?;
+        let body = self.read_body(&ctx, res).await?;
 
-        let json = res.json::<serde_json::Value>().await.map_err(|e| {
+        serde_json::from_str::<CountTokensResponse>(&body).map_err(|e| {
             tracing::error!(error=?e, "failed to parse response from google vertex");
-            GeminiError::ParseError(e.to_string())
+            GeminiError::ParseError(format!("failed to parse response: {}", e))
+        })
+    }
+
+    /// Starts the telemetry of a call, see [crate::telemetry]
+    fn telemetry(&self, model: &GeminiModel, request: &GenerateContentRequest) -> Telemetry {
+        let config = request.base_model_params.generation_config.as_ref();
+        let system = match self.client.config().backend() {
+            GeminiBackend::Vertex => SYSTEM_VERTEX_AI,
+            GeminiBackend::DeveloperApi => SYSTEM_GEMINI,
+        };
+        let telemetry = Telemetry::start(
+            system,
+            model,
+            RequestAttributes {
+                max_tokens: config.and_then(|c| c.max_output_tokens),
+                temperature: config.and_then(|c| c.temperature),
+                top_p: config.and_then(|c| c.top_p),
+            },
+            self.client.capture_content(),
+        );
+        telemetry.prompt(request, self.client.middleware());
+        telemetry
+    }
+
+    /// Creates a chat response
+    pub async fn generate_content(
+        &self,
+        model: GeminiModel,
+        request: GenerateContentRequest,
+    ) -> Result<GenerateContentResponse, GeminiError> {
+        let telemetry = self.telemetry(&model, &request);
+        let result = self
+            .generate_content_inner(model, request)
+            .instrument(telemetry.span().clone())
+            .await;
+
+        match &result {
+            Ok(response) => record_response(&telemetry, response),
+            Err(e) => telemetry.error(e),
+        }
+        result
+    }
+
+    async fn generate_content_inner(
+        &self,
+        model: GeminiModel,
+        request: GenerateContentRequest,
+    ) -> Result<GenerateContentResponse, GeminiError> {
+        check_capabilities(&model, &request)?;
+        let cache_scope = self.cache_scope(&model, &request);
+        if let Some(response) = cache_scope.as_ref().and_then(CacheScope::get) {
+            return Ok(response);
+        }
+        self.check_budget()?;
+        let permit = self.acquire_permit(&model, &request).await?;
+
+        let (res, ctx) = self
+            .send(&model, "generateContent", false, &request)
+            .await?;
+        let body = self.read_body(&ctx, res).await?;
+
+        let response = serde_json::from_str::<GenerateContentResponse>(&body).map_err(|e| {
+            tracing::error!(error=?e, "failed to parse response from google vertex");
+            GeminiError::ParseError(format!("failed to parse response: {}", e))
         })?;
 
-        let response = serde_json::from_value::<GenerateContentResponse>(json)
-            .map_err(|e| GeminiError::ParseError(format!("failed to parse response: {}", e)))?;
+        if let Some(usage) = &response.usage_metadata {
+            permit.reconcile(usage.prompt_token_count.unwrap_or_default());
+            if let Some(scope) = self.usage_scope(&model) {
+                scope.record(usage);
+            }
+        }
+        if let Some(cache_scope) = &cache_scope {
+            cache_scope.insert(&response);
+        }
 
         Ok(response)
     }
@@ -72,96 +539,202 @@ impl<'c> Gemini<'c> {
         Pin<Box<dyn Stream<Item = Result<GenerateContentResponse, GeminiError>> + Send + 'static>>,
         GeminiError,
     > {
-        let url = format!("https://{}-aiplatform.googleapis.com/v1/projects/{}/locations/{}/publishers/google/models/{}:streamGenerateContent?alt=sse",
-            self.client.config.location(),
-            self.client.config.project_id(),
-            self.client.config.location(),
-            model.to_string(),
-        );
+        let telemetry = self.telemetry(&model, &request);
+        let result = self
+            .stream_generate_content_inner(model, request, &telemetry)
+            .instrument(telemetry.span().clone())
+            .await;
 
-        let client = self.client.http_client.clone();
+        if let Err(e) = &result {
+            telemetry.error(e);
+        }
+        result
+    }
 
-        let token = self.client.config.token().await.map_err(|e| {
-            tracing::error!(error=?e, "failed to get authentication token");
-            GeminiError::AuthenticationError(e.to_string())
-        })?;
+    async fn stream_generate_content_inner(
+        &self,
+        model: GeminiModel,
+        request: GenerateContentRequest,
+        telemetry: &Telemetry,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<GenerateContentResponse, GeminiError>> + Send + 'static>>,
+        GeminiError,
+    > {
+        check_capabilities(&model, &request)?;
+        let cache_scope = self.cache_scope(&model, &request);
+        if let Some(response) = cache_scope
+            .as_ref()
+            .and_then(CacheScope::get::<GenerateContentResponse>)
+        {
+            record_response(telemetry, &response);
+            let chunks = generate_content_chunks(response).into_iter().map(Ok);
+            return Ok(Box::pin(futures::stream::iter(chunks)));
+        }
+        self.check_budget()?;
+        let permit = self.acquire_permit(&model, &request).await?;
+
+        let (res, ctx) = self
+            .send(&model, "streamGenerateContent", true, &request)
+            .await?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let headers = res.headers().clone();
+            let body = res.text().await.unwrap_or_default();
+            notify_response(
+                self.client.middleware(),
+                &ctx,
+                status,
+                &headers,
+                Some(&body),
+            );
+            let error = GeminiError::from_response(status.as_u16(), &body);
+            tracing::error!(error=?error, status=%status, "stream generate content failed");
+            return Err(error);
+        }
+        notify_response(self.client.middleware(), &ctx, status, res.headers(), None);
 
         let (wx, rx) = mpsc::unbounded_channel();
-        let api_key = token;
+        let usage_scope = self.usage_scope(&model);
+        let middleware = self.client.middleware().to_vec();
+        let idle_timeout = self.options.stream_idle_timeout();
+        let telemetry = telemetry.clone();
+        let span = telemetry.span().clone();
 
         tokio::spawn(async move {
-            let mut source = match client
-                .post(&url)
-                .header("content-type", "application/json; charset=utf-8")
-                .header("Authorization", format!("Bearer {}", api_key))
-                .json(&request)
-                .eventsource()
-            {
-                Ok(res) => res,
+            let mut source = res.bytes_stream().eventsource();
+            // chunks report the cumulative usage, so the merged response holds the final usage once the stream ends
+            let mut response = GenerateContentResponse::default();
+            let mut first_event = true;
 
-                Err(e) => {
-                    tracing::error!(error=?e, "failed to send request to google vertex");
-                    return;
-                }
-            };
+            loop {
+                let sse_event = match next_within(&mut source, idle_timeout).await {
+                    Ok(Some(sse_event)) => sse_event,
+                    Ok(None) => {
+                        if let Some(cache_scope) = &cache_scope {
+                            cache_scope.insert(&response);
+                        }
+                        break;
+                    }
+                    Err(_) => {
+                        tracing::error!(idle_timeout=?idle_timeout, "stream idle timeout elapsed");
+                        telemetry.error(&GeminiError::DeadlineExceeded);
+                        if let Err(send_error) = wx.send(Err(GeminiError::DeadlineExceeded)) {
+                            tracing::error!(error=?send_error, "failed to send error message to stream");
+                        }
+                        return;
+                    }
+                };
 
-            while let Some(sse_event) = source.next().await {
                 let event = match sse_event {
-                    Ok(e) => match e {
-                        Event::Message(m) => m,
-                        Event::Open => {
-                            tracing::trace!("received open event");
-                            continue;
-                        }
-                    },
+                    Ok(e) => e,
                     Err(e) => {
                         tracing::error!(error=?e, "failed to read chunk from google vertex");
+                        let error = match e {
+                            EventStreamError::Transport(e) => GeminiError::from(e),
+                            e => GeminiError::ParseError(e.to_string()),
+                        };
+                        telemetry.error(&error);
+                        if let Err(send_error) = wx.send(Err(error)) {
+                            tracing::error!(error=?send_error, "failed to send error message to stream");
+                        }
                         return;
                     }
                 };
 
                 let message = event.data;
+                notify_event(&middleware, &ctx, &message);
+                if std::mem::take(&mut first_event) {
+                    telemetry.first_token();
+                }
+
+                // Error messages would also parse as an empty response, so check for them first
+                if let Ok(c) = serde_json::from_str::<GenerateContentErrorResponse>(&message) {
+                    tracing::error!(error=?c, "generate content failed");
+                    let error = GeminiError::from(c);
+                    telemetry.error(&error);
+                    if let Err(send_error) = wx.send(Err(error)) {
+                        tracing::error!(error=?send_error, "failed to send error message to stream");
+                    }
+                    return;
+                }
 
                 let res = match serde_json::from_str::<GenerateContentResponse>(&message) {
                     Ok(c) => c,
                     Err(parse_error) => {
-                        // Check if the message is an error message
-                        match serde_json::from_str::<GenerateContentErrorResponse>(&message) {
-                            Ok(c) => {
-                                tracing::error!(error=?c, "generate content failed");
-                                if let Err(send_error) = wx.send(Err(c.into())) {
-                                    tracing::error!(
-                                        error=?send_error,
-                                        "failed to send error message to stream"
-                                    );
-                                    break;
-                                }
-                                return;
-                            }
-                            // this is not an error message
-                            Err(_) => {
-                                tracing::error!(error=?parse_error, "failed to parse response from google vertex");
-                                if let Err(send_error) =
-                                    wx.send(Err(GeminiError::ParseError(parse_error.to_string())))
-                                {
-                                    tracing::error!(error=?send_error, "failed to send error message to stream");
-                                    break;
-                                }
-                                return;
-                            }
-                        };
+                        tracing::error!(error=?parse_error, "failed to parse response from google vertex");
+                        let error = GeminiError::ParseError(parse_error.to_string());
+                        telemetry.error(&error);
+                        if let Err(send_error) = wx.send(Err(error)) {
+                            tracing::error!(error=?send_error, "failed to send error message to stream");
+                        }
+                        return;
                     }
                 };
 
+                response.merge(res.clone());
+
                 if let Err(send_error) = wx.send(Ok(res)) {
                     tracing::error!(error=?send_error, "failed to send response to stream");
                     break;
                 }
             }
-        });
+
+            if let Some(usage) = &response.usage_metadata {
+                permit.reconcile(usage.prompt_token_count.unwrap_or_default());
+                if let Some(scope) = usage_scope {
+                    scope.record(usage);
+                }
+            }
+            record_response(&telemetry, &response);
+        }
+        .instrument(span));
 
         Ok(Box::pin(
             tokio_stream::wrappers::UnboundedReceiverStream::new(rx),
         ))
     }
 }
+
+/// Records the finish reasons, token usage and completion of a successful call
+fn record_response(telemetry: &Telemetry, response: &GenerateContentResponse) {
+    let finish_reasons = response
+        .candidates
+        .iter()
+        .flatten()
+        .filter_map(|candidate| candidate.finish_reason.as_ref())
+        .filter_map(finish_reason)
+        .collect::<Vec<_>>();
+    let usage = response.usage_metadata.as_ref();
+
+    telemetry.response(
+        &finish_reasons,
+        usage.and_then(|u| u.prompt_token_count),
+        usage.and_then(|u| u.candidates_token_count),
+    );
+    telemetry.completion(|| response.text());
+}
+
+/// Rejects requests using features the model is known not to support, before they are sent
+fn check_capabilities(
+    model: &GeminiModel,
+    request: &GenerateContentRequest,
+) -> Result<(), GeminiError> {
+    let Some(capabilities) = model.capabilities() else {
+        return Ok(());
+    };
+
+    capabilities.check_generate_content(request).map_err(|e| {
+        tracing::error!(error=%e, model=%model, "request not supported by model");
+        GeminiError::UnsupportedCapability(e)
+    })
+}
+
+/// The model resource in the Developer API, e.g. `models/gemini-1.5-flash-002` or a `tunedModels/...` name
+fn developer_api_model(model: &GeminiModel) -> String {
+    let model = model.to_string();
+    match model.starts_with("models/") || model.starts_with("tunedModels/") {
+        true => model,
+        false => format!("models/{}", model),
+    }
+}
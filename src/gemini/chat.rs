@@ -0,0 +1,228 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    error::GeminiError,
+    gemini::Gemini,
+    types::{
+        content::{
+            BaseModelParams, Content, GenerateContentRequest, GenerateContentResponse,
+            GenerationConfig, Part, SafetySetting, StartChatParams, SystemInstruction, Tool,
+            ToolConfig,
+        },
+        gemini::GeminiModel,
+    },
+};
+
+const USER_ROLE: &str = "user";
+const MODEL_ROLE: &str = "model";
+
+/// A stateful multi-turn conversation with a Gemini model.
+///
+/// Every call sends the accumulated history together with the new message.
+/// The user turn and the model reply are only appended to the history once a
+/// response with content has been received, so failed, blocked or empty
+/// responses leave the history untouched.
+pub struct ChatSession {
+    gemini: Gemini,
+    model: GeminiModel,
+    history: Vec<Content>,
+    safety_settings: Option<Vec<SafetySetting>>,
+    generation_config: Option<GenerationConfig>,
+    tools: Option<Vec<Tool>>,
+    tool_config: Option<ToolConfig>,
+    system_instruction: Option<SystemInstruction>,
+    cached_content: Option<String>,
+}
+
+impl ChatSession {
+    pub fn new(gemini: Gemini, model: GeminiModel, params: StartChatParams) -> Self {
+        Self {
+            gemini,
+            model,
+            history: params.history.unwrap_or_default(),
+            safety_settings: params.safety_settings,
+            generation_config: params.generation_config,
+            tools: params.tools,
+            tool_config: params.tool_config,
+            system_instruction: params.system_instruction,
+            cached_content: params.cached_content,
+        }
+    }
+
+    pub fn model(&self) -> &GeminiModel {
+        &self.model
+    }
+
+    /// The turns exchanged so far.
+    pub fn history(&self) -> &[Content] {
+        &self.history
+    }
+
+    /// Returns a copy of the current history which can later be handed to [ChatSession::restore].
+    pub fn snapshot(&self) -> Vec<Content> {
+        self.history.clone()
+    }
+
+    /// Replaces the current history with a previously taken snapshot.
+    pub fn restore(&mut self, history: Vec<Content>) {
+        self.history = history;
+    }
+
+    /// Sends a message and waits for the complete reply.
+    pub async fn send_message(
+        &mut self,
+        parts: Vec<Part>,
+    ) -> Result<GenerateContentResponse, GeminiError> {
+        let user = Content {
+            parts,
+            role: USER_ROLE.to_string(),
+        };
+
+        let response = self
+            .gemini
+            .generate_content(self.model.clone(), self.request(&user))
+            .await?;
+
+        self.commit(user, &response)?;
+
+        Ok(response)
+    }
+
+    /// Sends a message and streams the reply.
+    /// The exchange is appended to the history once the stream has been fully consumed without errors.
+    pub async fn send_message_stream<'s>(
+        &'s mut self,
+        parts: Vec<Part>,
+    ) -> Result<ChatSessionStream<'s>, GeminiError> {
+        let user = Content {
+            parts,
+            role: USER_ROLE.to_string(),
+        };
+
+        let inner = self
+            .gemini
+            .stream_generate_content(self.model.clone(), self.request(&user))
+            .await?;
+
+        Ok(ChatSessionStream {
+            session: self,
+            inner,
+            user: Some(user),
+            response: GenerateContentResponse::default(),
+            failed: false,
+        })
+    }
+
+    fn request(&self, user: &Content) -> GenerateContentRequest {
+        let mut contents = self.history.clone();
+        contents.push(user.clone());
+
+        GenerateContentRequest {
+            base_model_params: BaseModelParams {
+                safety_settings: self.safety_settings.clone(),
+                generation_config: self.generation_config.clone(),
+                tools: self.tools.clone(),
+                tool_config: self.tool_config.clone(),
+                system_instruction: self.system_instruction.clone(),
+            },
+            contents,
+            cached_content: self.cached_content.clone(),
+        }
+    }
+
+    fn commit(
+        &mut self,
+        user: Content,
+        response: &GenerateContentResponse,
+    ) -> Result<(), GeminiError> {
+        if let Some(feedback) = &response.prompt_feedback {
+            if let Some(reason) = &feedback.block_reason {
+                return Err(GeminiError::PromptBlocked(format!(
+                    "{:?} {}",
+                    reason,
+                    feedback.block_reason_message.as_deref().unwrap_or_default()
+                )));
+            }
+        }
+
+        let candidate = response
+            .candidates
+            .as_ref()
+            .and_then(|candidates| candidates.first());
+        if let Some(reason) = candidate
+            .and_then(|candidate| candidate.finish_reason.as_ref())
+            .filter(|reason| reason.is_blocked())
+        {
+            return Err(GeminiError::ResponseBlocked(format!("{:?}", reason)));
+        }
+
+        let reply = candidate
+            .and_then(|candidate| candidate.content.as_ref())
+            .filter(|content| !content.parts.is_empty());
+
+        match reply {
+            Some(content) => {
+                self.history.push(user);
+                self.history.push(Content {
+                    parts: content.parts.clone(),
+                    role: MODEL_ROLE.to_string(),
+                });
+            }
+            None => tracing::warn!("gemini returned no content, chat history was not updated"),
+        }
+
+        Ok(())
+    }
+}
+
+/// Stream returned by [ChatSession::send_message_stream].
+pub struct ChatSessionStream<'s> {
+    session: &'s mut ChatSession,
+    inner: Pin<Box<dyn Stream<Item = Result<GenerateContentResponse, GeminiError>> + Send>>,
+    user: Option<Content>,
+    response: GenerateContentResponse,
+    failed: bool,
+}
+
+impl ChatSessionStream<'_> {
+    /// The response aggregated from the chunks received so far.
+    pub fn response(&self) -> &GenerateContentResponse {
+        &self.response
+    }
+}
+
+impl Stream for ChatSessionStream<'_> {
+    type Item = Result<GenerateContentResponse, GeminiError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        match this.inner.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                this.response.merge(chunk.clone());
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(Some(Err(e))) => {
+                this.failed = true;
+                Poll::Ready(Some(Err(e)))
+            }
+            Poll::Ready(None) => {
+                let user = match this.user.take() {
+                    Some(user) if !this.failed => user,
+                    _ => return Poll::Ready(None),
+                };
+
+                match this.session.commit(user, &this.response) {
+                    Ok(()) => Poll::Ready(None),
+                    Err(e) => Poll::Ready(Some(Err(e))),
+                }
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! The Live api, a bidirectional WebSocket session for realtime text, audio and video. Enabled by the `live` feature.
+//!
+//! The session starts with a [LiveSetup] and then takes [LiveClientMessage]s while yielding [LiveServerMessage]s,
+//! in any order. Use [LiveSession::split] to send audio while receiving the replies on another task.
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, Stream, StreamExt,
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{
+        self,
+        client::IntoClientRequest,
+        http::{HeaderName, HeaderValue},
+        protocol::frame::coding::CloseCode,
+        Message,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::{
+    config::GeminiBackend,
+    error::GeminiError,
+    types::{
+        content::{Content, GenerativeContentBlob, Part},
+        gemini::GeminiModel,
+        live::{
+            LiveClientContent, LiveClientMessage, LiveFunctionResponse, LiveRealtimeInput,
+            LiveServerMessage, LiveSetup, LiveToolResponse,
+        },
+    },
+    usage::UsageScope,
+};
+
+use super::{developer_api_model, Gemini};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A connected Live session, see [Gemini::live].
+pub struct LiveSession {
+    sender: LiveSender,
+    receiver: LiveReceiver,
+}
+
+impl LiveSession {
+    /// Connects, sends the setup and waits until the server accepted it
+    pub(crate) async fn connect(
+        gemini: &Gemini,
+        model: GeminiModel,
+        mut setup: LiveSetup,
+    ) -> Result<Self, GeminiError> {
+        gemini.check_budget()?;
+        setup.model = gemini.live_model(&model);
+
+        let connect = Self::handshake(gemini, model, setup);
+        match gemini.options.timeout {
+            Some(timeout) => tokio::time::timeout(Duration::from_millis(timeout), connect)
+                .await
+                .map_err(|_| {
+                    tracing::error!("live session setup timed out");
+                    GeminiError::DeadlineExceeded
+                })?,
+            None => connect.await,
+        }
+    }
+
+    async fn handshake(
+        gemini: &Gemini,
+        model: GeminiModel,
+        setup: LiveSetup,
+    ) -> Result<Self, GeminiError> {
+        let mut request = gemini.live_url().into_client_request().map_err(|e| {
+            tracing::error!(error=?e, "invalid live url");
+            GeminiError::Live(e.to_string())
+        })?;
+
+        let (auth_header, auth_value) = gemini.auth_header().await?;
+        let headers = [(auth_header.to_string(), auth_value)]
+            .into_iter()
+            .chain(gemini.options.custom_headers.clone().into_iter().flatten());
+        for (name, value) in headers {
+            let header = HeaderName::try_from(name.as_str())
+                .map_err(|e| e.to_string())
+                .and_then(|header| {
+                    let value = HeaderValue::try_from(value.as_str()).map_err(|e| e.to_string())?;
+                    Ok((header, value))
+                });
+            let (name, value) = header.map_err(|e| {
+                tracing::error!(error=%e, header=%name, "invalid live header");
+                GeminiError::Live(format!("invalid header {}: {}", name, e))
+            })?;
+            request.headers_mut().insert(name, value);
+        }
+
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| {
+                tracing::error!(error=?e, "failed to connect to the live api");
+                match e {
+                    tungstenite::Error::Http(response) => {
+                        GeminiError::from(response.status().as_u16() as usize)
+                    }
+                    e => GeminiError::Live(e.to_string()),
+                }
+            })?;
+
+        let (sink, stream) = socket.split();
+        let mut session = Self {
+            sender: LiveSender { sink },
+            receiver: LiveReceiver {
+                stream,
+                usage_scope: gemini.usage_scope(&model),
+            },
+        };
+
+        session
+            .sender
+            .send(LiveClientMessage::Setup(Box::new(setup)))
+            .await?;
+        match session.receiver.next().await {
+            Some(Ok(message)) if message.setup_complete.is_some() => Ok(session),
+            Some(Ok(message)) => Err(GeminiError::Live(format!(
+                "expected setupComplete, got {:?}",
+                message
+            ))),
+            Some(Err(e)) => Err(e),
+            None => Err(GeminiError::Live(
+                "connection closed before the setup completed".to_string(),
+            )),
+        }
+    }
+
+    pub fn sender(&mut self) -> &mut LiveSender {
+        &mut self.sender
+    }
+
+    /// Waits for the next message of the server, `None` once the session is closed
+    pub async fn next(&mut self) -> Option<Result<LiveServerMessage, GeminiError>> {
+        self.receiver.next().await
+    }
+
+    /// Splits the session so sending and receiving can happen on different tasks
+    pub fn split(self) -> (LiveSender, LiveReceiver) {
+        (self.sender, self.receiver)
+    }
+
+    pub async fn close(mut self) -> Result<(), GeminiError> {
+        self.sender.close().await
+    }
+}
+
+/// The sending half of a [LiveSession].
+pub struct LiveSender {
+    sink: SplitSink<Socket, Message>,
+}
+
+impl LiveSender {
+    pub async fn send(&mut self, message: LiveClientMessage) -> Result<(), GeminiError> {
+        let message = serde_json::to_string(&message).map_err(|e| {
+            tracing::error!(error=?e, "failed to serialize live message");
+            GeminiError::ParseError(format!("failed to serialize live message: {}", e))
+        })?;
+
+        self.sink.send(Message::Text(message)).await.map_err(|e| {
+            tracing::error!(error=?e, "failed to send live message");
+            GeminiError::Live(e.to_string())
+        })
+    }
+
+    /// Appends turns to the conversation, the model responds once a turn is complete
+    pub async fn send_client_content(
+        &mut self,
+        turns: Vec<Content>,
+        turn_complete: bool,
+    ) -> Result<(), GeminiError> {
+        self.send(LiveClientMessage::ClientContent(LiveClientContent {
+            turns,
+            turn_complete,
+        }))
+        .await
+    }
+
+    /// Sends a complete user turn with the text
+    pub async fn send_text(&mut self, text: impl Into<String>) -> Result<(), GeminiError> {
+        let turn = Content {
+            parts: vec![Part::from(text.into())],
+            role: "user".to_string(),
+        };
+        self.send_client_content(vec![turn], true).await
+    }
+
+    pub async fn send_realtime_input(
+        &mut self,
+        media_chunks: Vec<GenerativeContentBlob>,
+    ) -> Result<(), GeminiError> {
+        self.send(LiveClientMessage::RealtimeInput(LiveRealtimeInput {
+            media_chunks,
+        }))
+        .await
+    }
+
+    /// Streams raw media, e.g. a `image/jpeg` video frame
+    pub async fn send_media(
+        &mut self,
+        mime_type: impl Into<String>,
+        data: &[u8],
+    ) -> Result<(), GeminiError> {
+        self.send_realtime_input(vec![GenerativeContentBlob {
+            mime_type: mime_type.into(),
+            data: STANDARD.encode(data),
+        }])
+        .await
+    }
+
+    /// Streams a chunk of 16-bit little-endian PCM audio with the given sample rate, usually 16000
+    pub async fn send_audio(&mut self, pcm: &[u8], sample_rate: u32) -> Result<(), GeminiError> {
+        self.send_media(format!("audio/pcm;rate={}", sample_rate), pcm)
+            .await
+    }
+
+    /// Answers the function calls of a [crate::types::live::LiveToolCall]
+    pub async fn send_tool_response(
+        &mut self,
+        function_responses: Vec<LiveFunctionResponse>,
+    ) -> Result<(), GeminiError> {
+        self.send(LiveClientMessage::ToolResponse(LiveToolResponse {
+            function_responses,
+        }))
+        .await
+    }
+
+    pub async fn close(&mut self) -> Result<(), GeminiError> {
+        self.sink
+            .close()
+            .await
+            .map_err(|e| GeminiError::Live(e.to_string()))
+    }
+}
+
+/// The receiving half of a [LiveSession].
+pub struct LiveReceiver {
+    stream: SplitStream<Socket>,
+    usage_scope: Option<UsageScope>,
+}
+
+impl LiveReceiver {
+    /// Waits for the next message of the server, `None` once the session is closed.
+    /// Reported usage is recorded by the usage recorder of the client.
+    pub async fn next(&mut self) -> Option<Result<LiveServerMessage, GeminiError>> {
+        loop {
+            let data = match self.stream.next().await? {
+                Ok(Message::Text(text)) => text.into_bytes(),
+                Ok(Message::Binary(data)) => data,
+                Ok(Message::Close(Some(frame))) if frame.code != CloseCode::Normal => {
+                    tracing::error!(code=%frame.code, reason=%frame.reason, "live session closed by the server");
+                    return Some(Err(GeminiError::Live(format!(
+                        "closed by the server: {}",
+                        frame.reason
+                    ))));
+                }
+                Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => return None,
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::error!(error=?e, "failed to read live message");
+                    return Some(Err(GeminiError::Live(e.to_string())));
+                }
+            };
+
+            let message = serde_json::from_slice::<LiveServerMessage>(&data).map_err(|e| {
+                tracing::error!(error=?e, "failed to parse live message");
+                GeminiError::ParseError(format!("failed to parse live message: {}", e))
+            });
+
+            if let (Ok(message), Some(scope)) = (&message, &self.usage_scope) {
+                if let Some(usage) = &message.usage_metadata {
+                    scope.record(usage);
+                }
+            }
+            return Some(message);
+        }
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = Result<LiveServerMessage, GeminiError>> {
+        futures::stream::unfold(self, |mut receiver| async move {
+            let message = receiver.next().await?;
+            Some((message, receiver))
+        })
+    }
+}
+
+impl Gemini {
+    /// The WebSocket url of the Live api
+    fn live_url(&self) -> String {
+        let endpoint = self.client.endpoint(self.location());
+        let endpoint = match endpoint.split_once("://") {
+            Some(("http", host)) => format!("ws://{}", host),
+            Some((_, host)) => format!("wss://{}", host),
+            None => endpoint,
+        };
+
+        match self.client.config().backend() {
+            GeminiBackend::Vertex => format!(
+                "{}/ws/google.cloud.aiplatform.v1beta1.LlmBidiService/BidiGenerateContent",
+                endpoint
+            ),
+            GeminiBackend::DeveloperApi => format!(
+                "{}/ws/google.ai.generativelanguage.v1beta.GenerativeService.BidiGenerateContent",
+                endpoint
+            ),
+        }
+    }
+
+    /// The model as named in the [LiveSetup]
+    fn live_model(&self, model: &GeminiModel) -> String {
+        if self.client.config().backend() == GeminiBackend::DeveloperApi {
+            return developer_api_model(model);
+        }
+
+        match model.resource_name() {
+            Some(resource_name) => resource_name.to_string(),
+            None => format!(
+                "projects/{}/locations/{}/publishers/google/models/{}",
+                self.client.config().project_id(),
+                self.location(),
+                model,
+            ),
+        }
+    }
+}
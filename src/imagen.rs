@@ -0,0 +1,189 @@
+//! Image generation, editing and upscaling with the Imagen models.
+//!
+//! Editing and upscaling are only available on Vertex AI, generation also works with the Developer API.
+
+use crate::{
+    client::Client,
+    config::GeminiBackend,
+    error::ImagenError,
+    middleware::{apply_request, notify_response, RequestContext},
+    types::{
+        chat::ModelId,
+        content::RequestOptions,
+        gemini::GeminiModel,
+        imagen::{
+            EditImageRequest, GenerateImagesRequest, GenerateImagesResponse, ImagenModel, MaskMode,
+            PredictRequest, PredictResponse, UpscaleImageRequest,
+        },
+    },
+    usage::Tags,
+};
+
+#[derive(Clone)]
+pub struct Imagen {
+    client: Client,
+    location: Option<String>,
+    options: RequestOptions,
+}
+
+impl Imagen {
+    pub fn new(client: Client) -> Self {
+        Self {
+            options: client.options().clone(),
+            client,
+            location: None,
+        }
+    }
+
+    /// Applies the options, such as timeouts and custom headers, to calls made through this handle instead of the client defaults
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &RequestOptions {
+        &self.options
+    }
+
+    /// Sends requests to the given region instead of the location of the [crate::config::GeminiConfig]
+    pub fn with_location(mut self, location: impl Into<String>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    /// The region requests are sent to
+    pub fn location(&self) -> &str {
+        self.location
+            .as_deref()
+            .unwrap_or(self.client.config().location())
+    }
+
+    fn url(&self, model: &ImagenModel) -> String {
+        match self.client.config().backend() {
+            GeminiBackend::Vertex => format!(
+                "{}/v1/projects/{}/locations/{}/publishers/google/models/{}:predict",
+                self.client.endpoint(self.location()),
+                self.client.config().project_id(),
+                self.location(),
+                model,
+            ),
+            GeminiBackend::DeveloperApi => format!(
+                "{}/v1beta/models/{}:predict",
+                self.client.endpoint(self.location()),
+                model,
+            ),
+        }
+    }
+
+    fn require_vertex(&self, operation: &str) -> Result<(), ImagenError> {
+        match self.client.config().backend() {
+            GeminiBackend::Vertex => Ok(()),
+            GeminiBackend::DeveloperApi => Err(ImagenError::InvalidRequest(format!(
+                "{} is only available on Vertex AI",
+                operation
+            ))),
+        }
+    }
+
+    async fn predict(
+        &self,
+        model: &ImagenModel,
+        request: PredictRequest,
+    ) -> Result<GenerateImagesResponse, ImagenError> {
+        let (auth_header, auth_value) = self.client.config().auth_header().await.map_err(|e| {
+            tracing::error!(error=?e, "failed to get authentication token");
+            ImagenError::AuthenticationError(e.to_string())
+        })?;
+
+        let body = serde_json::to_string(&request).map_err(|e| {
+            tracing::error!(error=?e, "failed to serialize request");
+            ImagenError::ParseError(format!("failed to serialize request: {}", e))
+        })?;
+
+        let ctx = RequestContext {
+            model: Some(ModelId::Gemini(GeminiModel::Custom(model.to_string()))),
+            method: "predict".to_string(),
+            url: self.url(model),
+            region: self.location().to_string(),
+            stream: false,
+            body,
+            tags: Tags::default(),
+        };
+
+        let request = self
+            .client
+            .http_client()
+            .post(&ctx.url)
+            .header("content-type", "application/json; charset=utf-8")
+            .header(auth_header, auth_value)
+            .body(ctx.body.clone());
+        let request = self.options.apply(request);
+
+        let request = apply_request(self.client.middleware(), &ctx, request);
+        let res = self.client.execute(&ctx, request).await.map_err(|e| {
+            tracing::error!(error=?e, "failed to send request to imagen");
+            ImagenError::from(e)
+        })?;
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.text().await?;
+
+        notify_response(
+            self.client.middleware(),
+            &ctx,
+            status,
+            &headers,
+            Some(&body),
+        );
+
+        if !status.is_success() {
+            let error = ImagenError::from_response(status.as_u16(), &body);
+            tracing::error!(error=?error, status=%status, model=%model, "imagen request failed");
+            return Err(error);
+        }
+
+        let response = serde_json::from_str::<PredictResponse>(&body).map_err(|e| {
+            tracing::error!(error=?e, "failed to parse imagen response");
+            ImagenError::ParseError(format!("failed to parse response: {}", e))
+        })?;
+        GenerateImagesResponse::try_from(response).map_err(|e| {
+            tracing::error!(error=?e, "failed to decode image");
+            ImagenError::ParseError(format!("failed to decode image: {}", e))
+        })
+    }
+
+    /// Generates images from a prompt, e.g. with [ImagenModel::Imagen3Generate002]
+    pub async fn generate_images(
+        &self,
+        model: ImagenModel,
+        request: GenerateImagesRequest,
+    ) -> Result<GenerateImagesResponse, ImagenError> {
+        self.predict(&model, PredictRequest::from(&request)).await
+    }
+
+    /// Edits the masked area of an image, e.g. with [ImagenModel::Imagen3Capability001]
+    pub async fn edit_image(
+        &self,
+        model: ImagenModel,
+        request: EditImageRequest,
+    ) -> Result<GenerateImagesResponse, ImagenError> {
+        self.require_vertex("image editing")?;
+        if request.mask_mode == MaskMode::UserProvided && request.mask.is_none() {
+            return Err(ImagenError::InvalidRequest(
+                "a user provided mask mode needs a mask image".to_string(),
+            ));
+        }
+
+        self.predict(&model, PredictRequest::from(&request)).await
+    }
+
+    /// Upscales an image, e.g. with [ImagenModel::Imagen3Generate002]
+    pub async fn upscale_image(
+        &self,
+        model: ImagenModel,
+        request: UpscaleImageRequest,
+    ) -> Result<GenerateImagesResponse, ImagenError> {
+        self.require_vertex("upscaling")?;
+        self.predict(&model, PredictRequest::from(&request)).await
+    }
+}
@@ -1,6 +1,20 @@
+pub mod batch;
+pub mod cassette;
+pub mod chat;
 pub mod claude;
 pub mod client;
 pub mod config;
 pub mod error;
 pub mod gemini;
+pub mod imagen;
+pub mod limiter;
+pub mod middleware;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+pub mod response_cache;
+pub mod router;
+pub mod telemetry;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod types;
+pub mod usage;
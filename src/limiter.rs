@@ -0,0 +1,412 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::types::{
+    chat::ModelId,
+    claude::{ClaudeMessageContent, ClaudeSystemPrompt, RawPredictRequest},
+    content::{GenerateContentRequest, Part, SystemInstruction},
+};
+
+/// Quota of a model in a single region.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_minute: Option<u32>,
+    /// Input tokens per minute.
+    pub tokens_per_minute: Option<u32>,
+    /// The maximum number of calls in flight at the same time, streams count until they end.
+    pub max_concurrency: Option<usize>,
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_requests_per_minute(mut self, requests_per_minute: u32) -> Self {
+        self.requests_per_minute = Some(requests_per_minute);
+        self
+    }
+
+    pub fn with_tokens_per_minute(mut self, tokens_per_minute: u32) -> Self {
+        self.tokens_per_minute = Some(tokens_per_minute);
+        self
+    }
+
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency);
+        self
+    }
+}
+
+/// What to do with a call which would exceed the quota.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitBehavior {
+    /// Wait until the quota allows the call.
+    #[default]
+    Wait,
+    /// Reject the call with a rate limit error.
+    FailFast,
+}
+
+/// How the input tokens of a call are determined for the tokens per minute limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEstimate {
+    /// Estimate the tokens from the length of the text, about four characters per token.
+    #[default]
+    Heuristic,
+    /// Ask the `countTokens` api before every Gemini call, Claude calls are estimated.
+    CountTokens,
+}
+
+/// Tokens attributed to media parts, which can't be estimated from their size.
+const MEDIA_PART_TOKENS: u32 = 258;
+
+/// Estimates the input tokens of a Gemini request.
+pub fn estimate_generate_content_tokens(request: &GenerateContentRequest) -> u32 {
+    let system_parts = match &request.base_model_params.system_instruction {
+        Some(SystemInstruction::Content(content)) => content.parts.as_slice(),
+        Some(SystemInstruction::Text(text)) => {
+            return estimate_text(text) + estimate_parts(request)
+        }
+        None => &[],
+    };
+
+    estimate_parts(request) + system_parts.iter().map(estimate_part).sum::<u32>()
+}
+
+fn estimate_parts(request: &GenerateContentRequest) -> u32 {
+    request
+        .contents
+        .iter()
+        .flat_map(|content| content.parts.iter())
+        .map(estimate_part)
+        .sum()
+}
+
+fn estimate_part(part: &Part) -> u32 {
+    match part {
+        Part::TextPart(part) => estimate_text(&part.text),
+        Part::InlineDataPart(_) | Part::FileDataPart(_) => MEDIA_PART_TOKENS,
+        Part::FunctionCallPart(part) => estimate_text(&part.function_call.args.to_string()),
+        Part::FunctionResponsePart(part) => {
+            estimate_text(&part.function_response.response.to_string())
+        }
+    }
+}
+
+/// Estimates the input tokens of a Claude request.
+pub fn estimate_raw_predict_tokens(request: &RawPredictRequest) -> u32 {
+    let system = match &request.system {
+        ClaudeSystemPrompt::Text(text) => estimate_text(text),
+        ClaudeSystemPrompt::Content(blocks) => blocks.iter().map(|b| estimate_text(&b.text)).sum(),
+    };
+
+    let messages = request
+        .messages
+        .iter()
+        .map(|message| match &message.content {
+            ClaudeMessageContent::Text(text) => estimate_text(text),
+            ClaudeMessageContent::Blocks(blocks) => blocks
+                .iter()
+                .map(
+                    |block| match (&block.source, &block.input, &block.content) {
+                        (Some(_), _, _) => MEDIA_PART_TOKENS,
+                        (_, Some(value), _) | (_, _, Some(value)) => {
+                            estimate_text(&value.to_string())
+                        }
+                        _ => estimate_text(&block.text),
+                    },
+                )
+                .sum(),
+        })
+        .sum::<u32>();
+
+    system + messages
+}
+
+fn estimate_text(text: &str) -> u32 {
+    (text.chars().count() as u32).div_ceil(4)
+}
+
+struct Bucket {
+    capacity: f64,
+    level: f64,
+    per_second: f64,
+}
+
+impl Bucket {
+    fn per_minute(limit: u32) -> Self {
+        Self {
+            capacity: limit as f64,
+            level: limit as f64,
+            per_second: limit as f64 / 60.0,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.level = (self.level + elapsed.as_secs_f64() * self.per_second).min(self.capacity);
+    }
+
+    /// Time until the bucket holds the amount, which is capped at the capacity.
+    /// [Duration::MAX] if it never will, as the limit is zero.
+    fn wait_for(&self, amount: f64) -> Duration {
+        if self.per_second <= 0.0 {
+            return match amount > 0.0 {
+                true => Duration::MAX,
+                false => Duration::ZERO,
+            };
+        }
+
+        let missing = amount.min(self.capacity) - self.level;
+        match missing > 0.0 {
+            true => Duration::from_secs_f64(missing / self.per_second),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    updated: Instant,
+}
+
+struct LimitState {
+    buckets: Mutex<Buckets>,
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl LimitState {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            buckets: Mutex::new(Buckets {
+                requests: limit.requests_per_minute.map(Bucket::per_minute),
+                tokens: limit.tokens_per_minute.map(Bucket::per_minute),
+                updated: Instant::now(),
+            }),
+            semaphore: limit
+                .max_concurrency
+                .map(|n| Arc::new(Semaphore::new(n.max(1)))),
+        }
+    }
+
+    /// Takes a request and the tokens from the buckets, or returns how long to wait until they are available.
+    fn try_take(&self, tokens: u32) -> Result<(), Duration> {
+        let mut guard = self.buckets.lock().unwrap();
+        let buckets = &mut *guard;
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(buckets.updated);
+        buckets.updated = now;
+        for bucket in [&mut buckets.requests, &mut buckets.tokens]
+            .into_iter()
+            .flatten()
+        {
+            bucket.refill(elapsed);
+        }
+
+        let wait = buckets
+            .requests
+            .as_ref()
+            .map_or(Duration::ZERO, |b| b.wait_for(1.0))
+            .max(
+                buckets
+                    .tokens
+                    .as_ref()
+                    .map_or(Duration::ZERO, |b| b.wait_for(tokens as f64)),
+            );
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        if let Some(bucket) = &mut buckets.requests {
+            bucket.level -= 1.0;
+        }
+        if let Some(bucket) = &mut buckets.tokens {
+            bucket.level -= (tokens as f64).min(bucket.capacity);
+        }
+
+        Ok(())
+    }
+
+    /// Corrects the token bucket once the actual token count of a call is known.
+    fn reconcile(&self, estimated: u32, actual: u32) {
+        if let Some(bucket) = &mut self.buckets.lock().unwrap().tokens {
+            // the level may go negative, delaying the next calls until the overshoot has been refilled
+            bucket.level -= actual as f64 - (estimated as f64).min(bucket.capacity);
+        }
+    }
+}
+
+#[derive(Default)]
+struct LimiterState {
+    limits: HashMap<ModelId, RateLimit>,
+    region_limits: HashMap<(ModelId, String), RateLimit>,
+    states: HashMap<(ModelId, String), Arc<LimitState>>,
+}
+
+/// Client side requests per minute, tokens per minute and concurrency limits for each model and region.
+///
+/// Clones share the same quota.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    state: Arc<Mutex<LimiterState>>,
+    behavior: LimitBehavior,
+    token_estimate: TokenEstimate,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the model in every region, each region has its own quota.
+    pub fn with_limit(self, model: impl Into<ModelId>, limit: RateLimit) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .limits
+            .insert(model.into(), limit);
+        self
+    }
+
+    /// Limits the model in a single region, overriding [RateLimiter::with_limit].
+    pub fn with_region_limit(
+        self,
+        model: impl Into<ModelId>,
+        region: impl Into<String>,
+        limit: RateLimit,
+    ) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .region_limits
+            .insert((model.into(), region.into()), limit);
+        self
+    }
+
+    /// Whether calls exceeding the quota wait or fail, defaults to [LimitBehavior::Wait].
+    pub fn with_behavior(mut self, behavior: LimitBehavior) -> Self {
+        self.behavior = behavior;
+        self
+    }
+
+    /// How input tokens are counted, defaults to [TokenEstimate::Heuristic].
+    pub fn with_token_estimate(mut self, token_estimate: TokenEstimate) -> Self {
+        self.token_estimate = token_estimate;
+        self
+    }
+
+    pub fn behavior(&self) -> LimitBehavior {
+        self.behavior
+    }
+
+    pub fn token_estimate(&self) -> TokenEstimate {
+        self.token_estimate
+    }
+
+    /// The limit applying to the model in the region, if any.
+    pub fn limit(&self, model: &ModelId, region: &str) -> Option<RateLimit> {
+        let state = self.state.lock().unwrap();
+        state
+            .region_limits
+            .get(&(model.clone(), region.to_string()))
+            .or_else(|| state.limits.get(model))
+            .copied()
+    }
+
+    /// Whether the model has a tokens per minute limit in the region.
+    pub fn limits_tokens(&self, model: &ModelId, region: &str) -> bool {
+        self.limit(model, region)
+            .is_some_and(|limit| limit.tokens_per_minute.is_some())
+    }
+
+    fn limit_state(&self, model: &ModelId, region: &str) -> Option<Arc<LimitState>> {
+        let limit = self.limit(model, region)?;
+        let mut state = self.state.lock().unwrap();
+        let limit_state = state
+            .states
+            .entry((model.clone(), region.to_string()))
+            .or_insert_with(|| Arc::new(LimitState::new(&limit)));
+
+        Some(limit_state.clone())
+    }
+
+    /// Waits until the quota allows a call with the given input tokens, or fails if the limiter fails fast.
+    /// The returned permit has to be held until the call is done.
+    pub async fn acquire(
+        &self,
+        model: &ModelId,
+        region: &str,
+        tokens: u32,
+    ) -> Result<RatePermit, String> {
+        let Some(state) = self.limit_state(model, region) else {
+            return Ok(RatePermit::default());
+        };
+
+        let concurrency = match (&state.semaphore, self.behavior) {
+            (None, _) => None,
+            (Some(semaphore), LimitBehavior::Wait) => semaphore.clone().acquire_owned().await.ok(),
+            (Some(semaphore), LimitBehavior::FailFast) => {
+                Some(semaphore.clone().try_acquire_owned().map_err(|_| {
+                    format!("concurrency limit for {} in {} reached", model, region)
+                })?)
+            }
+        };
+
+        loop {
+            match state.try_take(tokens) {
+                Ok(()) => break,
+                Err(Duration::MAX) => {
+                    return Err(format!(
+                        "rate limit for {} in {} allows no calls",
+                        model, region
+                    ));
+                }
+                Err(wait) if self.behavior == LimitBehavior::FailFast => {
+                    return Err(format!(
+                        "rate limit for {} in {} reached, retry in {:.1}s",
+                        model,
+                        region,
+                        wait.as_secs_f64()
+                    ));
+                }
+                Err(wait) => {
+                    tracing::debug!(model=%model, region=%region, wait=?wait, "waiting for rate limit");
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+
+        Ok(RatePermit {
+            state: Some(state),
+            estimated: tokens,
+            _concurrency: concurrency,
+        })
+    }
+}
+
+/// Quota taken by a call, the concurrency slot is released when it is dropped.
+#[derive(Default)]
+pub struct RatePermit {
+    state: Option<Arc<LimitState>>,
+    estimated: u32,
+    _concurrency: Option<OwnedSemaphorePermit>,
+}
+
+impl RatePermit {
+    /// Corrects the estimated input tokens with the actual count reported by the api.
+    pub fn reconcile(&self, actual_tokens: u32) {
+        if let Some(state) = &self.state {
+            state.reconcile(self.estimated, actual_tokens);
+        }
+    }
+}
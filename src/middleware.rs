@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use reqwest::{header::HeaderMap, RequestBuilder, StatusCode};
+use serde_json::Value;
+
+use crate::{types::chat::ModelId, usage::Tags};
+
+/// Describes a call made through a [crate::client::Client], passed to every [Middleware] hook.
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+    /// `None` for calls which don't target a model, e.g. listing models or managing batch jobs.
+    pub model: Option<ModelId>,
+    /// The api method, e.g. `generateContent` or `streamRawPredict`.
+    pub method: String,
+    pub url: String,
+    pub region: String,
+    pub stream: bool,
+    /// The raw JSON request body.
+    pub body: String,
+    pub tags: Tags,
+}
+
+/// Hooks called for every call made through a [crate::client::Client].
+/// Middleware runs in the order it was added.
+pub trait Middleware: Send + Sync {
+    /// Called before the request is sent, e.g. to add headers or sign the request.
+    fn on_request(&self, _ctx: &RequestContext, request: RequestBuilder) -> RequestBuilder {
+        request
+    }
+
+    /// Called with the response, the body is `None` for successful streams whose events are passed to [Middleware::on_event].
+    fn on_response(
+        &self,
+        _ctx: &RequestContext,
+        _status: StatusCode,
+        _headers: &HeaderMap,
+        _body: Option<&str>,
+    ) {
+    }
+
+    /// Called with the raw data of each server sent event of a stream.
+    fn on_event(&self, _ctx: &RequestContext, _data: &str) {}
+
+    /// Called with the request before it is logged as a prompt event, e.g. to redact personal data.
+    /// Only the logged copy is changed, see [crate::client::ClientBuilder::capture_content].
+    fn redact_prompt(&self, _prompt: &mut Value) {}
+}
+
+pub(crate) fn apply_request(
+    middleware: &[Arc<dyn Middleware>],
+    ctx: &RequestContext,
+    request: RequestBuilder,
+) -> RequestBuilder {
+    middleware
+        .iter()
+        .fold(request, |request, m| m.on_request(ctx, request))
+}
+
+pub(crate) fn notify_response(
+    middleware: &[Arc<dyn Middleware>],
+    ctx: &RequestContext,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: Option<&str>,
+) {
+    for m in middleware {
+        m.on_response(ctx, status, headers, body);
+    }
+}
+
+pub(crate) fn redact_prompt(middleware: &[Arc<dyn Middleware>], prompt: &mut Value) {
+    for m in middleware {
+        m.redact_prompt(prompt);
+    }
+}
+
+pub(crate) fn notify_event(middleware: &[Arc<dyn Middleware>], ctx: &RequestContext, data: &str) {
+    for m in middleware {
+        m.on_event(ctx, data);
+    }
+}
+
+/// Adds static headers, and optionally the tags of the handle, to every request.
+#[derive(Clone, Debug, Default)]
+pub struct HeaderMiddleware {
+    headers: Vec<(String, String)>,
+    tag_prefix: Option<String>,
+}
+
+impl HeaderMiddleware {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Bills requests to the given project, for credentials which belong to another project.
+    pub fn with_user_project(self, project_id: impl Into<String>) -> Self {
+        self.with_header("x-goog-user-project", project_id)
+    }
+
+    /// Sends each tag of the handle as a header named `{prefix}{key}`.
+    pub fn with_tag_headers(mut self, prefix: impl Into<String>) -> Self {
+        self.tag_prefix = Some(prefix.into());
+        self
+    }
+}
+
+impl Middleware for HeaderMiddleware {
+    fn on_request(&self, ctx: &RequestContext, mut request: RequestBuilder) -> RequestBuilder {
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        if let Some(prefix) = &self.tag_prefix {
+            for (key, value) in &ctx.tags {
+                request = request.header(format!("{}{}", prefix, key), value);
+            }
+        }
+
+        request
+    }
+}
@@ -0,0 +1,215 @@
+//! The Anthropic Messages api in front of [crate::claude::Claude].
+//!
+//! The body is forwarded as it is, fields [RawPredictRequest] doesn't know included, except that the model
+//! moves from the body into the Vertex url and the `anthropic_version` is set to [ANTHROPIC_VERSION].
+//! Anthropic model ids and aliases are mapped to Vertex ids, see [ClaudeModel::from_anthropic_id].
+//! Responses and stream events are passed through as they arrive.
+
+use std::{convert::Infallible, str::FromStr};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use crate::{
+    config::ClaudeBackend,
+    error::ClaudeError,
+    types::claude::{ClaudeModel, RawPredictRequest, ANTHROPIC_VERSION},
+};
+
+use super::{claude_status, Proxy};
+
+pub(crate) async fn messages(State(proxy): State<Proxy>, body: Bytes) -> Response {
+    let (model, body) = match messages_request(&body) {
+        Ok(request) => request,
+        Err(message) => {
+            return error_response(StatusCode::BAD_REQUEST, "invalid_request_error", message)
+        }
+    };
+
+    let claude = proxy.client().claude();
+    // Vertex AI only serves dated model versions, undated ids are only known to the Anthropic API
+    if let (ClaudeBackend::Vertex, ClaudeModel::Custom(id)) = (claude.backend(), &model) {
+        if !id.contains('@') {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                format!(
+                    "model {} is not available on Vertex AI, use a dated id such as claude-3-5-sonnet-20241022",
+                    id
+                ),
+            );
+        }
+    }
+
+    let model_name = model.anthropic_id();
+    match body
+        .get("stream")
+        .and_then(Value::as_bool)
+        .unwrap_or_default()
+    {
+        true => match claude.stream_raw_predict_json(model, body).await {
+            Ok(events) => sse(events, model_name).into_response(),
+            Err(e) => claude_error_response(&e),
+        },
+        false => match claude.raw_predict_json(model, body).await {
+            Ok(mut response) => {
+                with_model(&mut response, &model_name);
+                Json(response).into_response()
+            }
+            Err(e) => claude_error_response(&e),
+        },
+    }
+}
+
+/// Splits a Messages api body into the model and the body sent to Vertex,
+/// which is checked against [RawPredictRequest] but keeps the fields it doesn't know
+pub fn messages_request(body: &[u8]) -> Result<(ClaudeModel, Value), String> {
+    let mut body = serde_json::from_slice::<Value>(body)
+        .map_err(|e| format!("invalid request body: {}", e))?;
+    let Some(object) = body.as_object_mut() else {
+        return Err("request body must be an object".to_string());
+    };
+
+    let model = match object.remove("model") {
+        // Anthropic clients send Anthropic ids, Vertex ids are accepted as well
+        Some(Value::String(model)) if model.contains('@') => {
+            ClaudeModel::from_str(&model).map_err(|_| format!("unknown model {}", model))?
+        }
+        Some(Value::String(model)) => ClaudeModel::from_anthropic_id(&model),
+        _ => return Err("model: field required".to_string()),
+    };
+
+    object.insert("anthropic_version".to_string(), json!(ANTHROPIC_VERSION));
+    object.entry("stream").or_insert(json!(false));
+    object.entry("system").or_insert(json!(""));
+
+    serde_json::from_value::<RawPredictRequest>(body.clone())
+        .map_err(|e| format!("invalid request: {}", e))?;
+    Ok((model, body))
+}
+
+/// Vertex responses don't name the model, Anthropic clients expect it
+fn with_model(message: &mut Value, model: &str) {
+    if let Some(message) = message.as_object_mut() {
+        message
+            .entry("model")
+            .or_insert_with(|| Value::String(model.to_string()));
+    }
+}
+
+fn event(mut data: Value, model: &str) -> Event {
+    let name = data
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or("message")
+        .to_string();
+    if name == "message_start" {
+        if let Some(message) = data.get_mut("message") {
+            with_model(message, model);
+        }
+    }
+
+    Event::default().event(name).data(data.to_string())
+}
+
+/// Passes the events through with their type as the event name, ending with an `error` event if the stream fails
+fn sse(
+    events: impl futures::Stream<Item = Result<Value, ClaudeError>> + Send + 'static,
+    model: String,
+) -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
+    let events = events.scan(false, move |failed, item| {
+        // the stream is over after the first error
+        if *failed {
+            return futures::future::ready(None);
+        }
+
+        let event = match item {
+            Ok(item) => event(item, &model),
+            Err(e) => {
+                *failed = true;
+                tracing::error!(error=%e, model=%model, "message stream failed");
+                Event::default()
+                    .event("error")
+                    .data(error_body(error_type(&e), message(&e)).to_string())
+            }
+        };
+        futures::future::ready(Some(Ok(event)))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+fn error_body(e_type: &str, message: String) -> Value {
+    json!({
+        "type": "error",
+        "error": {
+            "type": e_type,
+            "message": message,
+        }
+    })
+}
+
+/// Renders an error in the Anthropic format
+pub(crate) fn error_response(status: StatusCode, e_type: &str, message: String) -> Response {
+    (status, Json(error_body(e_type, message))).into_response()
+}
+
+fn claude_error_response(e: &ClaudeError) -> Response {
+    tracing::error!(error=%e, "message request failed");
+    let status = match e {
+        // anthropic reports overload with its own status code
+        ClaudeError::OverloadedError(_) => StatusCode::from_u16(529).unwrap_or(claude_status(e)),
+        _ => claude_status(e),
+    };
+    error_response(status, error_type(e), message(e))
+}
+
+/// The Anthropic error type of an error, errors raised by this crate are reported as the closest type
+fn error_type(e: &ClaudeError) -> &'static str {
+    match e {
+        ClaudeError::InvalidRequestError(_) | ClaudeError::UnsupportedCapability(_) => {
+            "invalid_request_error"
+        }
+        ClaudeError::AuthenticationError(_) => "authentication_error",
+        ClaudeError::PermissionError(_) => "permission_error",
+        ClaudeError::NotFoundError(_) => "not_found_error",
+        ClaudeError::RequestTooLarge(_) => "request_too_large",
+        ClaudeError::RateLimitError(_) | ClaudeError::BudgetExceeded(_) => "rate_limit_error",
+        ClaudeError::OverloadedError(_) => "overloaded_error",
+        ClaudeError::Timeout(_) => "timeout_error",
+        ClaudeError::ApiError(_)
+        | ClaudeError::Internal(_)
+        | ClaudeError::ParseError(_)
+        | ClaudeError::Cassette(_) => "api_error",
+    }
+}
+
+/// The message of an error without the description of its variant
+fn message(e: &ClaudeError) -> String {
+    match e {
+        ClaudeError::InvalidRequestError(m)
+        | ClaudeError::UnsupportedCapability(m)
+        | ClaudeError::AuthenticationError(m)
+        | ClaudeError::PermissionError(m)
+        | ClaudeError::NotFoundError(m)
+        | ClaudeError::RequestTooLarge(m)
+        | ClaudeError::RateLimitError(m)
+        | ClaudeError::ApiError(m)
+        | ClaudeError::OverloadedError(m)
+        | ClaudeError::Internal(m)
+        | ClaudeError::ParseError(m)
+        | ClaudeError::BudgetExceeded(m)
+        | ClaudeError::Timeout(m)
+        | ClaudeError::Cassette(m) => m.clone(),
+    }
+}
@@ -0,0 +1,152 @@
+//! An http server exposing the Gemini and Claude models through the OpenAI Chat Completions and
+//! Anthropic Messages apis, for tools which only speak one of those. Enabled by the `proxy` feature.
+//!
+//! - `POST /v1/chat/completions`, streamed if the request sets `stream`
+//! - `GET /v1/models`
+//! - `POST /v1/messages`, for Claude models only
+//!
+//! Chat completions are routed to Gemini or Claude by their model id, see [crate::types::chat::ModelId].
+
+pub mod anthropic;
+pub mod openai;
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+    routing::{get, post},
+    Router,
+};
+
+use crate::{
+    chat::ChatModel,
+    client::Client,
+    error::{ChatError, ClaudeError, GeminiError},
+    types::chat::ModelId,
+};
+
+#[derive(Clone)]
+pub struct Proxy {
+    client: Client,
+    api_keys: Arc<Vec<String>>,
+}
+
+impl Proxy {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            api_keys: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Only accepts requests with one of the keys, sent as a bearer token or in the `x-api-key` header.
+    /// Without keys every request is accepted.
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.api_keys).push(key.into());
+        self
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The routes of the proxy, which can be merged into a larger application
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/v1/chat/completions", post(openai::chat_completions))
+            .route("/v1/models", get(openai::models))
+            .route("/v1/messages", post(anthropic::messages))
+            .layer(axum::middleware::from_fn_with_state(
+                self.clone(),
+                authenticate,
+            ))
+            .with_state(self)
+    }
+
+    /// Serves the proxy until the listener fails
+    pub async fn serve(self, listener: tokio::net::TcpListener) -> std::io::Result<()> {
+        axum::serve(listener, self.router()).await
+    }
+
+    fn chat_model(&self, model: &str) -> Result<Box<dyn ChatModel>, ChatError> {
+        Ok(self.client.chat_model(model.parse::<ModelId>()?))
+    }
+
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        if self.api_keys.is_empty() {
+            return true;
+        }
+
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let api_key = headers
+            .get("x-api-key")
+            .and_then(|value| value.to_str().ok());
+
+        [bearer, api_key]
+            .into_iter()
+            .flatten()
+            .any(|key| self.api_keys.iter().any(|k| k == key))
+    }
+}
+
+async fn authenticate(State(proxy): State<Proxy>, request: Request, next: Next) -> Response {
+    if proxy.is_authorized(request.headers()) {
+        return next.run(request).await;
+    }
+
+    let message = "invalid api key".to_string();
+    match request.uri().path().starts_with("/v1/messages") {
+        true => {
+            anthropic::error_response(StatusCode::UNAUTHORIZED, "authentication_error", message)
+        }
+        false => openai::error_response(StatusCode::UNAUTHORIZED, message),
+    }
+}
+
+/// The http status a failed call is reported with
+pub(crate) fn status(error: &ChatError) -> StatusCode {
+    match error {
+        ChatError::Conversion(_) => StatusCode::BAD_REQUEST,
+        ChatError::UnknownModel(_) => StatusCode::NOT_FOUND,
+        ChatError::NoRouteTargets => StatusCode::INTERNAL_SERVER_ERROR,
+        ChatError::Gemini(e) => match e {
+            GeminiError::InvalidArgument
+            | GeminiError::PromptBlocked(_)
+            | GeminiError::ResponseBlocked(_)
+            | GeminiError::UnsupportedCapability(_) => StatusCode::BAD_REQUEST,
+            GeminiError::PermissionDenied => StatusCode::FORBIDDEN,
+            GeminiError::NotFound => StatusCode::NOT_FOUND,
+            GeminiError::ResourceExhausted
+            | GeminiError::RateLimited(_)
+            | GeminiError::BudgetExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+            GeminiError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            GeminiError::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        },
+        ChatError::Claude(e) => claude_status(e),
+    }
+}
+
+pub(crate) fn claude_status(error: &ClaudeError) -> StatusCode {
+    match error {
+        ClaudeError::InvalidRequestError(_) | ClaudeError::UnsupportedCapability(_) => {
+            StatusCode::BAD_REQUEST
+        }
+        ClaudeError::AuthenticationError(_) => StatusCode::UNAUTHORIZED,
+        ClaudeError::PermissionError(_) => StatusCode::FORBIDDEN,
+        ClaudeError::NotFoundError(_) => StatusCode::NOT_FOUND,
+        ClaudeError::RequestTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        ClaudeError::RateLimitError(_) | ClaudeError::BudgetExceeded(_) => {
+            StatusCode::TOO_MANY_REQUESTS
+        }
+        ClaudeError::OverloadedError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        ClaudeError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,492 @@
+//! Translation between the OpenAI Chat Completions api and [crate::chat].
+//!
+//! Gemini does not assign ids to tool calls, so the proxy makes them up and maps the results
+//! sent back for them to the function name again.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use axum::{
+    extract::{rejection::JsonRejection, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::StreamExt;
+use serde_json::Value;
+
+use crate::{
+    chat::ChatStream,
+    error::ChatError,
+    types::{
+        chat::{
+            guess_mime_type, ChatChunk, ChatFinishReason, ChatMessage, ChatPart, ChatRequest,
+            ChatResponse, ChatRole, ChatTool, ChatUsage, ModelId,
+        },
+        openai::{
+            ChatCompletion, ChatCompletionChoice, ChatCompletionChunk, ChatCompletionChunkChoice,
+            ChatCompletionMessage, ChatCompletionRequest, OpenAiContent, OpenAiContentPart,
+            OpenAiError, OpenAiErrorResponse, OpenAiFunctionCallDelta, OpenAiModel,
+            OpenAiModelList, OpenAiToolCallDelta, OpenAiUsage,
+        },
+    },
+};
+
+use super::{status, Proxy};
+
+pub(crate) async fn chat_completions(
+    State(proxy): State<Proxy>,
+    request: Result<Json<ChatCompletionRequest>, JsonRejection>,
+) -> Response {
+    let Json(request) = match request {
+        Ok(request) => request,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.body_text()),
+    };
+
+    let id = completion_id();
+    let model_name = request.model.clone();
+    let stream = request.stream;
+    let include_usage = request
+        .stream_options
+        .as_ref()
+        .is_some_and(|options| options.include_usage);
+
+    let result = async {
+        let model = proxy.chat_model(&request.model)?;
+        let request = chat_request(request)?;
+        match stream {
+            true => Ok(Err(model.chat_stream(request).await?)),
+            false => Ok(Ok(model.chat(request).await?)),
+        }
+    };
+
+    match result.await {
+        Ok(Ok(response)) => Json(completion(&id, &model_name, response)).into_response(),
+        Ok(Err(chunks)) => {
+            let state = StreamState {
+                id,
+                model: model_name,
+                created: chrono::Utc::now().timestamp(),
+                tool_calls: 0,
+                usage: None,
+                include_usage,
+            };
+            sse(chunks, state).into_response()
+        }
+        Err(e) => {
+            tracing::error!(error=%e, model=%model_name, "chat completion failed");
+            error_response(status(&e), e.to_string())
+        }
+    }
+}
+
+pub(crate) async fn models() -> Json<OpenAiModelList> {
+    let data = ModelId::known()
+        .map(|model| OpenAiModel {
+            id: model.to_string(),
+            object: "model".to_string(),
+            created: 0,
+            owned_by: match model {
+                ModelId::Gemini(_) => "google".to_string(),
+                ModelId::Claude(_) => "anthropic".to_string(),
+            },
+        })
+        .collect();
+
+    Json(OpenAiModelList {
+        object: "list".to_string(),
+        data,
+    })
+}
+
+/// Renders an error in the OpenAI format
+pub(crate) fn error_response(status: StatusCode, message: String) -> Response {
+    let e_type = match status {
+        StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND | StatusCode::PAYLOAD_TOO_LARGE => {
+            "invalid_request_error"
+        }
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::FORBIDDEN => "permission_error",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limit_error",
+        _ => "server_error",
+    };
+
+    let body = OpenAiErrorResponse {
+        error: OpenAiError {
+            message,
+            e_type: e_type.to_string(),
+            code: None,
+        },
+    };
+    (status, Json(body)).into_response()
+}
+
+fn completion_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "chatcmpl-{:x}{:04x}",
+        chrono::Utc::now().timestamp_millis(),
+        COUNTER.fetch_add(1, Ordering::Relaxed) & 0xffff
+    )
+}
+
+/// Converts a Chat Completions request, `system` and `developer` messages become the system prompt
+pub fn chat_request(request: ChatCompletionRequest) -> Result<ChatRequest, ChatError> {
+    let mut system: Vec<String> = Vec::new();
+    let mut messages: Vec<ChatMessage> = Vec::new();
+    // the function names of the tool calls, tool results only carry the id
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+
+    for message in request.messages {
+        let (role, parts) = match message.role.as_str() {
+            "system" | "developer" => {
+                system.push(text(message.content));
+                continue;
+            }
+            "user" => (ChatRole::User, parts(message.content)?),
+            "assistant" => {
+                let mut parts = parts(message.content)?;
+                for call in message.tool_calls.into_iter().flatten() {
+                    tool_names.insert(call.id.clone(), call.function.name.clone());
+                    parts.push(ChatPart::ToolCall {
+                        id: Some(call.id),
+                        name: call.function.name,
+                        arguments: arguments(call.function.arguments),
+                    });
+                }
+                (ChatRole::Assistant, parts)
+            }
+            "tool" => {
+                let id = message.tool_call_id.ok_or_else(|| {
+                    ChatError::Conversion("tool message without tool_call_id".to_string())
+                })?;
+                let name = tool_names
+                    .get(&id)
+                    .cloned()
+                    .ok_or_else(|| ChatError::Conversion(format!("unknown tool_call_id {}", id)))?;
+                let content = text(message.content);
+                let part = ChatPart::ToolResult {
+                    name,
+                    id: Some(id),
+                    content: match serde_json::from_str::<Value>(&content) {
+                        Ok(Value::Object(object)) => Value::Object(object),
+                        _ => Value::String(content),
+                    },
+                    is_error: None,
+                };
+                (ChatRole::User, vec![part])
+            }
+            other => return Err(ChatError::Conversion(format!("unknown role {}", other))),
+        };
+
+        // tool results follow each other as separate messages, both providers expect alternating roles
+        match messages.last_mut() {
+            Some(last) if last.role == role => last.parts.extend(parts),
+            _ => messages.push(ChatMessage { role, parts }),
+        }
+    }
+
+    Ok(ChatRequest {
+        system: Some(system.join("\n\n")).filter(|system| !system.is_empty()),
+        messages,
+        tools: request
+            .tools
+            .into_iter()
+            .map(|tool| ChatTool {
+                name: tool.function.name,
+                description: tool.function.description,
+                parameters: tool.function.parameters,
+            })
+            .collect(),
+        max_tokens: request.max_completion_tokens.or(request.max_tokens),
+        temperature: request.temperature,
+        top_p: request.top_p,
+        stop_sequences: request.stop.map(Vec::from),
+    })
+}
+
+fn text(content: Option<OpenAiContent>) -> String {
+    match content {
+        None => String::new(),
+        Some(OpenAiContent::Text(text)) => text,
+        Some(OpenAiContent::Parts(parts)) => parts
+            .into_iter()
+            .filter_map(|part| match part {
+                OpenAiContentPart::Text { text } => Some(text),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn parts(content: Option<OpenAiContent>) -> Result<Vec<ChatPart>, ChatError> {
+    match content {
+        None => Ok(Vec::new()),
+        Some(OpenAiContent::Text(text)) if text.is_empty() => Ok(Vec::new()),
+        Some(OpenAiContent::Text(text)) => Ok(vec![ChatPart::Text { text }]),
+        Some(OpenAiContent::Parts(parts)) => parts.into_iter().map(part).collect(),
+    }
+}
+
+fn part(part: OpenAiContentPart) -> Result<ChatPart, ChatError> {
+    match part {
+        OpenAiContentPart::Text { text } => Ok(ChatPart::Text { text }),
+        OpenAiContentPart::ImageUrl { image_url } => match image_url.url.strip_prefix("data:") {
+            // data:image/png;base64,....
+            Some(data_url) => {
+                let (mime_type, data) = data_url
+                    .split_once(";base64,")
+                    .ok_or_else(|| ChatError::Conversion("unsupported data url".to_string()))?;
+                Ok(ChatPart::InlineData {
+                    mime_type: mime_type.to_string(),
+                    data: data.to_string(),
+                })
+            }
+            None => Ok(ChatPart::FileData {
+                mime_type: guess_mime_type("image", &image_url.url).to_string(),
+                uri: image_url.url,
+            }),
+        },
+        OpenAiContentPart::InputAudio { input_audio } => Ok(ChatPart::InlineData {
+            mime_type: format!("audio/{}", input_audio.format),
+            data: input_audio.data,
+        }),
+    }
+}
+
+/// Tool call arguments are serialized JSON, Gemini requires an object
+fn arguments(arguments: String) -> Value {
+    match serde_json::from_str::<Value>(&arguments) {
+        Ok(value) => value,
+        Err(_) if arguments.trim().is_empty() => Value::Object(Default::default()),
+        Err(_) => Value::String(arguments),
+    }
+}
+
+fn tool_call_id(completion_id: &str, index: u32) -> String {
+    format!(
+        "call_{}_{}",
+        completion_id.trim_start_matches("chatcmpl-"),
+        index
+    )
+}
+
+/// Reports tool use as `tool_calls`, Gemini finishes tool calls with `STOP`
+fn finish_reason(reason: Option<ChatFinishReason>, tool_calls: bool) -> Option<String> {
+    let reason = match (reason?, tool_calls) {
+        (ChatFinishReason::ToolUse, _) | (ChatFinishReason::Stop, true) => "tool_calls",
+        (ChatFinishReason::Stop | ChatFinishReason::Other, false) => "stop",
+        (ChatFinishReason::Other, true) => "tool_calls",
+        (ChatFinishReason::MaxTokens, _) => "length",
+        (ChatFinishReason::ContentFilter, _) => "content_filter",
+    };
+    Some(reason.to_string())
+}
+
+impl From<ChatUsage> for OpenAiUsage {
+    fn from(usage: ChatUsage) -> Self {
+        Self {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+/// Converts a reply into a Chat Completions response
+pub fn completion(id: &str, model: &str, response: ChatResponse) -> ChatCompletion {
+    let mut tool_calls = Vec::new();
+    for part in &response.message.parts {
+        if let ChatPart::ToolCall {
+            id: call_id,
+            name,
+            arguments,
+        } = part
+        {
+            let index = tool_calls.len() as u32;
+            tool_calls.push(OpenAiToolCallDelta {
+                index,
+                id: Some(call_id.clone().unwrap_or_else(|| tool_call_id(id, index))),
+                t_type: Some("function".to_string()),
+                function: OpenAiFunctionCallDelta {
+                    name: Some(name.clone()),
+                    arguments: Some(arguments.to_string()),
+                },
+            });
+        }
+    }
+
+    let text = response.text();
+    ChatCompletion {
+        id: id.to_string(),
+        object: "chat.completion".to_string(),
+        created: chrono::Utc::now().timestamp(),
+        model: model.to_string(),
+        choices: vec![ChatCompletionChoice {
+            index: 0,
+            finish_reason: finish_reason(response.finish_reason, !tool_calls.is_empty()),
+            message: ChatCompletionMessage {
+                role: Some("assistant".to_string()),
+                content: Some(text).filter(|text| !text.is_empty() || tool_calls.is_empty()),
+                tool_calls: Some(tool_calls).filter(|calls| !calls.is_empty()),
+            },
+        }],
+        usage: response.usage.map(OpenAiUsage::from),
+    }
+}
+
+/// State of a streamed completion, tool calls are numbered across chunks
+struct StreamState {
+    id: String,
+    model: String,
+    created: i64,
+    tool_calls: u32,
+    usage: Option<ChatUsage>,
+    include_usage: bool,
+}
+
+impl StreamState {
+    fn chunk(
+        &self,
+        delta: ChatCompletionMessage,
+        finish_reason: Option<String>,
+    ) -> ChatCompletionChunk {
+        ChatCompletionChunk {
+            id: self.id.clone(),
+            object: "chat.completion.chunk".to_string(),
+            created: self.created,
+            model: self.model.clone(),
+            choices: vec![ChatCompletionChunkChoice {
+                index: 0,
+                delta,
+                finish_reason,
+            }],
+            usage: None,
+        }
+    }
+
+    /// The first chunk, which only carries the role
+    fn start(&self) -> ChatCompletionChunk {
+        self.chunk(
+            ChatCompletionMessage {
+                role: Some("assistant".to_string()),
+                ..Default::default()
+            },
+            None,
+        )
+    }
+
+    fn apply(&mut self, chunk: ChatChunk) -> Vec<ChatCompletionChunk> {
+        let mut chunks = Vec::new();
+        let mut content = String::new();
+        let mut tool_calls = Vec::new();
+
+        for part in chunk.parts {
+            match part {
+                ChatPart::Text { text } => content.push_str(&text),
+                ChatPart::ToolCall {
+                    id,
+                    name,
+                    arguments,
+                } => {
+                    // tool calls arrive complete, so they are sent in a single delta
+                    tool_calls.push(OpenAiToolCallDelta {
+                        index: self.tool_calls,
+                        id: Some(id.unwrap_or_else(|| tool_call_id(&self.id, self.tool_calls))),
+                        t_type: Some("function".to_string()),
+                        function: OpenAiFunctionCallDelta {
+                            name: Some(name),
+                            arguments: Some(arguments.to_string()),
+                        },
+                    });
+                    self.tool_calls += 1;
+                }
+                _ => {}
+            }
+        }
+
+        if !content.is_empty() || !tool_calls.is_empty() {
+            chunks.push(self.chunk(
+                ChatCompletionMessage {
+                    role: None,
+                    content: Some(content).filter(|content| !content.is_empty()),
+                    tool_calls: Some(tool_calls).filter(|calls| !calls.is_empty()),
+                },
+                None,
+            ));
+        }
+
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage;
+        }
+
+        if let Some(reason) = finish_reason(chunk.finish_reason, self.tool_calls > 0) {
+            chunks.push(self.chunk(ChatCompletionMessage::default(), Some(reason)));
+        }
+
+        chunks
+    }
+
+    /// The usage chunk, if requested with `stream_options.include_usage`
+    fn finish(&mut self) -> Option<ChatCompletionChunk> {
+        if !self.include_usage {
+            return None;
+        }
+
+        Some(ChatCompletionChunk {
+            choices: Vec::new(),
+            usage: Some(self.usage.take().unwrap_or_default().into()),
+            ..self.chunk(ChatCompletionMessage::default(), None)
+        })
+    }
+}
+
+fn event(chunk: &impl serde::Serialize) -> Event {
+    Event::default().data(serde_json::to_string(chunk).unwrap_or_default())
+}
+
+/// Re-emits the chunks of a reply as Chat Completions deltas, terminated by `[DONE]`
+fn sse(
+    chunks: ChatStream,
+    state: StreamState,
+) -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
+    let start = event(&state.start());
+    let events = futures::stream::unfold(Some((chunks, state)), |step| async move {
+        let (mut chunks, mut state) = step?;
+        match chunks.next().await {
+            Some(Ok(chunk)) => {
+                let events = state.apply(chunk).iter().map(event).collect::<Vec<_>>();
+                Some((events, Some((chunks, state))))
+            }
+            Some(Err(e)) => {
+                tracing::error!(error=%e, model=%state.model, "chat completion stream failed");
+                let error = OpenAiErrorResponse {
+                    error: OpenAiError {
+                        message: e.to_string(),
+                        e_type: "server_error".to_string(),
+                        code: None,
+                    },
+                };
+                Some((vec![event(&error)], None))
+            }
+            None => {
+                let mut events: Vec<Event> = state.finish().iter().map(event).collect();
+                events.push(Event::default().data("[DONE]"));
+                Some((events, None))
+            }
+        }
+    });
+
+    let events = futures::stream::once(async move { vec![start] })
+        .chain(events)
+        .flat_map(futures::stream::iter)
+        .map(Ok);
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,486 @@
+//! Caching of Gemini and Claude responses, keyed on the api, project, region and model of a call and the
+//! canonical JSON of the request.
+//!
+//! Responses are cached in memory by default, or on disk with [ResponseCache::sled] when the `disk-cache`
+//! feature is enabled. Streaming calls share their entries with regular calls, cached responses are replayed
+//! as a synthetic chunk stream.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::types::{
+    claude::{
+        ClaudeContent, ClaudeUsage, MessageDelta, RawPredictResponse, StreamRawPredictResponse,
+    },
+    content::{Content, GenerateContentCandidate, GenerateContentResponse},
+};
+
+pub use crate::types::content::CacheMode;
+
+/// A cached response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub response: serde_json::Value,
+    pub stored_at: DateTime<Utc>,
+}
+
+/// Where cached responses are kept, implement it to cache in another store such as SQLite or Redis.
+pub trait CacheStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<CacheEntry>;
+
+    fn insert(&self, key: String, entry: CacheEntry);
+
+    fn remove(&self, key: &str);
+
+    fn clear(&self);
+}
+
+#[derive(Default)]
+struct LruState {
+    entries: HashMap<String, (CacheEntry, u64)>,
+    /// The keys by the tick of their last use, the first one is evicted next.
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+/// Keeps the most recently used responses in memory.
+pub struct MemoryStore {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(LruState::default()),
+        }
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+
+        let (entry, last_used) = state.entries.get_mut(key)?;
+        let entry = entry.clone();
+        let previous = std::mem::replace(last_used, tick);
+        state.order.remove(&previous);
+        state.order.insert(tick, key.to_string());
+
+        Some(entry)
+    }
+
+    fn insert(&self, key: String, entry: CacheEntry) {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+
+        if let Some((_, previous)) = state.entries.insert(key.clone(), (entry, tick)) {
+            state.order.remove(&previous);
+        }
+        state.order.insert(tick, key);
+
+        while state.entries.len() > self.capacity {
+            let Some((_, evicted)) = state.order.pop_first() else {
+                break;
+            };
+            state.entries.remove(&evicted);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((_, tick)) = state.entries.remove(key) {
+            state.order.remove(&tick);
+        }
+    }
+
+    fn clear(&self) {
+        *self.state.lock().unwrap() = LruState::default();
+    }
+}
+
+/// Keeps responses in a sled database on disk, so they survive restarts.
+#[cfg(feature = "disk-cache")]
+pub struct SledStore {
+    db: sled::Db,
+}
+
+#[cfg(feature = "disk-cache")]
+impl SledStore {
+    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+}
+
+#[cfg(feature = "disk-cache")]
+impl CacheStore for SledStore {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let value = match self.db.get(key) {
+            Ok(value) => value?,
+            Err(e) => {
+                tracing::error!(error=?e, "failed to read response cache");
+                return None;
+            }
+        };
+
+        serde_json::from_slice(&value)
+            .map_err(|e| tracing::error!(error=?e, "failed to parse cached response"))
+            .ok()
+    }
+
+    fn insert(&self, key: String, entry: CacheEntry) {
+        let written = serde_json::to_vec(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|value| Ok(self.db.insert(key, value)?));
+        if let Err(e) = written {
+            tracing::error!(error=?e, "failed to write response cache");
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        if let Err(e) = self.db.remove(key) {
+            tracing::error!(error=?e, "failed to remove cached response");
+        }
+    }
+
+    fn clear(&self) {
+        if let Err(e) = self.db.clear() {
+            tracing::error!(error=?e, "failed to clear response cache");
+        }
+    }
+}
+
+/// Hits and misses of a [ResponseCache].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries which were found but older than the ttl, also counted as misses.
+    pub expired: u64,
+    pub writes: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    expired: AtomicU64,
+    writes: AtomicU64,
+}
+
+/// Caches successful responses of calls made through a [crate::client::Client].
+///
+/// Clones share the same store and stats.
+#[derive(Clone)]
+pub struct ResponseCache {
+    store: Arc<dyn CacheStore>,
+    ttl: Option<Duration>,
+    counters: Arc<Counters>,
+}
+
+impl ResponseCache {
+    /// Caches up to `capacity` responses in memory, evicting the least recently used ones.
+    pub fn memory(capacity: usize) -> Self {
+        Self::with_store(MemoryStore::new(capacity))
+    }
+
+    /// Caches responses in a sled database at the path.
+    #[cfg(feature = "disk-cache")]
+    pub fn sled(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Ok(Self::with_store(SledStore::open(path)?))
+    }
+
+    pub fn with_store(store: impl CacheStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            ttl: None,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// Treats responses older than the ttl as missing, they never expire by default.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            expired: self.counters.expired.load(Ordering::Relaxed),
+            writes: self.counters.writes.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        for counter in [
+            &self.counters.hits,
+            &self.counters.misses,
+            &self.counters.expired,
+            &self.counters.writes,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Removes every cached response.
+    pub fn clear(&self) {
+        self.store.clear();
+    }
+
+    /// The key of a request, the backend, project, location and model followed by the request JSON with sorted keys,
+    /// so calls sent to another api or region never share entries.
+    /// The `stream` flag of Claude requests is left out, so streaming and regular calls share entries.
+    pub fn key(
+        backend: &str,
+        project: &str,
+        location: &str,
+        model: &impl Display,
+        request: &impl Serialize,
+    ) -> String {
+        let mut request = serde_json::to_value(request).unwrap_or_default();
+        if let serde_json::Value::Object(request) = &mut request {
+            request.remove("stream");
+        }
+
+        let mut key = format!("{}:{}:{}:{}:", backend, project, location, model);
+        write_canonical(&mut key, &request);
+        key
+    }
+
+    /// The cached response, if it is present and not expired.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let entry = self.store.get(key).filter(|entry| {
+            let expired = self.ttl.is_some_and(|ttl| {
+                let age = Utc::now().signed_duration_since(entry.stored_at);
+                age.to_std().unwrap_or_default() > ttl
+            });
+            if expired {
+                self.counters.expired.fetch_add(1, Ordering::Relaxed);
+                self.store.remove(key);
+            }
+            !expired
+        });
+
+        match entry.and_then(|entry| serde_json::from_value(entry.response).ok()) {
+            Some(response) => {
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                Some(response)
+            }
+            None => {
+                self.counters.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: &str, response: &impl Serialize) {
+        let response = match serde_json::to_value(response) {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::error!(error=?e, "failed to serialize response for the cache");
+                return;
+            }
+        };
+
+        self.store.insert(
+            key.to_string(),
+            CacheEntry {
+                response,
+                stored_at: Utc::now(),
+            },
+        );
+        self.counters.writes.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn write_canonical(out: &mut String, value: &serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut entries = object.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(key, _)| *key);
+
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(out, value);
+            }
+            out.push('}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(out, item);
+            }
+            out.push(']');
+        }
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+/// The cache, key and mode of a single call.
+pub(crate) struct CacheScope {
+    pub cache: ResponseCache,
+    pub key: String,
+    pub mode: CacheMode,
+}
+
+impl CacheScope {
+    pub fn get<T: DeserializeOwned>(&self) -> Option<T> {
+        match self.mode {
+            CacheMode::Use => self.cache.get(&self.key),
+            CacheMode::Refresh | CacheMode::Bypass => None,
+        }
+    }
+
+    pub fn insert(&self, response: &impl Serialize) {
+        match self.mode {
+            CacheMode::Use | CacheMode::Refresh => self.cache.insert(&self.key, response),
+            CacheMode::Bypass => {}
+        }
+    }
+}
+
+/// Splits a cached Gemini response into a chunk per part, the last chunk carries the finish reason and usage.
+pub(crate) fn generate_content_chunks(
+    response: GenerateContentResponse,
+) -> Vec<GenerateContentResponse> {
+    let candidates = response.candidates.unwrap_or_default();
+    let parts = candidates
+        .iter()
+        .map(|c| c.content.as_ref().map_or(0, |content| content.parts.len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut chunks = (0..parts)
+        .map(|part| GenerateContentResponse {
+            candidates: Some(
+                candidates
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(position, candidate)| {
+                        let content = candidate.content.as_ref()?;
+                        Some(GenerateContentCandidate {
+                            index: Some(candidate.index.unwrap_or(position as u32)),
+                            content: Some(Content {
+                                role: content.role.clone(),
+                                parts: vec![content.parts.get(part)?.clone()],
+                            }),
+                            ..Default::default()
+                        })
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    chunks.push(GenerateContentResponse {
+        candidates: Some(
+            candidates
+                .into_iter()
+                .enumerate()
+                .map(|(position, candidate)| GenerateContentCandidate {
+                    index: Some(candidate.index.unwrap_or(position as u32)),
+                    content: None,
+                    ..candidate
+                })
+                .collect(),
+        ),
+        prompt_feedback: response.prompt_feedback,
+        usage_metadata: response.usage_metadata,
+    });
+
+    chunks
+}
+
+/// Replays a cached Claude response as the events of a stream.
+pub(crate) fn raw_predict_events(response: RawPredictResponse) -> Vec<StreamRawPredictResponse> {
+    let mut events = vec![StreamRawPredictResponse::MessageStart {
+        message: RawPredictResponse {
+            content: Vec::new(),
+            stop_reason: None,
+            stop_sequence: None,
+            usage: ClaudeUsage {
+                output_tokens: 0,
+                ..response.usage.clone()
+            },
+            ..response.clone()
+        },
+    }];
+
+    for (index, block) in response.content.into_iter().enumerate() {
+        let index = Some(index as u32);
+        let delta = match block.c_type.as_str() {
+            "text" => Some(ClaudeContent {
+                c_type: "text_delta".to_string(),
+                text: block.text.clone(),
+                ..Default::default()
+            }),
+            "tool_use" => block.input.as_ref().map(|input| ClaudeContent {
+                c_type: "input_json_delta".to_string(),
+                partial_json: Some(input.to_string()),
+                ..Default::default()
+            }),
+            _ => None,
+        };
+
+        let content_block = match &delta {
+            Some(_) => ClaudeContent {
+                text: String::new(),
+                input: block.input.as_ref().map(|_| serde_json::json!({})),
+                ..block
+            },
+            None => block,
+        };
+
+        events.push(StreamRawPredictResponse::ContentBlockStart {
+            index,
+            content_block,
+        });
+        if let Some(delta) = delta {
+            events.push(StreamRawPredictResponse::ContentBlockDelta { index, delta });
+        }
+        events.push(StreamRawPredictResponse::ContentBlockStop { index });
+    }
+
+    events.push(StreamRawPredictResponse::MessageDelta {
+        index: None,
+        delta: MessageDelta {
+            stop_reason: response.stop_reason,
+            stop_sequence: response.stop_sequence,
+            usage: None,
+        },
+        usage: Some(ClaudeUsage {
+            output_tokens: response.usage.output_tokens,
+            ..Default::default()
+        }),
+    });
+    events.push(StreamRawPredictResponse::MessageStop);
+
+    events
+}
@@ -0,0 +1,244 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::{ChatModel, ChatStream, ClaudeChatModel, GeminiChatModel},
+    client::Client,
+    error::ChatError,
+    types::chat::{ChatRequest, ChatResponse, ModelId},
+};
+
+/// A model in a specific region which can serve requests for a [Router].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RouteTarget {
+    pub model: ModelId,
+    /// The region to send requests to, defaults to the region of the provider handle.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+impl RouteTarget {
+    pub fn new(model: impl Into<ModelId>) -> Self {
+        Self {
+            model: model.into(),
+            region: None,
+        }
+    }
+
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+}
+
+impl fmt::Display for RouteTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.region {
+            Some(region) => write!(f, "{} ({})", self.model, region),
+            None => self.model.fmt(f),
+        }
+    }
+}
+
+/// A response together with the target which served it.
+#[derive(Debug)]
+pub struct Routed<T> {
+    pub target: RouteTarget,
+    pub response: T,
+}
+
+/// The health of a [RouteTarget] as tracked by the [Router].
+#[derive(Clone, Debug)]
+pub struct TargetHealth {
+    pub target: RouteTarget,
+    pub consecutive_failures: u32,
+    /// Time left until the target is tried again, if it is cooling down.
+    pub cooldown_remaining: Option<Duration>,
+    pub served: u64,
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct HealthState {
+    consecutive_failures: u32,
+    cooling_down_until: Option<Instant>,
+    served: u64,
+    failed: u64,
+}
+
+/// Sends requests to the first healthy target of an ordered list, falling back to the next
+/// target when a target fails with a retryable error such as `ResourceExhausted` or `OverloadedError`.
+///
+/// A target which failed [Router::with_failure_threshold] times in a row is skipped until its cooldown has passed.
+/// When every target is cooling down they are all tried in order anyway.
+///
+/// Clones share the health of the targets.
+#[derive(Clone)]
+pub struct Router {
+    client: Client,
+    targets: Vec<RouteTarget>,
+    health: Arc<Mutex<Vec<HealthState>>>,
+    cooldown: Duration,
+    failure_threshold: u32,
+}
+
+impl Router {
+    pub fn new(client: Client, targets: Vec<RouteTarget>) -> Self {
+        Self {
+            client,
+            health: Arc::new(Mutex::new(
+                targets.iter().map(|_| HealthState::default()).collect(),
+            )),
+            targets,
+            cooldown: Duration::from_secs(30),
+            failure_threshold: 1,
+        }
+    }
+
+    /// How long a failing target is skipped, defaults to 30 seconds.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// How many consecutive failures put a target into cooldown, defaults to 1.
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    pub fn targets(&self) -> &[RouteTarget] {
+        &self.targets
+    }
+
+    /// Returns the current health of every target.
+    pub fn health(&self) -> Vec<TargetHealth> {
+        let now = Instant::now();
+        let health = self.health.lock().unwrap();
+
+        self.targets
+            .iter()
+            .zip(health.iter())
+            .map(|(target, state)| TargetHealth {
+                target: target.clone(),
+                consecutive_failures: state.consecutive_failures,
+                cooldown_remaining: state
+                    .cooling_down_until
+                    .and_then(|until| until.checked_duration_since(now)),
+                served: state.served,
+                failed: state.failed,
+            })
+            .collect()
+    }
+
+    /// Creates a chat response on the first target able to serve it
+    pub async fn chat(&self, request: ChatRequest) -> Result<Routed<ChatResponse>, ChatError> {
+        let mut last_error = None;
+
+        for index in self.order() {
+            let model = self.model(index);
+            match model.chat(request.clone()).await {
+                Ok(response) => {
+                    self.record_success(index);
+                    return Ok(Routed {
+                        target: self.targets[index].clone(),
+                        response,
+                    });
+                }
+                Err(e) if e.is_retryable() => {
+                    self.record_failure(index, &e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or(ChatError::NoRouteTargets))
+    }
+
+    /// Create a chat stream response on the first target able to serve it
+    /// Targets are only switched before the stream starts, errors within the stream are passed on.
+    pub async fn chat_stream(&self, request: ChatRequest) -> Result<Routed<ChatStream>, ChatError> {
+        let mut last_error = None;
+
+        for index in self.order() {
+            let model = self.model(index);
+            match model.chat_stream(request.clone()).await {
+                Ok(response) => {
+                    self.record_success(index);
+                    return Ok(Routed {
+                        target: self.targets[index].clone(),
+                        response,
+                    });
+                }
+                Err(e) if e.is_retryable() => {
+                    self.record_failure(index, &e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or(ChatError::NoRouteTargets))
+    }
+
+    /// Indices of the targets to try, healthy targets first.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let health = self.health.lock().unwrap();
+
+        let healthy = (0..self.targets.len())
+            .filter(|&i| !matches!(health[i].cooling_down_until, Some(until) if until > now))
+            .collect::<Vec<_>>();
+
+        match healthy.is_empty() {
+            true => (0..self.targets.len()).collect(),
+            false => healthy,
+        }
+    }
+
+    fn model(&self, index: usize) -> Box<dyn ChatModel> {
+        let target = &self.targets[index];
+        match &target.model {
+            ModelId::Gemini(model) => {
+                let mut gemini = self.client.gemini();
+                if let Some(region) = &target.region {
+                    gemini = gemini.with_location(region);
+                }
+                Box::new(GeminiChatModel::new(gemini, model.clone()))
+            }
+            ModelId::Claude(model) => {
+                let mut claude = self.client.claude();
+                if let Some(region) = &target.region {
+                    claude = claude.with_location(region);
+                }
+                Box::new(ClaudeChatModel::new(claude, model.clone()))
+            }
+        }
+    }
+
+    fn record_success(&self, index: usize) {
+        let mut health = self.health.lock().unwrap();
+        let state = &mut health[index];
+        state.consecutive_failures = 0;
+        state.cooling_down_until = None;
+        state.served += 1;
+    }
+
+    fn record_failure(&self, index: usize, error: &ChatError) {
+        tracing::warn!(error=?error, target=%self.targets[index], "route target failed, falling back");
+
+        let mut health = self.health.lock().unwrap();
+        let state = &mut health[index];
+        state.consecutive_failures += 1;
+        state.failed += 1;
+        if state.consecutive_failures >= self.failure_threshold {
+            state.cooling_down_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Spans, events and metrics following the OpenTelemetry GenAI semantic conventions.
+//!
+//! Every `generate_content`, `stream_generate_content`, `raw_predict` and `stream_raw_predict` call runs in a
+//! `gen_ai` span carrying the `gen_ai.*` attributes. Prompt and completion events are only emitted when
+//! [crate::client::ClientBuilder::capture_content] is set, and metrics are recorded with the `metrics` crate
+//! when the `metrics` feature is enabled.
+
+use std::{
+    fmt::{Debug, Display},
+    sync::Arc,
+    time::Instant,
+};
+
+use serde::Serialize;
+use tracing::{field::Empty, Span};
+
+use crate::middleware::{redact_prompt, Middleware};
+
+/// `gen_ai.system` of Gemini calls to Vertex AI.
+pub const SYSTEM_VERTEX_AI: &str = "vertex_ai";
+/// `gen_ai.system` of Gemini calls to the Developer API.
+pub const SYSTEM_GEMINI: &str = "gemini";
+/// `gen_ai.system` of Claude calls.
+pub const SYSTEM_ANTHROPIC: &str = "anthropic";
+
+/// Metric names, recorded when the `metrics` feature is enabled.
+pub mod metric {
+    /// Histogram of the call duration in seconds, failed calls are labelled with `error.type`.
+    pub const OPERATION_DURATION: &str = "gen_ai.client.operation.duration";
+    /// Histogram of the input and output tokens of a call, labelled with `gen_ai.token.type`.
+    pub const TOKEN_USAGE: &str = "gen_ai.client.token.usage";
+    /// Histogram of the time until the first chunk of a stream in seconds.
+    pub const TIME_TO_FIRST_TOKEN: &str = "gen_ai.server.time_to_first_token";
+}
+
+/// The request attributes of a call.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct RequestAttributes {
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+}
+
+/// Records the attributes, events and metrics of a single call on the span of the call.
+#[derive(Clone)]
+pub(crate) struct Telemetry {
+    span: Span,
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    system: &'static str,
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    model: String,
+    capture_content: bool,
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    start: Instant,
+}
+
+impl Telemetry {
+    /// Starts recording a call in a new `gen_ai` span.
+    pub fn start(
+        system: &'static str,
+        model: &impl Display,
+        request: RequestAttributes,
+        capture_content: bool,
+    ) -> Self {
+        let span = tracing::info_span!(
+            "gen_ai",
+            otel.name = %format_args!("chat {}", model),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            gen_ai.operation.name = "chat",
+            gen_ai.system = system,
+            gen_ai.request.model = %model,
+            gen_ai.request.max_tokens = Empty,
+            gen_ai.request.temperature = Empty,
+            gen_ai.request.top_p = Empty,
+            gen_ai.response.finish_reasons = Empty,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            "error.type" = Empty,
+        );
+        if let Some(max_tokens) = request.max_tokens {
+            span.record("gen_ai.request.max_tokens", max_tokens);
+        }
+        if let Some(temperature) = request.temperature {
+            span.record("gen_ai.request.temperature", temperature as f64);
+        }
+        if let Some(top_p) = request.top_p {
+            span.record("gen_ai.request.top_p", top_p as f64);
+        }
+
+        Self {
+            span,
+            system,
+            model: model.to_string(),
+            capture_content,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Emits the request as a prompt event, if content capture is enabled, after the middleware redacted it.
+    pub fn prompt(&self, request: &impl Serialize, middleware: &[Arc<dyn Middleware>]) {
+        if self.capture_content {
+            let mut prompt = serde_json::to_value(request).unwrap_or_default();
+            redact_prompt(middleware, &mut prompt);
+            let body = prompt.to_string();
+            self.span.in_scope(|| {
+                tracing::info!(
+                    event.name = "gen_ai.content.prompt",
+                    gen_ai.prompt = body,
+                    "gen_ai.content.prompt"
+                )
+            });
+        }
+    }
+
+    /// Emits the generated text as a completion event, if content capture is enabled.
+    pub fn completion(&self, text: impl FnOnce() -> Option<String>) {
+        if self.capture_content {
+            if let Some(text) = text() {
+                self.span.in_scope(|| {
+                    tracing::info!(
+                        event.name = "gen_ai.content.completion",
+                        gen_ai.completion = text,
+                        "gen_ai.content.completion"
+                    )
+                });
+            }
+        }
+    }
+
+    /// Records the time until the first chunk of a stream arrived.
+    pub fn first_token(&self) {
+        #[cfg(feature = "metrics")]
+        metrics::histogram!(metric::TIME_TO_FIRST_TOKEN, &self.labels())
+            .record(self.start.elapsed().as_secs_f64());
+    }
+
+    /// Records the finish reasons and token usage of a successful call.
+    pub fn response(
+        &self,
+        finish_reasons: &[String],
+        input_tokens: Option<u32>,
+        output_tokens: Option<u32>,
+    ) {
+        if !finish_reasons.is_empty() {
+            self.span.record(
+                "gen_ai.response.finish_reasons",
+                tracing::field::debug(finish_reasons),
+            );
+        }
+        if let Some(input_tokens) = input_tokens {
+            self.span.record("gen_ai.usage.input_tokens", input_tokens);
+        }
+        if let Some(output_tokens) = output_tokens {
+            self.span
+                .record("gen_ai.usage.output_tokens", output_tokens);
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            let duration = self.start.elapsed().as_secs_f64();
+            let labels = self.labels();
+            metrics::histogram!(metric::OPERATION_DURATION, &labels).record(duration);
+
+            for (token_type, tokens) in [("input", input_tokens), ("output", output_tokens)] {
+                if let Some(tokens) = tokens {
+                    let mut labels = labels.clone();
+                    labels.push(("gen_ai.token.type", token_type.to_string()));
+                    metrics::histogram!(metric::TOKEN_USAGE, &labels).record(tokens as f64);
+                }
+            }
+        }
+    }
+
+    /// Records a failed call.
+    pub fn error(&self, error: &impl Debug) {
+        let error_type = error_type(error);
+        self.span.record("error.type", error_type.as_str());
+        self.span.record("otel.status_code", "ERROR");
+
+        #[cfg(feature = "metrics")]
+        {
+            let mut labels = self.labels();
+            labels.push(("error.type", error_type));
+            metrics::histogram!(metric::OPERATION_DURATION, &labels)
+                .record(self.start.elapsed().as_secs_f64());
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn labels(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("gen_ai.operation.name", "chat".to_string()),
+            ("gen_ai.system", self.system.to_string()),
+            ("gen_ai.request.model", self.model.clone()),
+        ]
+    }
+}
+
+/// The variant name of an error, e.g. `ResourceExhausted`.
+fn error_type(error: &impl Debug) -> String {
+    let error = format!("{:?}", error);
+    match error.find(['(', ' ', '{']) {
+        Some(end) => error[..end].to_string(),
+        None => error,
+    }
+}
+
+/// The serialized name of a finish reason, e.g. `stop` or `end_turn`.
+pub(crate) fn finish_reason(reason: &impl Serialize) -> Option<String> {
+    match serde_json::to_value(reason).ok()? {
+        serde_json::Value::String(reason) => Some(reason.to_lowercase()),
+        _ => None,
+    }
+}
@@ -0,0 +1,451 @@
+//! An in-process fake of the Vertex AI api, to test code using [Gemini](crate::gemini::Gemini) and
+//! [Claude](crate::claude::Claude) offline.
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! use async_google_gemini::{
+//!     testing::{MockReply, MockServer},
+//!     types::{
+//!         content::{Content, GenerateContentRequest, GenerateContentResponse},
+//!         gemini::GeminiModel,
+//!     },
+//! };
+//!
+//! let server = MockServer::start().await;
+//! server.reply(MockReply::generate_content(&GenerateContentResponse::default()));
+//!
+//! let request = GenerateContentRequest::builder()
+//!     .contents(vec![Content::default()])
+//!     .build()?;
+//! server
+//!     .client()
+//!     .gemini()
+//!     .generate_content(GeminiModel::Gemini15Flash002, request)
+//!     .await?;
+//!
+//! let requests = server.requests();
+//! assert_eq!(requests[0].method(), Some("generateContent"));
+//! assert_eq!(requests[0].header("authorization"), Some("Bearer test-token"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With the `live` feature, [MockLiveServer] stands in for the WebSocket Live api.
+
+#[cfg(feature = "live")]
+mod live;
+
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use crate::{
+    client::{Client, ClientBuilder},
+    config::GeminiConfig,
+    types::{
+        claude::{RawPredictResponse, StreamRawPredictResponse},
+        content::GenerateContentResponse,
+    },
+};
+
+#[cfg(feature = "live")]
+pub use live::MockLiveServer;
+
+/// The project id of clients created by [MockServer::client].
+pub const TEST_PROJECT_ID: &str = "test-project";
+/// The bearer token of clients created by [MockServer::client].
+pub const TEST_TOKEN: &str = "test-token";
+
+/// A [GeminiConfig] with a static token, which never talks to GCP.
+pub fn test_config() -> GeminiConfig {
+    GeminiConfig::from_static_token(TEST_PROJECT_ID, TEST_TOKEN)
+}
+
+#[derive(Clone, Debug)]
+enum MockBody {
+    Body(String),
+    Events(Vec<String>),
+    Disconnect,
+}
+
+/// A scripted reply of the [MockServer].
+#[derive(Clone, Debug)]
+pub struct MockReply {
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: MockBody,
+    delay: Duration,
+    event_delay: Duration,
+}
+
+impl MockReply {
+    fn new(status: StatusCode, body: MockBody) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body,
+            delay: Duration::ZERO,
+            event_delay: Duration::ZERO,
+        }
+    }
+
+    /// Replies with the JSON of the value.
+    pub fn json(value: &impl Serialize) -> Self {
+        Self::new(
+            StatusCode::OK,
+            MockBody::Body(serde_json::to_string(value).expect("mock reply is not serializable")),
+        )
+    }
+
+    /// Replies to `generateContent` or `countTokens`.
+    pub fn generate_content(response: &GenerateContentResponse) -> Self {
+        Self::json(response)
+    }
+
+    /// Replies to `rawPredict`.
+    pub fn raw_predict(response: &RawPredictResponse) -> Self {
+        Self::json(response)
+    }
+
+    /// Replies with a server sent event for each of the raw data strings.
+    pub fn events(events: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::new(
+            StatusCode::OK,
+            MockBody::Events(events.into_iter().map(Into::into).collect()),
+        )
+    }
+
+    /// Replies to `streamGenerateContent` with a server sent event for each chunk.
+    pub fn stream_generate_content(chunks: &[GenerateContentResponse]) -> Self {
+        Self::events(
+            chunks
+                .iter()
+                .map(|chunk| serde_json::to_string(chunk).expect("mock reply is not serializable")),
+        )
+    }
+
+    /// Replies to `streamRawPredict` with a server sent event for each event.
+    pub fn stream_raw_predict(events: &[StreamRawPredictResponse]) -> Self {
+        Self::events(
+            events
+                .iter()
+                .map(|event| serde_json::to_string(event).expect("mock reply is not serializable")),
+        )
+    }
+
+    /// Replies with the status and raw body.
+    pub fn status(status: u16, body: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::from_u16(status).expect("invalid status code"),
+            MockBody::Body(body.into()),
+        )
+    }
+
+    /// Replies with a Google api error, as returned for Gemini calls.
+    pub fn gemini_error(status: u16, message: impl Into<String>) -> Self {
+        let status = StatusCode::from_u16(status).expect("invalid status code");
+        let body = serde_json::json!({
+            "error": {
+                "code": status.as_u16(),
+                "message": message.into(),
+                "status": google_status(status),
+            }
+        });
+        Self::new(status, MockBody::Body(body.to_string()))
+    }
+
+    /// Replies with an Anthropic api error, as returned for Claude calls.
+    pub fn claude_error(status: u16, error_type: &str, message: impl Into<String>) -> Self {
+        let body = serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": error_type,
+                "message": message.into(),
+            }
+        });
+        Self::new(
+            StatusCode::from_u16(status).expect("invalid status code"),
+            MockBody::Body(body.to_string()),
+        )
+    }
+
+    /// Closes the connection without replying, which fails the call with a transport error.
+    pub fn disconnect() -> Self {
+        Self::new(StatusCode::OK, MockBody::Disconnect)
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Waits before replying, e.g. to trigger timeouts.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Waits before each server sent event, e.g. to trigger the stream idle timeout.
+    pub fn with_event_delay(mut self, delay: Duration) -> Self {
+        self.event_delay = delay;
+        self
+    }
+}
+
+/// The canonical Google status of an http status.
+fn google_status(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "INVALID_ARGUMENT",
+        401 => "UNAUTHENTICATED",
+        403 => "PERMISSION_DENIED",
+        404 => "NOT_FOUND",
+        409 => "ABORTED",
+        429 => "RESOURCE_EXHAUSTED",
+        499 => "CANCELLED",
+        501 => "UNIMPLEMENTED",
+        503 => "UNAVAILABLE",
+        504 => "DEADLINE_EXCEEDED",
+        _ => "INTERNAL",
+    }
+}
+
+/// A request received by the [MockServer].
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub http_method: String,
+    /// The path and query, e.g. `/v1/projects/test-project/locations/us-central1/publishers/google/models/gemini-1.5-pro:generateContent`.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// The value of the header, names are compared case insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The api method, e.g. `generateContent` or `streamRawPredict`.
+    pub fn method(&self) -> Option<&str> {
+        let path = self.path.split('?').next()?;
+        path.rsplit_once(':').map(|(_, method)| method)
+    }
+
+    /// The model id of the path, e.g. `gemini-1.5-pro`.
+    pub fn model(&self) -> Option<&str> {
+        let path = self.path.split('?').next()?;
+        let (resource, _) = path.rsplit_once(':')?;
+        resource.rsplit('/').next()
+    }
+
+    /// Parses the body, e.g. as a `GenerateContentRequest`.
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.body)
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    replies: VecDeque<MockReply>,
+    fallback: Option<MockReply>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// A local http server which answers requests with scripted replies, in the order they were added.
+///
+/// Every request is recorded, see [MockServer::requests]. Requests without a scripted reply get a 500 error.
+/// The server stops when it is dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind mock server");
+        let addr = listener.local_addr().expect("mock server has no address");
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle(stream, state.clone()));
+                }
+            }
+        });
+
+        Self { addr, state, task }
+    }
+
+    /// The base url of the server, see [ClientBuilder::base_url].
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A client builder with a static token which sends every request to this server.
+    pub fn client_builder(&self) -> ClientBuilder {
+        Client::builder(test_config()).base_url(self.url())
+    }
+
+    /// A client with a static token which sends every request to this server.
+    pub fn client(&self) -> Client {
+        self.client_builder()
+            .build()
+            .expect("failed to build mock client")
+    }
+
+    /// Adds a reply for the next unanswered request.
+    pub fn reply(&self, reply: MockReply) -> &Self {
+        self.state.lock().unwrap().replies.push_back(reply);
+        self
+    }
+
+    /// Answers requests with the reply once all scripted replies are used up.
+    pub fn fallback(&self, reply: MockReply) -> &Self {
+        self.state.lock().unwrap().fallback = Some(reply);
+        self
+    }
+
+    /// The requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The number of scripted replies which have not been used yet.
+    pub fn pending_replies(&self) -> usize {
+        self.state.lock().unwrap().replies.len()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle(mut stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+
+    let reply = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request);
+        state.replies.pop_front().or_else(|| state.fallback.clone())
+    };
+    let reply = reply.unwrap_or_else(|| {
+        tracing::warn!("mock server received a request without a scripted reply");
+        MockReply::gemini_error(500, "no mock reply scripted")
+    });
+
+    if let Err(e) = write_reply(&mut stream, reply).await {
+        tracing::debug!(error=?e, "failed to write mock reply");
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    let header_end = loop {
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let http_method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect::<Vec<_>>();
+
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or_default();
+
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Some(RecordedRequest {
+        http_method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+async fn write_reply(stream: &mut TcpStream, reply: MockReply) -> std::io::Result<()> {
+    tokio::time::sleep(reply.delay).await;
+
+    let content_type = match &reply.body {
+        MockBody::Body(_) => "application/json",
+        MockBody::Events(_) => "text/event-stream",
+        MockBody::Disconnect => return stream.shutdown().await,
+    };
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: {}\r\nconnection: close\r\n",
+        reply.status.as_u16(),
+        reply.status.canonical_reason().unwrap_or_default(),
+        content_type,
+    );
+    if let MockBody::Body(body) = &reply.body {
+        head.push_str(&format!("content-length: {}\r\n", body.len()));
+    }
+    for (name, value) in &reply.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+
+    match reply.body {
+        MockBody::Body(body) => stream.write_all(body.as_bytes()).await?,
+        MockBody::Events(events) => {
+            for event in events {
+                tokio::time::sleep(reply.event_delay).await;
+                stream
+                    .write_all(format!("data: {}\r\n\r\n", event).as_bytes())
+                    .await?;
+                stream.flush().await?;
+            }
+        }
+        MockBody::Disconnect => {}
+    }
+
+    stream.shutdown().await
+}
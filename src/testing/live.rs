@@ -0,0 +1,191 @@
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{Request, Response},
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+
+use crate::{
+    client::{Client, ClientBuilder},
+    types::live::{LiveClientMessage, LiveServerMessage},
+};
+
+use super::{test_config, RecordedRequest};
+
+#[derive(Default)]
+struct LiveState {
+    replies: VecDeque<Vec<LiveServerMessage>>,
+    setup_error: Option<String>,
+    handshakes: Vec<RecordedRequest>,
+    messages: Vec<LiveClientMessage>,
+}
+
+/// A local WebSocket server standing in for the Live api.
+///
+/// The setup is answered with `setupComplete`, every later client message with the next scripted reply, if any.
+/// Handshakes and client messages are recorded. The server stops when it is dropped.
+pub struct MockLiveServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<LiveState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockLiveServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind mock live server");
+        let addr = listener
+            .local_addr()
+            .expect("mock live server has no address");
+        let state = Arc::new(Mutex::new(LiveState::default()));
+
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle(stream, state.clone()));
+                }
+            }
+        });
+
+        Self { addr, state, task }
+    }
+
+    /// The base url of the server, see [ClientBuilder::base_url].
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A client builder with a static token which connects to this server.
+    pub fn client_builder(&self) -> ClientBuilder {
+        Client::builder(test_config()).base_url(self.url())
+    }
+
+    /// A client with a static token which connects to this server.
+    pub fn client(&self) -> Client {
+        self.client_builder()
+            .build()
+            .expect("failed to build mock client")
+    }
+
+    /// Adds the messages sent in reply to the next client message after the setup.
+    pub fn reply(&self, messages: impl IntoIterator<Item = LiveServerMessage>) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .replies
+            .push_back(messages.into_iter().collect());
+        self
+    }
+
+    /// Rejects the setup by closing the connection with the reason, as the api does for invalid setups.
+    pub fn fail_setup(&self, reason: impl Into<String>) -> &Self {
+        self.state.lock().unwrap().setup_error = Some(reason.into());
+        self
+    }
+
+    /// The WebSocket handshakes received so far, with their path and headers.
+    pub fn handshakes(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().handshakes.clone()
+    }
+
+    /// The client messages received so far, oldest first, starting with the setup.
+    pub fn messages(&self) -> Vec<LiveClientMessage> {
+        self.state.lock().unwrap().messages.clone()
+    }
+}
+
+impl Drop for MockLiveServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle(stream: TcpStream, state: Arc<Mutex<LiveState>>) {
+    // the error response is fixed by tungstenite
+    #[allow(clippy::result_large_err)]
+    let record = |request: &Request, response: Response| {
+        let headers = request
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    value.to_str().unwrap_or_default().to_string(),
+                )
+            })
+            .collect();
+        state.lock().unwrap().handshakes.push(RecordedRequest {
+            http_method: request.method().to_string(),
+            path: request.uri().to_string(),
+            headers,
+            body: String::new(),
+        });
+        Ok(response)
+    };
+
+    let mut socket = match tokio_tungstenite::accept_hdr_async(stream, record).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            tracing::debug!(error=?e, "mock live handshake failed");
+            return;
+        }
+    };
+
+    while let Some(Ok(message)) = socket.next().await {
+        let data = match message {
+            Message::Text(text) => text.into_bytes(),
+            Message::Binary(data) => data,
+            // reading on flushes the reply to the close frame
+            _ => continue,
+        };
+        let Ok(message) = serde_json::from_slice::<LiveClientMessage>(&data) else {
+            tracing::warn!("mock live server received an invalid message");
+            continue;
+        };
+
+        let is_setup = matches!(message, LiveClientMessage::Setup(_));
+        let (replies, setup_error) = {
+            let mut state = state.lock().unwrap();
+            state.messages.push(message);
+            match is_setup {
+                true => (Vec::new(), state.setup_error.take()),
+                false => (state.replies.pop_front().unwrap_or_default(), None),
+            }
+        };
+
+        if let Some(reason) = setup_error {
+            let frame = CloseFrame {
+                code: CloseCode::Invalid,
+                reason: reason.into(),
+            };
+            let _ = socket.close(Some(frame)).await;
+            return;
+        }
+
+        let replies = match is_setup {
+            true => vec![json!({ "setupComplete": {} }).to_string()],
+            false => replies
+                .iter()
+                .filter_map(|reply| serde_json::to_string(reply).ok())
+                .collect(),
+        };
+        for reply in replies {
+            if socket.send(Message::Text(reply)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use derive_builder::Builder;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use super::{
+    claude::{RawPredictRequest, RawPredictResponse},
+    content::{GenerateContentRequest, GenerateContentResponse},
+};
+
+/// State of a batch prediction job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JobState {
+    #[serde(rename = "JOB_STATE_QUEUED")]
+    Queued,
+    #[serde(rename = "JOB_STATE_PENDING")]
+    Pending,
+    #[serde(rename = "JOB_STATE_RUNNING")]
+    Running,
+    #[serde(rename = "JOB_STATE_SUCCEEDED")]
+    Succeeded,
+    #[serde(rename = "JOB_STATE_FAILED")]
+    Failed,
+    #[serde(rename = "JOB_STATE_CANCELLING")]
+    Cancelling,
+    #[serde(rename = "JOB_STATE_CANCELLED")]
+    Cancelled,
+    #[serde(rename = "JOB_STATE_PAUSED")]
+    Paused,
+    #[serde(rename = "JOB_STATE_EXPIRED")]
+    Expired,
+    #[serde(rename = "JOB_STATE_UPDATING")]
+    Updating,
+    #[serde(rename = "JOB_STATE_PARTIALLY_SUCCEEDED")]
+    PartiallySucceeded,
+    #[serde(rename = "JOB_STATE_UNSPECIFIED", other)]
+    Unspecified,
+}
+
+impl JobState {
+    /// Whether the job has finished and its state won't change anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            JobState::Succeeded
+                | JobState::Failed
+                | JobState::Cancelled
+                | JobState::Expired
+                | JobState::PartiallySucceeded
+        )
+    }
+}
+
+/// Where the requests of a job are read from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchInputConfig {
+    /// `jsonl` for Cloud Storage files, `bigquery` for a table.
+    #[serde(rename = "instancesFormat")]
+    pub instances_format: String,
+    #[serde(rename = "gcsSource", skip_serializing_if = "Option::is_none")]
+    pub gcs_source: Option<GcsSource>,
+    #[serde(rename = "bigquerySource", skip_serializing_if = "Option::is_none")]
+    pub bigquery_source: Option<BigQuerySource>,
+}
+
+impl BatchInputConfig {
+    /// Reads the requests from JSONL files, e.g. `gs://bucket/requests.jsonl`.
+    pub fn gcs(uris: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            instances_format: "jsonl".to_string(),
+            gcs_source: Some(GcsSource {
+                uris: uris.into_iter().map(Into::into).collect(),
+            }),
+            bigquery_source: None,
+        }
+    }
+
+    /// Reads the requests from a table with a `request` column, e.g. `bq://project.dataset.table`.
+    pub fn bigquery(input_uri: impl Into<String>) -> Self {
+        Self {
+            instances_format: "bigquery".to_string(),
+            gcs_source: None,
+            bigquery_source: Some(BigQuerySource {
+                input_uri: input_uri.into(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GcsSource {
+    pub uris: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BigQuerySource {
+    #[serde(rename = "inputUri")]
+    pub input_uri: String,
+}
+
+/// Where the responses of a job are written to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchOutputConfig {
+    /// `jsonl` for Cloud Storage files, `bigquery` for a table.
+    #[serde(rename = "predictionsFormat")]
+    pub predictions_format: String,
+    #[serde(rename = "gcsDestination", skip_serializing_if = "Option::is_none")]
+    pub gcs_destination: Option<GcsDestination>,
+    #[serde(
+        rename = "bigqueryDestination",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bigquery_destination: Option<BigQueryDestination>,
+}
+
+impl BatchOutputConfig {
+    /// Writes the responses to JSONL files in a new directory under the prefix, e.g. `gs://bucket/output/`.
+    pub fn gcs(output_uri_prefix: impl Into<String>) -> Self {
+        Self {
+            predictions_format: "jsonl".to_string(),
+            gcs_destination: Some(GcsDestination {
+                output_uri_prefix: output_uri_prefix.into(),
+            }),
+            bigquery_destination: None,
+        }
+    }
+
+    /// Writes the responses to a table, e.g. `bq://project.dataset.table`, or a new table in a dataset, e.g. `bq://project.dataset`.
+    pub fn bigquery(output_uri: impl Into<String>) -> Self {
+        Self {
+            predictions_format: "bigquery".to_string(),
+            gcs_destination: None,
+            bigquery_destination: Some(BigQueryDestination {
+                output_uri: output_uri.into(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GcsDestination {
+    #[serde(rename = "outputUriPrefix")]
+    pub output_uri_prefix: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BigQueryDestination {
+    #[serde(rename = "outputUri")]
+    pub output_uri: String,
+}
+
+/// Request to create a batch prediction job, see [crate::batch::Batch::create].
+#[derive(Clone, Debug, Serialize, Deserialize, Builder)]
+#[builder(setter(into))]
+pub struct CreateBatchPredictionJobRequest {
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    /// The model resource, e.g. `publishers/google/models/gemini-1.5-flash-002`, see [crate::batch::Batch::model_resource].
+    pub model: String,
+    #[serde(rename = "inputConfig")]
+    pub input_config: BatchInputConfig,
+    #[serde(rename = "outputConfig")]
+    pub output_config: BatchOutputConfig,
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+}
+
+impl CreateBatchPredictionJobRequest {
+    pub fn builder() -> CreateBatchPredictionJobRequestBuilder {
+        CreateBatchPredictionJobRequestBuilder::default()
+    }
+}
+
+/// A batch prediction job as reported by Vertex AI.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchPredictionJob {
+    /// The resource name, `projects/{project}/locations/{location}/batchPredictionJobs/{id}`.
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+    pub model: String,
+    #[serde(rename = "inputConfig")]
+    pub input_config: BatchInputConfig,
+    #[serde(rename = "outputConfig")]
+    pub output_config: BatchOutputConfig,
+    pub state: JobState,
+    /// Why the job failed, for failed jobs.
+    pub error: Option<JobError>,
+    /// The first errors of individual requests, for jobs which partially failed.
+    #[serde(rename = "partialFailures", default)]
+    pub partial_failures: Vec<JobError>,
+    #[serde(rename = "outputInfo")]
+    pub output_info: Option<BatchOutputInfo>,
+    #[serde(rename = "completionStats")]
+    pub completion_stats: Option<CompletionStats>,
+    #[serde(rename = "createTime")]
+    pub create_time: Option<String>,
+    #[serde(rename = "startTime")]
+    pub start_time: Option<String>,
+    #[serde(rename = "endTime")]
+    pub end_time: Option<String>,
+    #[serde(rename = "updateTime")]
+    pub update_time: Option<String>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+impl BatchPredictionJob {
+    /// The last segment of the resource name.
+    pub fn id(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+
+    /// The Cloud Storage directory or BigQuery table the responses were written to, once known.
+    pub fn output_location(&self) -> Option<&str> {
+        let info = self.output_info.as_ref()?;
+        info.gcs_output_directory
+            .as_deref()
+            .or(info.bigquery_output_table.as_deref())
+            .or(info.bigquery_output_dataset.as_deref())
+    }
+
+    /// The requests processed so far, successful or not.
+    pub fn processed(&self) -> u64 {
+        self.completion_stats
+            .as_ref()
+            .map_or(0, |stats| stats.successful_count + stats.failed_count)
+    }
+}
+
+/// Status of a failed job or request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobError {
+    #[serde(default)]
+    pub code: i32,
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchOutputInfo {
+    #[serde(rename = "gcsOutputDirectory")]
+    pub gcs_output_directory: Option<String>,
+    #[serde(rename = "bigqueryOutputDataset")]
+    pub bigquery_output_dataset: Option<String>,
+    #[serde(rename = "bigqueryOutputTable")]
+    pub bigquery_output_table: Option<String>,
+}
+
+/// Counts of the requests of a job by outcome.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CompletionStats {
+    #[serde(rename = "successfulCount", default, deserialize_with = "int64")]
+    pub successful_count: u64,
+    #[serde(rename = "failedCount", default, deserialize_with = "int64")]
+    pub failed_count: u64,
+    /// Requests which were not processed, e.g. because the job was cancelled.
+    #[serde(rename = "incompleteCount", default, deserialize_with = "int64")]
+    pub incomplete_count: u64,
+}
+
+/// Vertex AI encodes int64 values as strings.
+fn int64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => s.parse().map_err(serde::de::Error::custom),
+        Value::Number(n) => n
+            .as_u64()
+            .ok_or_else(|| serde::de::Error::custom("expected an unsigned integer")),
+        _ => Ok(0),
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListBatchPredictionJobsResponse {
+    #[serde(rename = "batchPredictionJobs", default)]
+    pub batch_prediction_jobs: Vec<BatchPredictionJob>,
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+}
+
+/// One line of the JSONL input of a Gemini batch job.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeminiBatchRequest {
+    pub request: GenerateContentRequest,
+}
+
+/// One line of the JSONL input of a Claude batch job.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClaudeBatchRequest {
+    /// Identifies the request in the output, which is not in input order.
+    pub custom_id: String,
+    pub request: RawPredictRequest,
+}
+
+/// One line of the JSONL output of a Gemini batch job.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeminiBatchResponse {
+    /// The request as it was read from the input.
+    pub request: Value,
+    pub response: Option<GenerateContentResponse>,
+    /// The error of a failed request, empty for successful ones.
+    #[serde(default)]
+    pub status: String,
+}
+
+/// One line of the JSONL output of a Claude batch job.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClaudeBatchResponse {
+    pub custom_id: Option<String>,
+    /// The request as it was read from the input.
+    pub request: Option<Value>,
+    pub response: Option<RawPredictResponse>,
+    /// The error of a failed request.
+    pub error: Option<Value>,
+}
@@ -0,0 +1,388 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    chat::ModelId,
+    claude::{ClaudeMessageContent, ClaudeModel, ClaudeSystemPrompt, RawPredictRequest},
+    content::{GenerateContentRequest, Part, SystemInstruction, Tool},
+    gemini::GeminiModel,
+};
+
+/// A kind of input a model accepts.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, strum_macros::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Modality {
+    Text,
+    Image,
+    Audio,
+    Video,
+    Pdf,
+}
+
+impl Modality {
+    /// Returns the modality of a MIME type, if it is one of the known kinds of input.
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        let mime_type = mime_type.trim().to_ascii_lowercase();
+        match mime_type.split_once('/').map(|(kind, _)| kind) {
+            _ if mime_type == "application/pdf" => Some(Modality::Pdf),
+            Some("text") => Some(Modality::Text),
+            Some("image") => Some(Modality::Image),
+            Some("audio") => Some(Modality::Audio),
+            Some("video") => Some(Modality::Video),
+            _ => None,
+        }
+    }
+}
+
+/// Metadata about what a model supports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    /// The maximum number of input tokens.
+    pub context_window: u32,
+    /// The maximum number of tokens the model can generate in a single response.
+    pub max_output_tokens: u32,
+    pub input_modalities: &'static [Modality],
+    pub function_calling: bool,
+    /// Whether the model supports a response schema or JSON response mime type.
+    pub structured_output: bool,
+    /// The regions the model is available in by default.
+    pub regions: &'static [&'static str],
+    /// The date the model is discontinued, if it has been announced.
+    pub deprecation_date: Option<NaiveDate>,
+}
+
+impl ModelCapabilities {
+    pub fn supports(&self, modality: Modality) -> bool {
+        self.input_modalities.contains(&modality)
+    }
+
+    pub fn is_available_in(&self, region: &str) -> bool {
+        self.regions.contains(&region)
+    }
+
+    /// Whether the model is discontinued on the given date.
+    pub fn is_deprecated_at(&self, date: NaiveDate) -> bool {
+        self.deprecation_date
+            .is_some_and(|deprecation| deprecation <= date)
+    }
+
+    /// Checks a Gemini request against the capabilities, returning a description of the first unsupported feature.
+    pub fn check_generate_content(&self, request: &GenerateContentRequest) -> Result<(), String> {
+        let params = &request.base_model_params;
+
+        let system_parts = match &params.system_instruction {
+            Some(SystemInstruction::Content(content)) => content.parts.as_slice(),
+            _ => &[],
+        };
+        let parts = request
+            .contents
+            .iter()
+            .flat_map(|content| content.parts.iter())
+            .chain(system_parts);
+
+        for part in parts {
+            let mime_type = match part {
+                Part::InlineDataPart(part) => &part.inline_data.mime_type,
+                Part::FileDataPart(part) => &part.file_data.mime_type,
+                _ => continue,
+            };
+            self.check_mime_type(mime_type)?;
+        }
+
+        let has_functions = params.tools.iter().flatten().any(|tool| {
+            matches!(tool, Tool::FunctionDeclarationsTool(tool) if tool.function_declarations.as_ref().is_some_and(|d| !d.is_empty()))
+        });
+        if has_functions && !self.function_calling {
+            return Err("function calling is not supported".to_string());
+        }
+
+        if let Some(config) = &params.generation_config {
+            let json_response = config
+                .response_mime_type
+                .as_deref()
+                .is_some_and(|mime_type| mime_type == "application/json");
+            if (json_response || config.response_schema.is_some()) && !self.structured_output {
+                return Err("structured output is not supported".to_string());
+            }
+            if let Some(max_output_tokens) = config.max_output_tokens {
+                self.check_max_output_tokens(max_output_tokens)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks a Claude request against the capabilities, returning a description of the first unsupported feature.
+    pub fn check_raw_predict(&self, request: &RawPredictRequest) -> Result<(), String> {
+        let system_blocks = match &request.system {
+            ClaudeSystemPrompt::Content(blocks) => blocks.as_slice(),
+            ClaudeSystemPrompt::Text(_) => &[],
+        };
+        let blocks = request
+            .messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                ClaudeMessageContent::Blocks(blocks) => Some(blocks.iter()),
+                ClaudeMessageContent::Text(_) => None,
+            })
+            .flatten()
+            .chain(system_blocks);
+
+        for block in blocks {
+            let media_type = block
+                .source
+                .as_ref()
+                .and_then(|source| source.media_type.as_deref());
+            match (block.c_type.as_str(), media_type) {
+                (_, Some(media_type)) => self.check_mime_type(media_type)?,
+                ("image", None) => self.check_modality(Modality::Image)?,
+                ("document", None) => self.check_modality(Modality::Pdf)?,
+                _ => {}
+            }
+        }
+
+        if request
+            .tools
+            .as_ref()
+            .is_some_and(|tools| !tools.is_empty())
+            && !self.function_calling
+        {
+            return Err("function calling is not supported".to_string());
+        }
+
+        self.check_max_output_tokens(request.max_tokens)
+    }
+
+    fn check_mime_type(&self, mime_type: &str) -> Result<(), String> {
+        match Modality::from_mime_type(mime_type) {
+            Some(modality) if !self.supports(modality) => Err(format!(
+                "{} input is not supported ({})",
+                modality, mime_type
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_modality(&self, modality: Modality) -> Result<(), String> {
+        match self.supports(modality) {
+            true => Ok(()),
+            false => Err(format!("{} input is not supported", modality)),
+        }
+    }
+
+    fn check_max_output_tokens(&self, max_output_tokens: u32) -> Result<(), String> {
+        match max_output_tokens > self.max_output_tokens {
+            true => Err(format!(
+                "max output tokens {} exceeds the limit of {}",
+                max_output_tokens, self.max_output_tokens
+            )),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Models which expose their [ModelCapabilities].
+pub trait Capabilities {
+    /// Returns `None` for custom models, whose capabilities are unknown.
+    fn capabilities(&self) -> Option<ModelCapabilities>;
+}
+
+const ALL_MODALITIES: &[Modality] = &[
+    Modality::Text,
+    Modality::Image,
+    Modality::Audio,
+    Modality::Video,
+    Modality::Pdf,
+];
+
+const GEMINI_REGIONS: &[&str] = &[
+    "us-central1",
+    "us-east1",
+    "us-east4",
+    "us-east5",
+    "us-south1",
+    "us-west1",
+    "us-west4",
+    "northamerica-northeast1",
+    "europe-central2",
+    "europe-north1",
+    "europe-southwest1",
+    "europe-west1",
+    "europe-west2",
+    "europe-west3",
+    "europe-west4",
+    "europe-west6",
+    "europe-west8",
+    "europe-west9",
+    "asia-east1",
+    "asia-east2",
+    "asia-northeast1",
+    "asia-northeast3",
+    "asia-south1",
+    "asia-southeast1",
+    "australia-southeast1",
+    "me-central1",
+    "me-central2",
+    "me-west1",
+    "southamerica-east1",
+];
+
+const fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+impl GeminiModel {
+    /// Every model with known capabilities.
+    pub const KNOWN: &'static [GeminiModel] = &[
+        GeminiModel::Gemini20FlashLite001,
+        GeminiModel::Gemini20Flash001,
+        GeminiModel::Gemini15Flash001,
+        GeminiModel::Gemini15Flash002,
+        GeminiModel::Gemini15ProLatestStable,
+        GeminiModel::Gemini15Pro001,
+        GeminiModel::Gemini15Pro002,
+        GeminiModel::Gemini10ProVision001,
+        GeminiModel::Gemini10Pro,
+        GeminiModel::Gemini10Pro001,
+        GeminiModel::Gemini10Pro002,
+    ];
+}
+
+impl Capabilities for GeminiModel {
+    fn capabilities(&self) -> Option<ModelCapabilities> {
+        let multimodal = |context_window, deprecation_date| ModelCapabilities {
+            context_window,
+            max_output_tokens: 8_192,
+            input_modalities: ALL_MODALITIES,
+            function_calling: true,
+            structured_output: true,
+            regions: GEMINI_REGIONS,
+            deprecation_date,
+        };
+        let gemini_10_pro = ModelCapabilities {
+            context_window: 32_760,
+            max_output_tokens: 8_192,
+            input_modalities: &[Modality::Text],
+            function_calling: true,
+            structured_output: false,
+            regions: GEMINI_REGIONS,
+            deprecation_date: date(2025, 4, 21),
+        };
+
+        let capabilities = match self {
+            GeminiModel::Gemini20FlashLite001 => multimodal(1_048_576, date(2026, 2, 25)),
+            GeminiModel::Gemini20Flash001 => multimodal(1_048_576, date(2026, 2, 5)),
+            GeminiModel::Gemini15Flash001 => multimodal(1_048_576, date(2025, 5, 24)),
+            GeminiModel::Gemini15Flash002 => multimodal(1_048_576, date(2025, 9, 24)),
+            GeminiModel::Gemini15ProLatestStable => multimodal(2_097_152, date(2025, 9, 24)),
+            GeminiModel::Gemini15Pro001 => multimodal(2_097_152, date(2025, 5, 24)),
+            GeminiModel::Gemini15Pro002 => multimodal(2_097_152, date(2025, 9, 24)),
+            GeminiModel::Gemini10ProVision001 => ModelCapabilities {
+                context_window: 16_384,
+                max_output_tokens: 2_048,
+                input_modalities: &[Modality::Text, Modality::Image, Modality::Video],
+                function_calling: false,
+                ..gemini_10_pro
+            },
+            GeminiModel::Gemini10Pro
+            | GeminiModel::Gemini10Pro001
+            | GeminiModel::Gemini10Pro002 => gemini_10_pro,
+            GeminiModel::Custom(_) => return None,
+        };
+
+        Some(capabilities)
+    }
+}
+
+impl ClaudeModel {
+    /// Every model with known capabilities.
+    pub const KNOWN: &'static [ClaudeModel] = &[
+        ClaudeModel::Claude37Sonnet,
+        ClaudeModel::Claude35SonnetV2,
+        ClaudeModel::Claude35Sonnet,
+        ClaudeModel::Claude3Opus,
+        ClaudeModel::Claude3Haiku,
+        ClaudeModel::Claude3Sonnet,
+    ];
+}
+
+impl Capabilities for ClaudeModel {
+    fn capabilities(&self) -> Option<ModelCapabilities> {
+        let claude =
+            |max_output_tokens, input_modalities, regions, deprecation_date| ModelCapabilities {
+                context_window: 200_000,
+                max_output_tokens,
+                input_modalities,
+                function_calling: true,
+                structured_output: false,
+                regions,
+                deprecation_date,
+            };
+        let text_image: &'static [Modality] = &[Modality::Text, Modality::Image];
+        let text_image_pdf: &'static [Modality] = &[Modality::Text, Modality::Image, Modality::Pdf];
+
+        let capabilities = match self {
+            ClaudeModel::Claude37Sonnet => claude(
+                64_000,
+                text_image_pdf,
+                &["us-east5", "europe-west1"],
+                date(2026, 2, 19),
+            ),
+            ClaudeModel::Claude35SonnetV2 => claude(
+                8_192,
+                text_image_pdf,
+                &["us-east5", "europe-west1"],
+                date(2025, 10, 22),
+            ),
+            ClaudeModel::Claude35Sonnet => claude(
+                8_192,
+                text_image,
+                &["us-east5", "europe-west1", "asia-southeast1"],
+                date(2025, 10, 22),
+            ),
+            ClaudeModel::Claude3Opus => claude(4_096, text_image, &["us-east5"], date(2026, 1, 5)),
+            ClaudeModel::Claude3Haiku => claude(
+                4_096,
+                text_image,
+                &["us-east5", "europe-west1", "asia-southeast1"],
+                None,
+            ),
+            ClaudeModel::Claude3Sonnet => {
+                claude(4_096, text_image, &["us-east5"], date(2025, 7, 21))
+            }
+            ClaudeModel::Custom(_) => return None,
+        };
+
+        Some(capabilities)
+    }
+}
+
+impl Capabilities for ModelId {
+    fn capabilities(&self) -> Option<ModelCapabilities> {
+        match self {
+            ModelId::Gemini(model) => model.capabilities(),
+            ModelId::Claude(model) => model.capabilities(),
+        }
+    }
+}
+
+impl ModelId {
+    /// Every model with known capabilities.
+    pub fn known() -> impl Iterator<Item = ModelId> {
+        GeminiModel::KNOWN
+            .iter()
+            .cloned()
+            .map(ModelId::Gemini)
+            .chain(ClaudeModel::KNOWN.iter().cloned().map(ModelId::Claude))
+    }
+
+    /// Known models which satisfy the predicate, in the order of [ModelId::known].
+    pub fn select(predicate: impl Fn(&ModelCapabilities) -> bool) -> Vec<ModelId> {
+        Self::known()
+            .filter(|model| model.capabilities().is_some_and(|c| predicate(&c)))
+            .collect()
+    }
+}
@@ -0,0 +1,584 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use derive_builder::Builder;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{
+    error::ChatError,
+    types::{
+        claude::{
+            ClaudeContent, ClaudeMessage, ClaudeMessageContent, ClaudeModel, ClaudeStopReason,
+            ClaudeUsage,
+        },
+        content::{
+            Content, FileData, FileDataPart, FinishReason, FunctionCall, FunctionCallPart,
+            FunctionResponse, FunctionResponsePart, GenerativeContentBlob, InlineDataPart, Part,
+            TextPart, UsageMetadata,
+        },
+        gemini::GeminiModel,
+        media::mime_type_from_extension,
+    },
+};
+
+/// A model from any of the supported providers.
+///
+/// Parses from and serializes to the plain model id, e.g. `gemini-2.0-flash-001` or `claude-3-7-sonnet@20250219`,
+/// which allows picking the provider through configuration alone.
+/// Unknown ids starting with `claude` are treated as Claude models, everything else as Gemini models.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ModelId {
+    Gemini(GeminiModel),
+    Claude(ClaudeModel),
+}
+
+impl fmt::Display for ModelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelId::Gemini(model) => model.fmt(f),
+            ModelId::Claude(model) => model.fmt(f),
+        }
+    }
+}
+
+impl FromStr for ModelId {
+    type Err = ChatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(ChatError::UnknownModel(s.to_string()));
+        }
+
+        let gemini =
+            GeminiModel::from_str(s).map_err(|_| ChatError::UnknownModel(s.to_string()))?;
+        if !matches!(gemini, GeminiModel::Custom(_)) {
+            return Ok(ModelId::Gemini(gemini));
+        }
+
+        // unknown ids are routed by their prefix
+        let claude =
+            ClaudeModel::from_str(s).map_err(|_| ChatError::UnknownModel(s.to_string()))?;
+        if !matches!(claude, ClaudeModel::Custom(_)) || s.starts_with("claude") {
+            return Ok(ModelId::Claude(claude));
+        }
+
+        Ok(ModelId::Gemini(gemini))
+    }
+}
+
+impl Serialize for ModelId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ModelId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<GeminiModel> for ModelId {
+    fn from(model: GeminiModel) -> Self {
+        ModelId::Gemini(model)
+    }
+}
+
+impl From<ClaudeModel> for ModelId {
+    fn from(model: ClaudeModel) -> Self {
+        ModelId::Claude(model)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+/// A provider independent piece of message content.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatPart {
+    Text {
+        text: String,
+    },
+    /// Base64 encoded data.
+    InlineData {
+        mime_type: String,
+        data: String,
+    },
+    /// Data referenced by URI.
+    FileData {
+        mime_type: String,
+        uri: String,
+    },
+    /// A function call requested by the model. Gemini does not assign ids to calls.
+    ToolCall {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        name: String,
+        arguments: Value,
+    },
+    /// The result of a function call.
+    /// Gemini identifies results by name, Claude by the id of the call.
+    ToolResult {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default)]
+        name: String,
+        content: Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        is_error: Option<bool>,
+    },
+}
+
+impl ChatPart {
+    pub fn text(text: impl Into<String>) -> Self {
+        ChatPart::Text { text: text.into() }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub parts: Vec<ChatPart>,
+}
+
+impl ChatMessage {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::User,
+            parts: vec![ChatPart::text(text)],
+        }
+    }
+
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::Assistant,
+            parts: vec![ChatPart::text(text)],
+        }
+    }
+
+    /// Returns the concatenated text parts of the message.
+    pub fn text(&self) -> String {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                ChatPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A function the model may call.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatTool {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON schema of the function arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Builder)]
+#[builder(setter(into, strip_option), default)]
+pub struct ChatRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ChatTool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+}
+
+impl ChatRequest {
+    pub fn builder() -> ChatRequestBuilder {
+        ChatRequestBuilder::default()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatFinishReason {
+    Stop,
+    MaxTokens,
+    ToolUse,
+    ContentFilter,
+    Other,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChatUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub message: ChatMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<ChatFinishReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ChatUsage>,
+}
+
+impl ChatResponse {
+    /// Returns the concatenated text parts of the reply.
+    pub fn text(&self) -> String {
+        self.message.text()
+    }
+}
+
+/// A partial reply received while streaming.
+/// Text arrives in fragments, tool calls are only emitted once complete.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChatChunk {
+    pub parts: Vec<ChatPart>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<ChatFinishReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ChatUsage>,
+}
+
+impl From<Part> for ChatPart {
+    fn from(part: Part) -> Self {
+        match part {
+            Part::TextPart(p) => ChatPart::Text { text: p.text },
+            Part::InlineDataPart(p) => ChatPart::InlineData {
+                mime_type: p.inline_data.mime_type,
+                data: p.inline_data.data,
+            },
+            Part::FileDataPart(p) => ChatPart::FileData {
+                mime_type: p.file_data.mime_type,
+                uri: p.file_data.file_uri,
+            },
+            Part::FunctionCallPart(p) => ChatPart::ToolCall {
+                id: None,
+                name: p.function_call.name,
+                arguments: p.function_call.args,
+            },
+            Part::FunctionResponsePart(p) => ChatPart::ToolResult {
+                id: None,
+                name: p.function_response.name,
+                content: unwrap_tool_result(p.function_response.response),
+                is_error: None,
+            },
+        }
+    }
+}
+
+/// Tool call ids and the error flag of tool results are dropped, Gemini has no equivalent.
+/// Tool results which are not JSON objects are wrapped in a `__tool_result` field, and unwrapped again when converted back.
+impl From<ChatPart> for Part {
+    fn from(part: ChatPart) -> Self {
+        match part {
+            ChatPart::Text { text } => Part::TextPart(TextPart { text }),
+            ChatPart::InlineData { mime_type, data } => Part::InlineDataPart(InlineDataPart {
+                inline_data: GenerativeContentBlob { mime_type, data },
+            }),
+            ChatPart::FileData { mime_type, uri } => Part::FileDataPart(FileDataPart {
+                file_data: FileData {
+                    mime_type,
+                    file_uri: uri,
+                },
+                video_metadata: None,
+            }),
+            ChatPart::ToolCall {
+                name, arguments, ..
+            } => Part::FunctionCallPart(FunctionCallPart {
+                function_call: FunctionCall {
+                    name,
+                    args: arguments,
+                },
+            }),
+            ChatPart::ToolResult { name, content, .. } => {
+                Part::FunctionResponsePart(FunctionResponsePart {
+                    function_response: FunctionResponse {
+                        name,
+                        // Gemini only accepts objects as function responses
+                        response: match content {
+                            Value::Object(_) => content,
+                            other => serde_json::json!({ TOOL_RESULT_KEY: other }),
+                        },
+                    },
+                })
+            }
+        }
+    }
+}
+
+/// Field wrapping tool results which are not JSON objects, chosen to not clash with real tool output
+const TOOL_RESULT_KEY: &str = "__tool_result";
+
+/// Undoes the [TOOL_RESULT_KEY] wrapper of tool results which are not JSON objects
+fn unwrap_tool_result(response: Value) -> Value {
+    match response {
+        Value::Object(mut object) if object.len() == 1 && object.contains_key(TOOL_RESULT_KEY) => {
+            object.remove(TOOL_RESULT_KEY).unwrap_or_default()
+        }
+        other => other,
+    }
+}
+
+/// `model` maps to [ChatRole::Assistant], every other role to [ChatRole::User].
+impl From<Content> for ChatMessage {
+    fn from(content: Content) -> Self {
+        Self {
+            role: match content.role.as_str() {
+                "model" => ChatRole::Assistant,
+                _ => ChatRole::User,
+            },
+            parts: content.parts.into_iter().map(ChatPart::from).collect(),
+        }
+    }
+}
+
+impl From<ChatMessage> for Content {
+    fn from(message: ChatMessage) -> Self {
+        Self {
+            parts: message.parts.into_iter().map(Part::from).collect(),
+            role: match message.role {
+                ChatRole::User => "user".to_string(),
+                ChatRole::Assistant => "model".to_string(),
+            },
+        }
+    }
+}
+
+impl TryFrom<ClaudeContent> for ChatPart {
+    type Error = ChatError;
+
+    fn try_from(block: ClaudeContent) -> Result<Self, Self::Error> {
+        match block.c_type.as_str() {
+            "text" => Ok(ChatPart::Text { text: block.text }),
+            "image" | "document" => {
+                let source = block.source.ok_or_else(|| {
+                    ChatError::Conversion(format!("{} block without source", block.c_type))
+                })?;
+
+                match (source.data, source.url) {
+                    (Some(data), _) => Ok(ChatPart::InlineData {
+                        mime_type: source.media_type.unwrap_or_default(),
+                        data,
+                    }),
+                    (None, Some(uri)) => Ok(ChatPart::FileData {
+                        mime_type: guess_mime_type(&block.c_type, &uri).to_string(),
+                        uri,
+                    }),
+                    (None, None) => Err(ChatError::Conversion(format!(
+                        "{} block without data or url",
+                        block.c_type
+                    ))),
+                }
+            }
+            "tool_use" => Ok(ChatPart::ToolCall {
+                id: block.id,
+                name: block.name.unwrap_or_default(),
+                arguments: block.input.unwrap_or(Value::Object(Default::default())),
+            }),
+            "tool_result" => Ok(ChatPart::ToolResult {
+                id: block.tool_use_id,
+                name: block.name.unwrap_or_default(),
+                content: block.content.unwrap_or(Value::Null),
+                is_error: block.is_error,
+            }),
+            other => Err(ChatError::Conversion(format!(
+                "unsupported content block type {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Tool results which are neither a string nor a list of content blocks are sent as serialized JSON.
+/// Tool calls without an id get a unique one, tool results need the id of their call.
+/// Claude only accepts images and PDF documents as media, audio and video are rejected.
+impl TryFrom<ChatPart> for ClaudeContent {
+    type Error = ChatError;
+
+    fn try_from(part: ChatPart) -> Result<Self, Self::Error> {
+        match part {
+            ChatPart::Text { text } => Ok(ClaudeContent::text(text)),
+            ChatPart::InlineData { mime_type, data } => {
+                check_claude_media(&mime_type)?;
+                Ok(ClaudeContent::base64(mime_type, data))
+            }
+            ChatPart::FileData { mime_type, uri } => {
+                check_claude_media(&mime_type)?;
+                Ok(ClaudeContent::url(&mime_type, uri))
+            }
+            ChatPart::ToolCall {
+                id,
+                name,
+                arguments,
+            } => Ok(ClaudeContent::tool_use(
+                id.unwrap_or_else(tool_use_id),
+                name,
+                arguments,
+            )),
+            ChatPart::ToolResult {
+                id,
+                name,
+                content,
+                is_error,
+            } => {
+                let id = id.ok_or_else(|| {
+                    ChatError::Conversion(format!(
+                        "tool result for {} without a tool call id",
+                        name
+                    ))
+                })?;
+
+                Ok(ClaudeContent {
+                    is_error,
+                    // Claude only accepts a string or a list of content blocks as tool results
+                    ..ClaudeContent::tool_result(
+                        id,
+                        match content {
+                            Value::String(_) | Value::Array(_) => content,
+                            other => Value::String(other.to_string()),
+                        },
+                    )
+                })
+            }
+        }
+    }
+}
+
+fn check_claude_media(mime_type: &str) -> Result<(), ChatError> {
+    match mime_type.starts_with("audio/") || mime_type.starts_with("video/") {
+        true => Err(ChatError::Conversion(format!(
+            "Claude does not accept {} content",
+            mime_type
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// Generates a unique id for tool calls which come without one, e.g. from Gemini.
+pub(crate) fn tool_use_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!("toolu_{:024x}", COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+impl TryFrom<ClaudeMessage> for ChatMessage {
+    type Error = ChatError;
+
+    fn try_from(message: ClaudeMessage) -> Result<Self, Self::Error> {
+        let role = match message.role.as_str() {
+            "user" => ChatRole::User,
+            "assistant" => ChatRole::Assistant,
+            other => return Err(ChatError::Conversion(format!("unknown role {}", other))),
+        };
+
+        let parts = match message.content {
+            ClaudeMessageContent::Text(text) => vec![ChatPart::Text { text }],
+            ClaudeMessageContent::Blocks(blocks) => blocks
+                .into_iter()
+                .map(ChatPart::try_from)
+                .collect::<Result<_, _>>()?,
+        };
+
+        Ok(Self { role, parts })
+    }
+}
+
+/// A message consisting of a single text part is sent as plain text.
+impl TryFrom<ChatMessage> for ClaudeMessage {
+    type Error = ChatError;
+
+    fn try_from(message: ChatMessage) -> Result<Self, Self::Error> {
+        let role = match message.role {
+            ChatRole::User => "user".to_string(),
+            ChatRole::Assistant => "assistant".to_string(),
+        };
+
+        let content = match message.parts.as_slice() {
+            [ChatPart::Text { text }] => ClaudeMessageContent::Text(text.clone()),
+            _ => ClaudeMessageContent::Blocks(
+                message
+                    .parts
+                    .into_iter()
+                    .map(ClaudeContent::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+
+        Ok(Self { role, content })
+    }
+}
+
+impl From<FinishReason> for ChatFinishReason {
+    fn from(reason: FinishReason) -> Self {
+        match reason {
+            FinishReason::Stop => ChatFinishReason::Stop,
+            FinishReason::MaxTokens => ChatFinishReason::MaxTokens,
+            FinishReason::Safety
+            | FinishReason::Recitation
+            | FinishReason::Blocklist
+            | FinishReason::ProhibitedContent
+            | FinishReason::Spii => ChatFinishReason::ContentFilter,
+            FinishReason::FinishReasonUnspecified | FinishReason::Other => ChatFinishReason::Other,
+        }
+    }
+}
+
+impl From<ClaudeStopReason> for ChatFinishReason {
+    fn from(reason: ClaudeStopReason) -> Self {
+        match reason {
+            ClaudeStopReason::EndTurn | ClaudeStopReason::StopSequence => ChatFinishReason::Stop,
+            ClaudeStopReason::MaxTokens => ChatFinishReason::MaxTokens,
+            ClaudeStopReason::ToolUse => ChatFinishReason::ToolUse,
+        }
+    }
+}
+
+impl From<UsageMetadata> for ChatUsage {
+    fn from(usage: UsageMetadata) -> Self {
+        let input_tokens = usage.prompt_token_count.unwrap_or_default();
+        let output_tokens = usage.candidates_token_count.unwrap_or_default();
+        Self {
+            input_tokens,
+            output_tokens,
+            total_tokens: usage
+                .total_token_count
+                .unwrap_or(input_tokens + output_tokens),
+        }
+    }
+}
+
+impl From<ClaudeUsage> for ChatUsage {
+    fn from(usage: ClaudeUsage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            total_tokens: usage.input_tokens + usage.output_tokens,
+        }
+    }
+}
+
+/// The MIME type of a referenced file from its extension, or the usual type of the block
+pub(crate) fn guess_mime_type(block_type: &str, uri: &str) -> &'static str {
+    match mime_type_from_extension(uri) {
+        Some(mime_type) => mime_type,
+        None if block_type == "document" => "application/pdf",
+        None => "image/jpeg",
+    }
+}
@@ -1,5 +1,6 @@
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use strum_macros::IntoStaticStr;
 
 #[derive(
@@ -10,7 +11,11 @@ use strum_macros::IntoStaticStr;
     strum_macros::EnumString,
     Clone,
     Debug,
+    PartialEq,
+    Eq,
+    Hash,
 )]
+#[strum(serialize_all = "kebab-case")]
 pub enum ClaudeModel {
     #[serde(rename = "claude-3-7-sonnet@20250219")]
     #[strum(serialize = "claude-3-7-sonnet@20250219")]
@@ -30,6 +35,12 @@ pub enum ClaudeModel {
     #[serde(rename = "claude-3-sonnet@20240229")]
     #[strum(serialize = "claude-3-sonnet@20240229")]
     Claude3Sonnet,
+
+    /// Any other model id, e.g. `claude-sonnet-4@20250514`.
+    /// Converts into the static str `custom`, the kebab-case variant name, use the [std::fmt::Display] implementation to get the id.
+    #[serde(untagged)]
+    #[strum(default)]
+    Custom(String),
 }
 
 pub trait WithoutVersion {
@@ -46,6 +57,10 @@ impl WithoutVersion for ClaudeModel {
             ClaudeModel::Claude3Haiku => "claude-3-haiku".to_string(),
             ClaudeModel::Claude3Sonnet => "claude-3-sonnet".to_string(),
             ClaudeModel::Claude37Sonnet => "claude-3-7-sonnet".to_string(),
+            ClaudeModel::Custom(model) => model
+                .split_once('@')
+                .map_or(model.as_str(), |(name, _)| name)
+                .to_string(),
         }
     }
 
@@ -56,12 +71,67 @@ impl WithoutVersion for ClaudeModel {
             "claude-3-opus" => ClaudeModel::Claude3Opus,
             "claude-3-haiku" => ClaudeModel::Claude3Haiku,
             "claude-3-sonnet" => ClaudeModel::Claude3Sonnet,
-            _ => ClaudeModel::Claude35SonnetV2,
+            "claude-3-7-sonnet" => ClaudeModel::Claude37Sonnet,
+            _ => ClaudeModel::Custom(s),
+        }
+    }
+}
+
+impl ClaudeModel {
+    /// The id of the model on the Anthropic API, e.g. `claude-3-5-sonnet-20241022` for [ClaudeModel::Claude35SonnetV2]
+    pub fn anthropic_id(&self) -> String {
+        match self {
+            ClaudeModel::Claude37Sonnet => "claude-3-7-sonnet-20250219".to_string(),
+            ClaudeModel::Claude35SonnetV2 => "claude-3-5-sonnet-20241022".to_string(),
+            ClaudeModel::Claude35Sonnet => "claude-3-5-sonnet-20240620".to_string(),
+            ClaudeModel::Claude3Opus => "claude-3-opus-20240229".to_string(),
+            ClaudeModel::Claude3Haiku => "claude-3-haiku-20240307".to_string(),
+            ClaudeModel::Claude3Sonnet => "claude-3-sonnet-20240229".to_string(),
+            // aliases such as `claude-sonnet-4-0` have no date and are kept as they are
+            ClaudeModel::Custom(model) => match model.split_once('@') {
+                Some((name, date)) => format!("{}-{}", name, date),
+                None => model.clone(),
+            },
+        }
+    }
+
+    /// The model for an Anthropic API id or alias, the date suffix of unknown ids is moved behind an `@` as on Vertex AI.
+    /// Unknown aliases such as `claude-sonnet-4-0` have no date and are kept as they are, Vertex AI doesn't serve them.
+    pub fn from_anthropic_id(id: &str) -> Self {
+        // aliases point to the latest version of a model
+        let id = match id {
+            "claude-3-7-sonnet-latest" => "claude-3-7-sonnet-20250219",
+            "claude-3-5-sonnet-latest" => "claude-3-5-sonnet-20241022",
+            "claude-3-5-haiku-latest" => "claude-3-5-haiku-20241022",
+            "claude-3-opus-latest" => "claude-3-opus-20240229",
+            id => id,
+        };
+
+        let known = [
+            ClaudeModel::Claude37Sonnet,
+            ClaudeModel::Claude35SonnetV2,
+            ClaudeModel::Claude35Sonnet,
+            ClaudeModel::Claude3Opus,
+            ClaudeModel::Claude3Haiku,
+            ClaudeModel::Claude3Sonnet,
+        ];
+        if let Some(model) = known.into_iter().find(|model| model.anthropic_id() == id) {
+            return model;
+        }
+
+        match id.rsplit_once('-') {
+            Some((name, date)) if date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) => {
+                ClaudeModel::Custom(format!("{}@{}", name, date))
+            }
+            _ => ClaudeModel::Custom(id.to_string()),
         }
     }
 }
 
-const ANTHROPIC_VERSION: &str = "vertex-2023-10-16";
+pub const ANTHROPIC_VERSION: &str = "vertex-2023-10-16";
+
+/// The `anthropic-version` header sent to the Anthropic API, Vertex AI takes [ANTHROPIC_VERSION] in the body instead.
+pub const ANTHROPIC_API_VERSION: &str = "2023-06-01";
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
@@ -84,19 +154,199 @@ pub struct RawPredictRequest {
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ClaudeTool>>,
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ClaudeToolChoice>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Builder, Debug)]
 pub struct ClaudeMessage {
     pub role: String,
-    pub content: String,
+    #[builder(setter(into))]
+    pub content: ClaudeMessageContent,
 }
 
+/// The content of a message, either plain text or a list of content blocks.
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum ClaudeMessageContent {
+    Text(String),
+    Blocks(Vec<ClaudeContent>),
+}
+
+impl ClaudeMessageContent {
+    /// Returns the content as a list of content blocks.
+    pub fn into_blocks(self) -> Vec<ClaudeContent> {
+        match self {
+            ClaudeMessageContent::Text(text) => vec![ClaudeContent::text(text)],
+            ClaudeMessageContent::Blocks(blocks) => blocks,
+        }
+    }
+
+    /// Returns the concatenated text of the content.
+    pub fn text(&self) -> String {
+        match self {
+            ClaudeMessageContent::Text(text) => text.clone(),
+            ClaudeMessageContent::Blocks(blocks) => blocks
+                .iter()
+                .filter(|block| block.c_type == "text")
+                .map(|block| block.text.as_str())
+                .collect(),
+        }
+    }
+}
+
+impl From<String> for ClaudeMessageContent {
+    fn from(text: String) -> Self {
+        ClaudeMessageContent::Text(text)
+    }
+}
+
+impl From<&str> for ClaudeMessageContent {
+    fn from(text: &str) -> Self {
+        ClaudeMessageContent::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ClaudeContent>> for ClaudeMessageContent {
+    fn from(blocks: Vec<ClaudeContent>) -> Self {
+        ClaudeMessageContent::Blocks(blocks)
+    }
+}
+
+/// A content block. Which of the optional fields are set depends on the block type,
+/// e.g. `text`, `image`, `document`, `tool_use`, `tool_result`, or one of the stream delta types.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct ClaudeContent {
     #[serde(rename = "type")]
     pub c_type: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub text: String,
+    /// The source of an `image` or `document` block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<ClaudeContentSource>,
+    /// The id of a `tool_use` block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The name of the tool called by a `tool_use` block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The arguments of a `tool_use` block.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<Value>,
+    /// The id of the `tool_use` block a `tool_result` block answers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_use_id: Option<String>,
+    /// The content of a `tool_result` block, either a string or a list of content blocks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_error: Option<bool>,
+    /// A fragment of the `tool_use` input, sent in `input_json_delta` stream events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partial_json: Option<String>,
+}
+
+impl ClaudeContent {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            c_type: "text".to_string(),
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn tool_use(id: impl Into<String>, name: impl Into<String>, input: Value) -> Self {
+        Self {
+            c_type: "tool_use".to_string(),
+            id: Some(id.into()),
+            name: Some(name.into()),
+            input: Some(input),
+            ..Default::default()
+        }
+    }
+
+    pub fn tool_result(tool_use_id: impl Into<String>, content: Value) -> Self {
+        Self {
+            c_type: "tool_result".to_string(),
+            tool_use_id: Some(tool_use_id.into()),
+            content: Some(content),
+            ..Default::default()
+        }
+    }
+
+    /// Creates an `image` block, or a `document` block for PDFs, from base64 encoded data.
+    pub fn base64(media_type: impl Into<String>, data: impl Into<String>) -> Self {
+        let media_type = media_type.into();
+        Self {
+            c_type: block_type(&media_type).to_string(),
+            source: Some(ClaudeContentSource {
+                s_type: "base64".to_string(),
+                media_type: Some(media_type),
+                data: Some(data.into()),
+                url: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Creates an `image` block, or a `document` block for PDFs, referencing a URL.
+    pub fn url(media_type: &str, url: impl Into<String>) -> Self {
+        Self {
+            c_type: block_type(media_type).to_string(),
+            source: Some(ClaudeContentSource {
+                s_type: "url".to_string(),
+                media_type: None,
+                data: None,
+                url: Some(url.into()),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+fn block_type(media_type: &str) -> &'static str {
+    match media_type {
+        "application/pdf" => "document",
+        _ => "image",
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClaudeContentSource {
+    /// Either `base64` or `url`.
+    #[serde(rename = "type")]
+    pub s_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClaudeTool {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON schema of the tool input.
+    pub input_schema: Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClaudeToolChoice {
+    Auto,
+    Any,
+    Tool { name: String },
+    None,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -105,18 +355,42 @@ pub enum ClaudeStopReason {
     EndTurn,
     #[serde(rename = "max_tokens")]
     MaxTokens,
-    #[serde(rename = "max_time")]
+    #[serde(rename = "stop_sequence")]
     StopSequence,
     #[serde(rename = "tool_use")]
     ToolUse,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct ClaudeUsage {
     /// The number of input tokens which were used.
+    /// `message_delta` events only report output tokens, in which case this is zero.
+    #[serde(default)]
     pub input_tokens: u32,
     /// The number of output tokens which were used.
     pub output_tokens: u32,
+    /// The number of input tokens used to create a prompt cache entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u32>,
+    /// The number of input tokens read from the prompt cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u32>,
+}
+
+impl ClaudeUsage {
+    /// Applies the cumulative usage of a `message_delta` event.
+    pub fn apply(&mut self, delta: &ClaudeUsage) {
+        self.output_tokens = delta.output_tokens;
+        if delta.input_tokens > 0 {
+            self.input_tokens = delta.input_tokens;
+        }
+        if delta.cache_creation_input_tokens.is_some() {
+            self.cache_creation_input_tokens = delta.cache_creation_input_tokens;
+        }
+        if delta.cache_read_input_tokens.is_some() {
+            self.cache_read_input_tokens = delta.cache_read_input_tokens;
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Builder, Clone, Debug)]
@@ -135,6 +409,7 @@ pub struct RawPredictResponse {
 pub struct MessageDelta {
     pub stop_reason: Option<ClaudeStopReason>,
     pub stop_sequence: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<ClaudeUsage>,
 }
 
@@ -159,6 +434,8 @@ pub enum StreamRawPredictResponse {
     MessageDelta {
         index: Option<u32>,
         delta: MessageDelta,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        usage: Option<ClaudeUsage>,
     },
     #[serde(rename = "message_stop")]
     MessageStop,
@@ -166,6 +443,67 @@ pub enum StreamRawPredictResponse {
     Ping,
 }
 
+impl RawPredictResponse {
+    /// Folds an event received from `stream_raw_predict` into a response created from the `message_start` event.
+    pub fn apply(&mut self, event: &StreamRawPredictResponse) {
+        match event {
+            StreamRawPredictResponse::MessageStart { message } => *self = message.clone(),
+            StreamRawPredictResponse::ContentBlockStart {
+                index,
+                content_block,
+            } => {
+                let index = index.map(|i| i as usize).unwrap_or(self.content.len());
+                if index < self.content.len() {
+                    self.content[index] = content_block.clone();
+                } else {
+                    self.content.push(content_block.clone());
+                }
+            }
+            StreamRawPredictResponse::ContentBlockDelta { index, delta } => {
+                let block = match index {
+                    Some(i) => self.content.get_mut(*i as usize),
+                    None => self.content.last_mut(),
+                };
+                if let Some(block) = block {
+                    block.text.push_str(&delta.text);
+                    if let Some(fragment) = &delta.partial_json {
+                        block
+                            .partial_json
+                            .get_or_insert_with(String::new)
+                            .push_str(fragment);
+                    }
+                }
+            }
+            StreamRawPredictResponse::ContentBlockStop { index } => {
+                let block = match index {
+                    Some(i) => self.content.get_mut(*i as usize),
+                    None => self.content.last_mut(),
+                };
+                if let Some(block) = block {
+                    if let Some(json) = block.partial_json.take().filter(|j| !j.is_empty()) {
+                        match serde_json::from_str(&json) {
+                            Ok(input) => block.input = Some(input),
+                            Err(e) => tracing::error!(error=?e, "failed to parse tool input"),
+                        }
+                    }
+                }
+            }
+            StreamRawPredictResponse::MessageDelta { delta, usage, .. } => {
+                if delta.stop_reason.is_some() {
+                    self.stop_reason = delta.stop_reason.clone();
+                }
+                if delta.stop_sequence.is_some() {
+                    self.stop_sequence = delta.stop_sequence.clone();
+                }
+                if let Some(usage) = usage.as_ref().or(delta.usage.as_ref()) {
+                    self.usage.apply(usage);
+                }
+            }
+            StreamRawPredictResponse::MessageStop | StreamRawPredictResponse::Ping => {}
+        }
+    }
+}
+
 impl RawPredictRequest {
     pub fn builder() -> RawPredictRequestBuilder {
         RawPredictRequestBuilder::default()
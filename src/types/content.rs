@@ -1,7 +1,7 @@
 use derive_builder::Builder;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 // Assume GoogleAuthOptions is defined elsewhere
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
@@ -18,6 +18,7 @@ pub enum HarmCategory {
     HarmCategoryDangerousContent,
     HarmCategoryHarassment,
     HarmCategorySexuallyExplicit,
+    HarmCategoryCivicIntegrity,
 }
 
 // Enums for Harm Block Threshold
@@ -79,6 +80,20 @@ pub enum FinishReason {
     Spii,
 }
 
+impl FinishReason {
+    /// Whether the candidate was stopped because its content was blocked.
+    pub fn is_blocked(&self) -> bool {
+        matches!(
+            self,
+            FinishReason::Safety
+                | FinishReason::Recitation
+                | FinishReason::Blocklist
+                | FinishReason::ProhibitedContent
+                | FinishReason::Spii
+        )
+    }
+}
+
 // VertexInit struct
 #[derive(Clone, Serialize, Deserialize, Debug, Default, Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -148,6 +163,7 @@ impl GenerateContentRequest {
 #[builder(setter(into, strip_option), default)]
 pub struct Content {
     pub parts: Vec<Part>,
+    #[serde(default)]
     pub role: String,
 }
 
@@ -162,6 +178,20 @@ pub enum Part {
     FunctionCallPart(FunctionCallPart),
 }
 
+impl From<String> for Part {
+    fn from(text: String) -> Self {
+        Part::TextPart(TextPart { text })
+    }
+}
+
+impl From<&str> for Part {
+    fn from(text: &str) -> Self {
+        Part::TextPart(TextPart {
+            text: text.to_string(),
+        })
+    }
+}
+
 // TextPart struct
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TextPart {
@@ -189,6 +219,78 @@ pub struct GenerativeContentBlob {
 pub struct FileDataPart {
     #[serde(rename = "fileData")]
     pub file_data: FileData,
+    #[serde(
+        rename = "videoMetadata",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub video_metadata: Option<VideoMetadata>,
+}
+
+/// Clips a video to a time range and sets the rate its frames are sampled at, one frame per second by default.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct VideoMetadata {
+    #[serde(
+        rename = "startOffset",
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_offset",
+        deserialize_with = "deserialize_offset"
+    )]
+    pub start_offset: Option<Duration>,
+    #[serde(
+        rename = "endOffset",
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_offset",
+        deserialize_with = "deserialize_offset"
+    )]
+    pub end_offset: Option<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f32>,
+}
+
+impl VideoMetadata {
+    /// Only the part of the video between the offsets
+    pub fn clip(start_offset: Duration, end_offset: Duration) -> Self {
+        Self {
+            start_offset: Some(start_offset),
+            end_offset: Some(end_offset),
+            fps: None,
+        }
+    }
+
+    /// Samples the given number of frames per second
+    pub fn with_fps(mut self, fps: f32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+}
+
+/// Offsets are encoded as protobuf durations, e.g. `12.5s`.
+fn serialize_offset<S: Serializer>(
+    offset: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match offset {
+        Some(offset) => serializer.serialize_str(&format!("{}s", offset.as_secs_f64())),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_offset<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(offset) => offset
+            .trim_end_matches('s')
+            .parse::<f64>()
+            .ok()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid offset {}", offset))),
+        None => Ok(None),
+    }
 }
 
 // FileData struct
@@ -259,6 +361,61 @@ pub struct GenerationConfig {
     pub response_mime_type: Option<String>,
     #[serde(rename = "responseSchema", skip_serializing_if = "Option::is_none")]
     pub response_schema: Option<ResponseSchema>,
+    /// Lets models which support it respond with images or audio, see [GenerateContentResponse::images].
+    #[serde(rename = "responseModalities", skip_serializing_if = "Option::is_none")]
+    pub response_modalities: Option<Vec<ResponseModality>>,
+    #[serde(rename = "speechConfig", skip_serializing_if = "Option::is_none")]
+    pub speech_config: Option<SpeechConfig>,
+}
+
+/// The kinds of output a model may respond with.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ResponseModality {
+    Text,
+    Image,
+    Audio,
+}
+
+/// How the model speaks when it responds with audio.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Builder)]
+#[builder(setter(into, strip_option), default)]
+pub struct SpeechConfig {
+    #[serde(rename = "voiceConfig", skip_serializing_if = "Option::is_none")]
+    pub voice_config: Option<VoiceConfig>,
+    #[serde(rename = "languageCode", skip_serializing_if = "Option::is_none")]
+    pub language_code: Option<String>,
+}
+
+impl SpeechConfig {
+    /// Speaks with one of the prebuilt voices, e.g. `Puck` or `Aoede`
+    pub fn voice(name: impl Into<String>) -> Self {
+        Self {
+            voice_config: Some(VoiceConfig {
+                prebuilt_voice_config: Some(PrebuiltVoiceConfig {
+                    voice_name: name.into(),
+                }),
+            }),
+            language_code: None,
+        }
+    }
+}
+
+/// The voice the model speaks with.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct VoiceConfig {
+    #[serde(
+        rename = "prebuiltVoiceConfig",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub prebuilt_voice_config: Option<PrebuiltVoiceConfig>,
+}
+
+/// One of the voices provided by the api.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct PrebuiltVoiceConfig {
+    #[serde(rename = "voiceName")]
+    pub voice_name: String,
 }
 
 // ResponseSchema struct
@@ -445,6 +602,87 @@ pub struct GenerateContentResponse {
     pub usage_metadata: Option<UsageMetadata>,
 }
 
+impl GenerateContentResponse {
+    /// Returns the concatenated text parts of the first candidate, if any.
+    pub fn text(&self) -> Option<String> {
+        let content = self.candidates.as_ref()?.first()?.content.as_ref()?;
+
+        let text = content
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::TextPart(p) => Some(p.text.as_str()),
+                _ => None,
+            })
+            .collect::<String>();
+
+        Some(text)
+    }
+
+    /// Folds a chunk received from `stream_generate_content` into this response.
+    /// Adjacent text parts of a candidate are concatenated, every other field takes the latest value received.
+    pub fn merge(&mut self, chunk: GenerateContentResponse) {
+        if let Some(chunk_candidates) = chunk.candidates {
+            let candidates = self.candidates.get_or_insert_with(Vec::new);
+
+            for (position, candidate) in chunk_candidates.into_iter().enumerate() {
+                let index = candidate.index.unwrap_or(position as u32) as usize;
+                while candidates.len() <= index {
+                    candidates.push(GenerateContentCandidate {
+                        index: Some(candidates.len() as u32),
+                        ..Default::default()
+                    });
+                }
+
+                candidates[index].merge(candidate);
+            }
+        }
+
+        if chunk.prompt_feedback.is_some() {
+            self.prompt_feedback = chunk.prompt_feedback;
+        }
+
+        if chunk.usage_metadata.is_some() {
+            self.usage_metadata = chunk.usage_metadata;
+        }
+    }
+}
+
+// CountTokensRequest struct
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct CountTokensRequest {
+    pub contents: Vec<Content>,
+    #[serde(rename = "systemInstruction", skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<SystemInstruction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(rename = "generationConfig", skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<GenerationConfig>,
+}
+
+impl From<&GenerateContentRequest> for CountTokensRequest {
+    fn from(request: &GenerateContentRequest) -> Self {
+        Self {
+            contents: request.contents.clone(),
+            system_instruction: request.base_model_params.system_instruction.clone(),
+            tools: request.base_model_params.tools.clone(),
+            generation_config: request.base_model_params.generation_config.clone(),
+        }
+    }
+}
+
+// CountTokensResponse struct
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct CountTokensResponse {
+    #[serde(rename = "totalTokens", default)]
+    pub total_tokens: u32,
+    #[serde(
+        rename = "totalBillableCharacters",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub total_billable_characters: Option<u32>,
+}
+
 // GenerateContentCandidate struct
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct GenerateContentCandidate {
@@ -462,6 +700,42 @@ pub struct GenerateContentCandidate {
     pub grounding_metadata: Option<GroundingMetadata>,
 }
 
+impl GenerateContentCandidate {
+    fn merge(&mut self, chunk: GenerateContentCandidate) {
+        if let Some(chunk_content) = chunk.content {
+            let content = self.content.get_or_insert_with(Content::default);
+            if content.role.is_empty() {
+                content.role = chunk_content.role;
+            }
+
+            for part in chunk_content.parts {
+                match (content.parts.last_mut(), part) {
+                    (Some(Part::TextPart(last)), Part::TextPart(next)) => {
+                        last.text.push_str(&next.text)
+                    }
+                    (_, part) => content.parts.push(part),
+                }
+            }
+        }
+
+        if chunk.finish_reason.is_some() {
+            self.finish_reason = chunk.finish_reason;
+        }
+        if chunk.finish_message.is_some() {
+            self.finish_message = chunk.finish_message;
+        }
+        if chunk.safety_ratings.is_some() {
+            self.safety_ratings = chunk.safety_ratings;
+        }
+        if chunk.citation_metadata.is_some() {
+            self.citation_metadata = chunk.citation_metadata;
+        }
+        if chunk.grounding_metadata.is_some() {
+            self.grounding_metadata = chunk.grounding_metadata;
+        }
+    }
+}
+
 // CitationMetadata struct
 #[derive(Clone, Serialize, Deserialize, Debug, Default)]
 pub struct CitationMetadata {
@@ -606,13 +880,70 @@ pub struct GoogleAuth {
 }
 
 // RequestOptions struct
-#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Builder)]
+#[builder(setter(into, strip_option), default)]
 pub struct RequestOptions {
+    /// Timeout in milliseconds until the response headers arrive, which also covers reading the body unless it is streamed.
+    /// The body of a stream is bounded by `stream_idle_timeout` instead.
     pub timeout: Option<u64>,
+    /// Sent as the `x-goog-api-client` header.
     #[serde(rename = "apiClient", skip_serializing_if = "Option::is_none")]
     pub api_client: Option<String>,
     #[serde(rename = "customHeaders", skip_serializing_if = "Option::is_none")]
     pub custom_headers: Option<HashMap<String, String>>,
+    /// The longest time in milliseconds to wait for the next chunk of a stream.
+    #[serde(rename = "streamIdleTimeout", skip_serializing_if = "Option::is_none")]
+    pub stream_idle_timeout: Option<u64>,
+    /// How the call uses the response cache of the client, if it has one.
+    #[serde(rename = "cacheMode", skip_serializing_if = "Option::is_none")]
+    pub cache_mode: Option<CacheMode>,
+}
+
+impl RequestOptions {
+    pub fn builder() -> RequestOptionsBuilder {
+        RequestOptionsBuilder::default()
+    }
+
+    /// Applies the timeout and headers to a request whose response is read at once.
+    pub fn apply(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(timeout) = self.timeout() {
+            request = request.timeout(timeout);
+        }
+        self.apply_headers(request)
+    }
+
+    /// Applies the headers to a request, for streams whose timeout only covers the wait for the response headers.
+    pub fn apply_headers(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(api_client) = &self.api_client {
+            request = request.header("x-goog-api-client", api_client);
+        }
+        for (name, value) in self.custom_headers.iter().flatten() {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    pub fn timeout(&self) -> Option<std::time::Duration> {
+        self.timeout.map(std::time::Duration::from_millis)
+    }
+
+    pub fn stream_idle_timeout(&self) -> Option<std::time::Duration> {
+        self.stream_idle_timeout
+            .map(std::time::Duration::from_millis)
+    }
+}
+
+/// How a call uses the response cache of the client, see [crate::response_cache].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheMode {
+    /// Answer from the cache if possible, and cache the response otherwise.
+    #[default]
+    Use,
+    /// Always send the request, and replace the cached response.
+    Refresh,
+    /// Neither read nor write the cache.
+    Bypass,
 }
 
 // CachedContent struct
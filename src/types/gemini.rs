@@ -5,6 +5,9 @@ use strum_macros::IntoStaticStr;
 #[derive(
     Clone,
     Debug,
+    PartialEq,
+    Eq,
+    Hash,
     Serialize,
     Deserialize,
     strum_macros::Display,
@@ -57,4 +60,155 @@ pub enum GeminiModel {
     #[serde(rename = "gemini-1.0-pro-002")]
     #[strum(serialize = "gemini-1.0-pro-002")]
     Gemini10Pro002,
+
+    /// Any other model id, or the full resource name of a tuned model endpoint,
+    /// e.g. `projects/{project}/locations/{location}/endpoints/{endpoint}`.
+    /// Converts into the static str `custom`, the kebab-case variant name, use the [std::fmt::Display] implementation to get the id.
+    #[serde(untagged)]
+    #[strum(default)]
+    Custom(String),
+}
+
+impl GeminiModel {
+    /// Refers to a tuned model deployed to a Vertex AI endpoint.
+    pub fn endpoint(project_id: &str, location: &str, endpoint_id: &str) -> Self {
+        GeminiModel::Custom(format!(
+            "projects/{}/locations/{}/endpoints/{}",
+            project_id, location, endpoint_id
+        ))
+    }
+
+    /// Returns the full resource name if the model is a fully qualified resource such as a tuned model endpoint.
+    pub fn resource_name(&self) -> Option<&str> {
+        match self {
+            GeminiModel::Custom(model) if model.starts_with("projects/") => Some(model.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Returns the location segment of a fully qualified resource name.
+    pub fn resource_location(&self) -> Option<&str> {
+        let mut segments = self.resource_name()?.split('/');
+        segments.find(|segment| *segment == "locations")?;
+        segments.next()
+    }
+}
+
+/// A model available to the backend of the client, see [crate::gemini::Gemini::list_models].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// The model id without the resource prefix, e.g. `gemini-1.5-flash-002`.
+    pub id: String,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    pub input_token_limit: Option<u32>,
+    pub output_token_limit: Option<u32>,
+    /// The supported methods such as `generateContent`, only reported by the Developer API.
+    pub supported_methods: Vec<String>,
+}
+
+impl ModelInfo {
+    pub fn model(&self) -> GeminiModel {
+        self.id
+            .parse()
+            .unwrap_or_else(|_| GeminiModel::Custom(self.id.clone()))
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ListModelsResponse {
+    pub models: Vec<ModelInfo>,
+    pub next_page_token: Option<String>,
+}
+
+/// The models listed by the Developer API.
+#[derive(Deserialize)]
+pub(crate) struct DeveloperModelList {
+    #[serde(default)]
+    models: Vec<DeveloperModel>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeveloperModel {
+    name: String,
+    version: Option<String>,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+    description: Option<String>,
+    #[serde(rename = "inputTokenLimit")]
+    input_token_limit: Option<u32>,
+    #[serde(rename = "outputTokenLimit")]
+    output_token_limit: Option<u32>,
+    #[serde(rename = "supportedGenerationMethods", default)]
+    supported_generation_methods: Vec<String>,
+}
+
+impl From<DeveloperModelList> for ListModelsResponse {
+    fn from(list: DeveloperModelList) -> Self {
+        Self {
+            models: list
+                .models
+                .into_iter()
+                .map(|model| ModelInfo {
+                    id: model
+                        .name
+                        .strip_prefix("models/")
+                        .unwrap_or(&model.name)
+                        .to_string(),
+                    display_name: model.display_name,
+                    description: model.description,
+                    version: model.version,
+                    input_token_limit: model.input_token_limit,
+                    output_token_limit: model.output_token_limit,
+                    supported_methods: model.supported_generation_methods,
+                })
+                .collect(),
+            next_page_token: list.next_page_token.filter(|token| !token.is_empty()),
+        }
+    }
+}
+
+/// The Google publisher models listed by Vertex AI.
+#[derive(Deserialize)]
+pub(crate) struct PublisherModelList {
+    #[serde(rename = "publisherModels", default)]
+    publisher_models: Vec<PublisherModel>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PublisherModel {
+    name: String,
+    #[serde(rename = "versionId")]
+    version_id: Option<String>,
+}
+
+impl From<PublisherModelList> for ListModelsResponse {
+    fn from(list: PublisherModelList) -> Self {
+        Self {
+            models: list
+                .publisher_models
+                .into_iter()
+                .map(|model| ModelInfo {
+                    id: model
+                        .name
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    display_name: None,
+                    description: None,
+                    version: model.version_id,
+                    input_token_limit: None,
+                    output_token_limit: None,
+                    supported_methods: Vec::new(),
+                })
+                .collect(),
+            next_page_token: list.next_page_token.filter(|token| !token.is_empty()),
+        }
+    }
 }
@@ -0,0 +1,510 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use strum_macros::IntoStaticStr;
+
+use crate::error::MediaError;
+
+use super::media::sniff_mime_type;
+
+/// Imagen models, the generate models create images and the capability model edits them.
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::Display,
+    IntoStaticStr,
+    strum_macros::EnumString,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ImagenModel {
+    #[serde(rename = "imagen-3.0-generate-002")]
+    #[strum(serialize = "imagen-3.0-generate-002")]
+    Imagen3Generate002,
+    #[serde(rename = "imagen-3.0-generate-001")]
+    #[strum(serialize = "imagen-3.0-generate-001")]
+    Imagen3Generate001,
+    #[serde(rename = "imagen-3.0-fast-generate-001")]
+    #[strum(serialize = "imagen-3.0-fast-generate-001")]
+    Imagen3FastGenerate001,
+    /// Mask based editing, see [crate::imagen::Imagen::edit_image].
+    #[serde(rename = "imagen-3.0-capability-001")]
+    #[strum(serialize = "imagen-3.0-capability-001")]
+    Imagen3Capability001,
+
+    /// Any other model id.
+    /// Converts into the static str `custom`, the kebab-case variant name, use the [std::fmt::Display] implementation to get the id.
+    #[serde(untagged)]
+    #[strum(default)]
+    Custom(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AspectRatio {
+    #[serde(rename = "1:1")]
+    Square,
+    #[serde(rename = "3:4")]
+    Portrait3x4,
+    #[serde(rename = "4:3")]
+    Landscape4x3,
+    #[serde(rename = "9:16")]
+    Portrait9x16,
+    #[serde(rename = "16:9")]
+    Landscape16x9,
+}
+
+/// How strictly generated images are filtered for harmful content.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SafetyFilterLevel {
+    BlockLowAndAbove,
+    BlockMediumAndAbove,
+    BlockOnlyHigh,
+    BlockNone,
+}
+
+/// Whether images may show people.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PersonGeneration {
+    DontAllow,
+    AllowAdult,
+    AllowAll,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EditMode {
+    /// Adds the content of the prompt inside the mask.
+    #[serde(rename = "EDIT_MODE_INPAINT_INSERTION")]
+    InpaintInsertion,
+    /// Removes the content inside the mask.
+    #[serde(rename = "EDIT_MODE_INPAINT_REMOVAL")]
+    InpaintRemoval,
+    /// Extends the image into the masked area around it.
+    #[serde(rename = "EDIT_MODE_OUTPAINT")]
+    Outpaint,
+    /// Replaces the background of a product image.
+    #[serde(rename = "EDIT_MODE_BGSWAP")]
+    BackgroundSwap,
+}
+
+/// Where the mask of an edit comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaskMode {
+    /// The mask image of the request, white marks the area to edit.
+    #[serde(rename = "MASK_MODE_USER_PROVIDED")]
+    UserProvided,
+    #[serde(rename = "MASK_MODE_BACKGROUND")]
+    Background,
+    #[serde(rename = "MASK_MODE_FOREGROUND")]
+    Foreground,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UpscaleFactor {
+    #[serde(rename = "x2")]
+    X2,
+    #[serde(rename = "x4")]
+    X4,
+}
+
+/// The encoding of the returned images, PNG by default.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OutputOptions {
+    /// `image/png` or `image/jpeg`.
+    #[serde(rename = "mimeType", skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// The JPEG quality from 0 to 100.
+    #[serde(rename = "compressionQuality", skip_serializing_if = "Option::is_none")]
+    pub compression_quality: Option<u32>,
+}
+
+/// A decoded image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(mime_type: impl Into<String>, data: Vec<u8>) -> Self {
+        Self {
+            mime_type: mime_type.into(),
+            data,
+        }
+    }
+
+    /// An image with the MIME type detected from its content
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, MediaError> {
+        match sniff_mime_type(&data) {
+            Some(mime_type) if mime_type.starts_with("image/") => Ok(Self::new(mime_type, data)),
+            mime_type => Err(MediaError::UnexpectedMimeType {
+                expected: "image",
+                mime_type: mime_type.unwrap_or("unknown").to_string(),
+            }),
+        }
+    }
+
+    fn encode(&self) -> EncodedImage {
+        EncodedImage {
+            bytes_base64_encoded: Some(STANDARD.encode(&self.data)),
+            mime_type: Some(self.mime_type.clone()),
+        }
+    }
+}
+
+/// Request to generate images from a prompt, see [crate::imagen::Imagen::generate_images].
+#[derive(Clone, Debug, Builder)]
+#[builder(setter(into))]
+pub struct GenerateImagesRequest {
+    pub prompt: String,
+    /// What the images should not show.
+    #[builder(default, setter(strip_option))]
+    pub negative_prompt: Option<String>,
+    /// From 1 to 4, defaults to 4.
+    #[builder(default, setter(strip_option))]
+    pub number_of_images: Option<u32>,
+    #[builder(default, setter(strip_option))]
+    pub aspect_ratio: Option<AspectRatio>,
+    /// Makes the images reproducible, only used without a watermark.
+    #[builder(default, setter(strip_option))]
+    pub seed: Option<u32>,
+    #[builder(default, setter(strip_option))]
+    pub safety_filter_level: Option<SafetyFilterLevel>,
+    #[builder(default, setter(strip_option))]
+    pub person_generation: Option<PersonGeneration>,
+    /// Adds an invisible SynthID watermark, on by default.
+    #[builder(default, setter(strip_option))]
+    pub add_watermark: Option<bool>,
+    /// Rewrites the prompt for better images, the rewritten prompt is returned with the images.
+    #[builder(default, setter(strip_option))]
+    pub enhance_prompt: Option<bool>,
+    #[builder(default, setter(strip_option))]
+    pub output_options: Option<OutputOptions>,
+}
+
+impl GenerateImagesRequest {
+    pub fn builder() -> GenerateImagesRequestBuilder {
+        GenerateImagesRequestBuilder::default()
+    }
+}
+
+/// Request to edit an image, see [crate::imagen::Imagen::edit_image].
+#[derive(Clone, Debug, Builder)]
+#[builder(setter(into))]
+pub struct EditImageRequest {
+    /// What to insert, or describes the new background, empty for removals.
+    #[builder(default)]
+    pub prompt: String,
+    pub image: Image,
+    pub edit_mode: EditMode,
+    /// Required with [MaskMode::UserProvided], white marks the area to edit.
+    #[builder(default, setter(strip_option))]
+    pub mask: Option<Image>,
+    #[builder(default = "MaskMode::UserProvided")]
+    pub mask_mode: MaskMode,
+    /// Grows the mask by this fraction of the image width, e.g. `0.01`.
+    #[builder(default, setter(strip_option))]
+    pub mask_dilation: Option<f32>,
+    #[builder(default, setter(strip_option))]
+    pub negative_prompt: Option<String>,
+    #[builder(default, setter(strip_option))]
+    pub number_of_images: Option<u32>,
+    #[builder(default, setter(strip_option))]
+    pub seed: Option<u32>,
+    #[builder(default, setter(strip_option))]
+    pub safety_filter_level: Option<SafetyFilterLevel>,
+    #[builder(default, setter(strip_option))]
+    pub person_generation: Option<PersonGeneration>,
+    #[builder(default, setter(strip_option))]
+    pub add_watermark: Option<bool>,
+    /// More steps give better results slower, e.g. 75 for insertion.
+    #[builder(default, setter(strip_option))]
+    pub base_steps: Option<u32>,
+    #[builder(default, setter(strip_option))]
+    pub output_options: Option<OutputOptions>,
+}
+
+impl EditImageRequest {
+    pub fn builder() -> EditImageRequestBuilder {
+        EditImageRequestBuilder::default()
+    }
+}
+
+/// Request to upscale an image, see [crate::imagen::Imagen::upscale_image].
+#[derive(Clone, Debug, Builder)]
+#[builder(setter(into))]
+pub struct UpscaleImageRequest {
+    pub image: Image,
+    pub factor: UpscaleFactor,
+    #[builder(default, setter(strip_option))]
+    pub add_watermark: Option<bool>,
+    #[builder(default, setter(strip_option))]
+    pub output_options: Option<OutputOptions>,
+}
+
+impl UpscaleImageRequest {
+    pub fn builder() -> UpscaleImageRequestBuilder {
+        UpscaleImageRequestBuilder::default()
+    }
+}
+
+/// The images of a request, in the order they were generated.
+#[derive(Clone, Debug, Default)]
+pub struct GenerateImagesResponse {
+    pub images: Vec<GeneratedImage>,
+}
+
+impl GenerateImagesResponse {
+    /// The images which were not filtered
+    pub fn images(&self) -> impl Iterator<Item = &Image> {
+        self.images.iter().filter_map(|image| image.image.as_ref())
+    }
+
+    /// Why images were filtered, one reason per filtered image
+    pub fn filtered_reasons(&self) -> impl Iterator<Item = &str> {
+        self.images
+            .iter()
+            .filter_map(|image| image.rai_filtered_reason.as_deref())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GeneratedImage {
+    /// `None` if the image was removed by the responsible AI filters.
+    pub image: Option<Image>,
+    /// Why the image was filtered.
+    pub rai_filtered_reason: Option<String>,
+    /// The prompt the image was generated from, if it was enhanced.
+    pub enhanced_prompt: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EncodedImage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes_base64_encoded: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mime_type: Option<String>,
+}
+
+/// The body of a `:predict` call.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct PredictRequest {
+    instances: Vec<PredictInstance>,
+    parameters: PredictParameters,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PredictInstance {
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<EncodedImage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    reference_images: Vec<ReferenceImage>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReferenceImage {
+    reference_type: &'static str,
+    reference_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reference_image: Option<EncodedImage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mask_image_config: Option<MaskImageConfig>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MaskImageConfig {
+    mask_mode: MaskMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dilation: Option<f32>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PredictParameters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    negative_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aspect_ratio: Option<AspectRatio>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u32>,
+    #[serde(rename = "safetySetting", skip_serializing_if = "Option::is_none")]
+    safety_filter_level: Option<SafetyFilterLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    person_generation: Option<PersonGeneration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    add_watermark: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enhance_prompt: Option<bool>,
+    /// Always set, filtered images are reported with the reason instead of being dropped silently.
+    include_rai_reason: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_options: Option<OutputOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edit_mode: Option<EditMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edit_config: Option<EditConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upscale_config: Option<UpscaleConfig>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EditConfig {
+    base_steps: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpscaleConfig {
+    upscale_factor: UpscaleFactor,
+}
+
+impl From<&GenerateImagesRequest> for PredictRequest {
+    fn from(request: &GenerateImagesRequest) -> Self {
+        PredictRequest {
+            instances: vec![PredictInstance {
+                prompt: request.prompt.clone(),
+                ..Default::default()
+            }],
+            parameters: PredictParameters {
+                sample_count: request.number_of_images,
+                negative_prompt: request.negative_prompt.clone(),
+                aspect_ratio: request.aspect_ratio,
+                seed: request.seed,
+                safety_filter_level: request.safety_filter_level,
+                person_generation: request.person_generation,
+                add_watermark: request.add_watermark,
+                enhance_prompt: request.enhance_prompt,
+                include_rai_reason: true,
+                output_options: request.output_options.clone(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<&EditImageRequest> for PredictRequest {
+    fn from(request: &EditImageRequest) -> Self {
+        let raw = ReferenceImage {
+            reference_type: "REFERENCE_TYPE_RAW",
+            reference_id: 1,
+            reference_image: Some(request.image.encode()),
+            mask_image_config: None,
+        };
+        let mask = ReferenceImage {
+            reference_type: "REFERENCE_TYPE_MASK",
+            reference_id: 2,
+            reference_image: request.mask.as_ref().map(Image::encode),
+            mask_image_config: Some(MaskImageConfig {
+                mask_mode: request.mask_mode,
+                dilation: request.mask_dilation,
+            }),
+        };
+
+        PredictRequest {
+            instances: vec![PredictInstance {
+                prompt: request.prompt.clone(),
+                reference_images: vec![raw, mask],
+                ..Default::default()
+            }],
+            parameters: PredictParameters {
+                sample_count: request.number_of_images,
+                negative_prompt: request.negative_prompt.clone(),
+                seed: request.seed,
+                safety_filter_level: request.safety_filter_level,
+                person_generation: request.person_generation,
+                add_watermark: request.add_watermark,
+                include_rai_reason: true,
+                output_options: request.output_options.clone(),
+                edit_mode: Some(request.edit_mode),
+                edit_config: request
+                    .base_steps
+                    .map(|base_steps| EditConfig { base_steps }),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<&UpscaleImageRequest> for PredictRequest {
+    fn from(request: &UpscaleImageRequest) -> Self {
+        PredictRequest {
+            instances: vec![PredictInstance {
+                image: Some(request.image.encode()),
+                ..Default::default()
+            }],
+            parameters: PredictParameters {
+                sample_count: Some(1),
+                add_watermark: request.add_watermark,
+                include_rai_reason: true,
+                output_options: request.output_options.clone(),
+                mode: Some("upscale"),
+                upscale_config: Some(UpscaleConfig {
+                    upscale_factor: request.factor,
+                }),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// The body of a `:predict` response.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct PredictResponse {
+    #[serde(default)]
+    predictions: Vec<Prediction>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Prediction {
+    #[serde(flatten)]
+    image: EncodedImage,
+    rai_filtered_reason: Option<String>,
+    prompt: Option<String>,
+}
+
+impl TryFrom<PredictResponse> for GenerateImagesResponse {
+    type Error = base64::DecodeError;
+
+    fn try_from(response: PredictResponse) -> Result<Self, Self::Error> {
+        let images = response
+            .predictions
+            .into_iter()
+            .map(|prediction| {
+                let image = match prediction.image.bytes_base64_encoded {
+                    Some(data) => Some(Image {
+                        mime_type: prediction
+                            .image
+                            .mime_type
+                            .unwrap_or("image/png".to_string()),
+                        data: STANDARD.decode(data)?,
+                    }),
+                    None => None,
+                };
+
+                Ok(GeneratedImage {
+                    image,
+                    rai_filtered_reason: prediction.rai_filtered_reason,
+                    enhanced_prompt: prediction.prompt,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(GenerateImagesResponse { images })
+    }
+}
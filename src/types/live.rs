@@ -0,0 +1,264 @@
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::content::{
+    Content, GenerativeContentBlob, Part, ResponseModality, SpeechConfig, Tool, ToolConfig,
+};
+
+/// The first message of a Live session, configuring the model for the whole session.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Builder)]
+#[builder(setter(into, strip_option), default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveSetup {
+    /// The resource name of the model, set when the session connects.
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generation_config: Option<LiveGenerationConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<ToolConfig>,
+    /// Transcribes the audio sent to the model, see [LiveServerContent::input_transcription].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_audio_transcription: Option<AudioTranscriptionConfig>,
+    /// Transcribes the audio spoken by the model, see [LiveServerContent::output_transcription].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_audio_transcription: Option<AudioTranscriptionConfig>,
+}
+
+impl LiveSetup {
+    pub fn builder() -> LiveSetupBuilder {
+        LiveSetupBuilder::default()
+    }
+}
+
+/// The generation options of a Live session, a subset of [crate::types::content::GenerationConfig]
+/// without structured output and stop sequences.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Builder)]
+#[builder(setter(into, strip_option), default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveGenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    /// Whether the model answers with text or audio, sessions only support one of them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_modalities: Option<Vec<ResponseModality>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speech_config: Option<SpeechConfig>,
+}
+
+impl LiveGenerationConfig {
+    pub fn builder() -> LiveGenerationConfigBuilder {
+        LiveGenerationConfigBuilder::default()
+    }
+}
+
+/// Enables audio transcription, there are no options yet.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct AudioTranscriptionConfig {}
+
+/// A message sent by the client of a Live session.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum LiveClientMessage {
+    Setup(Box<LiveSetup>),
+    ClientContent(LiveClientContent),
+    RealtimeInput(LiveRealtimeInput),
+    ToolResponse(LiveToolResponse),
+}
+
+/// Turns appended to the conversation, the model responds once `turn_complete` is set.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveClientContent {
+    pub turns: Vec<Content>,
+    pub turn_complete: bool,
+}
+
+/// Audio or video streamed to the model as it is captured, e.g. `audio/pcm;rate=16000` or `image/jpeg` frames.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveRealtimeInput {
+    pub media_chunks: Vec<GenerativeContentBlob>,
+}
+
+/// The results of the function calls of a [LiveToolCall].
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveToolResponse {
+    pub function_responses: Vec<LiveFunctionResponse>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct LiveFunctionResponse {
+    /// The id of the [LiveFunctionCall] this responds to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    pub response: Value,
+}
+
+/// A message sent by the server of a Live session, usually only one of the fields is set.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveServerMessage {
+    /// Sent once in reply to the [LiveSetup].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setup_complete: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_content: Option<LiveServerContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call: Option<LiveToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_cancellation: Option<LiveToolCallCancellation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<LiveUsageMetadata>,
+    /// The server is about to close the connection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub go_away: Option<LiveGoAway>,
+}
+
+impl LiveServerMessage {
+    /// The text of the model turn, if any
+    pub fn text(&self) -> Option<String> {
+        self.server_content.as_ref()?.text()
+    }
+
+    /// The decoded audio of the model turn, if any
+    pub fn audio(&self) -> Option<Vec<u8>> {
+        self.server_content.as_ref()?.audio()
+    }
+}
+
+/// Content generated by the model in reply to the client.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveServerContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_turn: Option<Content>,
+    /// The model is done and waits for the client.
+    #[serde(default)]
+    pub turn_complete: bool,
+    /// The client interrupted the model, e.g. by speaking, audio still queued for playback should be dropped.
+    #[serde(default)]
+    pub interrupted: bool,
+    /// The model is done generating, it may still be streaming the turn.
+    #[serde(default)]
+    pub generation_complete: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_transcription: Option<LiveTranscription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_transcription: Option<LiveTranscription>,
+}
+
+impl LiveServerContent {
+    /// The concatenated text parts of the model turn
+    pub fn text(&self) -> Option<String> {
+        let text = self
+            .model_turn
+            .as_ref()?
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::TextPart(part) => Some(part.text.as_str()),
+                _ => None,
+            })
+            .collect::<String>();
+        Some(text).filter(|text| !text.is_empty())
+    }
+
+    /// The concatenated audio parts of the model turn, e.g. 24kHz 16-bit PCM
+    pub fn audio(&self) -> Option<Vec<u8>> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let audio = self
+            .model_turn
+            .as_ref()?
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::InlineDataPart(part) if part.inline_data.mime_type.starts_with("audio/") => {
+                    STANDARD.decode(&part.inline_data.data).ok()
+                }
+                _ => None,
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        Some(audio).filter(|audio| !audio.is_empty())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct LiveTranscription {
+    #[serde(default)]
+    pub text: String,
+}
+
+/// Functions the model wants the client to call, answered with a [LiveToolResponse].
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveToolCall {
+    pub function_calls: Vec<LiveFunctionCall>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct LiveFunctionCall {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+impl LiveFunctionCall {
+    /// Answers the call with the result of the function
+    pub fn response(&self, response: Value) -> LiveFunctionResponse {
+        LiveFunctionResponse {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            response,
+        }
+    }
+}
+
+/// Tool calls which should not be executed anymore, e.g. because the client interrupted the model.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct LiveToolCallCancellation {
+    pub ids: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveUsageMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_token_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_content_token_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_token_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_token_count: Option<u32>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveGoAway {
+    /// The time left before the connection is closed, e.g. `10s`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_left: Option<String>,
+}
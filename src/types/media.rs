@@ -0,0 +1,337 @@
+//! Builds [Part]s from images, audio, video and documents, detecting the MIME type and enforcing the inline data limit.
+//!
+//! Media larger than [MAX_INLINE_DATA_BYTES] has to be uploaded, e.g. to Cloud Storage, and passed by its uri with
+//! [Part::file_data].
+//!
+//! Images and audio generated by the model, see [crate::types::content::ResponseModality], are decoded with
+//! [GenerateContentResponse::images] and [GenerateContentResponse::audio].
+
+use std::path::Path;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::error::MediaError;
+
+use super::{
+    capabilities::{Capabilities, Modality},
+    content::{
+        FileData, FileDataPart, GenerateContentCandidate, GenerateContentResponse,
+        GenerativeContentBlob, InlineDataPart, Part, VideoMetadata,
+    },
+    gemini::GeminiModel,
+    imagen::Image,
+};
+
+/// The largest inline data in bytes after base64 encoding, requests are limited to 20 MB in total.
+pub const MAX_INLINE_DATA_BYTES: usize = 20 * 1024 * 1024;
+
+/// The document types Gemini models read, other text formats should be sent as text parts.
+pub const DOCUMENT_MIME_TYPES: &[&str] = &["application/pdf", "text/plain"];
+
+/// Detects the MIME type of images, audio, video and PDFs from their leading bytes
+pub fn sniff_mime_type(data: &[u8]) -> Option<&'static str> {
+    let at = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+
+    match data {
+        _ if at(0, b"\x89PNG\r\n\x1a\n") => Some("image/png"),
+        _ if at(0, b"\xff\xd8\xff") => Some("image/jpeg"),
+        _ if at(0, b"GIF87a") || at(0, b"GIF89a") => Some("image/gif"),
+        _ if at(0, b"%PDF-") => Some("application/pdf"),
+        _ if at(0, b"RIFF") && at(8, b"WEBP") => Some("image/webp"),
+        _ if at(0, b"RIFF") && at(8, b"WAVE") => Some("audio/wav"),
+        _ if at(0, b"RIFF") && at(8, b"AVI ") => Some("video/avi"),
+        _ if at(0, b"FORM") && (at(8, b"AIFF") || at(8, b"AIFC")) => Some("audio/aiff"),
+        _ if at(0, b"fLaC") => Some("audio/flac"),
+        _ if at(0, b"OggS") => Some("audio/ogg"),
+        _ if at(0, b"ID3") => Some("audio/mpeg"),
+        _ if at(0, b"\x1a\x45\xdf\xa3") => Some("video/webm"),
+        _ if at(0, b"FLV") => Some("video/x-flv"),
+        _ if at(0, b"\x30\x26\xb2\x75\x8e\x66\xcf\x11") => Some("video/wmv"),
+        _ if at(0, b"\x00\x00\x01\xba") || at(0, b"\x00\x00\x01\xb3") => Some("video/mpeg"),
+        _ if at(4, b"ftyp") => match data.get(8..12) {
+            Some(b"heic" | b"heix") => Some("image/heic"),
+            Some(b"mif1" | b"msf1") => Some("image/heif"),
+            Some(b"M4A " | b"M4B ") => Some("audio/mp4"),
+            Some(b"qt  ") => Some("video/quicktime"),
+            Some(brand) if brand.starts_with(b"3g") => Some("video/3gpp"),
+            Some(
+                b"isom" | b"iso2" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42" | b"avc1"
+                | b"dash" | b"M4V " | b"M4VP" | b"f4v " | b"MSNV" | b"NDAS" | b"mmp4",
+            ) => Some("video/mp4"),
+            // unknown brands, e.g. avif images, are not guessed
+            _ => None,
+        },
+        // frame sync of mpeg audio, the layer bits are zero for aac
+        [0xff, second, ..] if second & 0xe0 == 0xe0 => match second & 0x06 {
+            0 => Some("audio/aac"),
+            _ => Some("audio/mpeg"),
+        },
+        _ => None,
+    }
+}
+
+/// Guesses the MIME type from the extension of a path or uri
+pub fn mime_type_from_extension(path: &str) -> Option<&'static str> {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let (_, extension) = path.rsplit_once('.')?;
+    let mime_type = match extension.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "heif" => "image/heif",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "aac" => "audio/aac",
+        "flac" => "audio/flac",
+        "ogg" | "opus" => "audio/ogg",
+        "m4a" => "audio/mp4",
+        "aif" | "aiff" => "audio/aiff",
+        "mp4" | "m4v" => "video/mp4",
+        "mov" => "video/quicktime",
+        "mpeg" | "mpg" => "video/mpeg",
+        "avi" => "video/avi",
+        "wmv" => "video/wmv",
+        "flv" => "video/x-flv",
+        "webm" => "video/webm",
+        "3gp" => "video/3gpp",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+/// Decoded audio generated by the model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Audio {
+    /// E.g. `audio/L16;codec=pcm;rate=24000` for raw 16-bit PCM.
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Audio {
+    /// Whether the audio is raw PCM, whose chunks can be joined byte for byte unlike container formats such as wav
+    pub fn is_pcm(&self) -> bool {
+        is_pcm(&self.mime_type)
+    }
+
+    /// The sample rate of raw PCM audio, taken from the `rate` parameter of the MIME type
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.mime_type
+            .split(';')
+            .filter_map(|param| param.trim().split_once('='))
+            .find(|(name, _)| name.eq_ignore_ascii_case("rate"))
+            .and_then(|(_, rate)| rate.parse().ok())
+    }
+}
+
+impl GenerateContentResponse {
+    /// The decoded images of the first candidate, see [GenerateContentCandidate::images] for the others
+    pub fn images(&self) -> Vec<Image> {
+        self.candidates
+            .iter()
+            .flatten()
+            .next()
+            .map(GenerateContentCandidate::images)
+            .unwrap_or_default()
+    }
+
+    /// The decoded audio of the first candidate, see [GenerateContentCandidate::audio] for the others
+    pub fn audio(&self) -> Option<Audio> {
+        self.candidates.as_ref()?.first()?.audio()
+    }
+}
+
+impl GenerateContentCandidate {
+    /// The decoded inline images, in order. Parts which are not valid base64 are skipped.
+    pub fn images(&self) -> Vec<Image> {
+        self.inline_data("image/")
+            .map(|(mime_type, data)| Image::new(mime_type, data))
+            .collect()
+    }
+
+    /// The decoded inline audio. Raw PCM streamed in several parts is concatenated,
+    /// e.g. after folding the chunks of `stream_generate_content` with [GenerateContentResponse::merge].
+    /// Other formats carry a header in each part and can't be joined, only the first part is returned.
+    pub fn audio(&self) -> Option<Audio> {
+        let mut parts = self.inline_data("audio/");
+        let (mime_type, mut data) = parts.next()?;
+        if is_pcm(mime_type) {
+            for (_, chunk) in parts.take_while(|(chunk_type, _)| *chunk_type == mime_type) {
+                data.extend(chunk);
+            }
+        }
+        Some(Audio {
+            mime_type: mime_type.to_string(),
+            data,
+        })
+    }
+
+    fn inline_data<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = (&'a str, Vec<u8>)> + 'a {
+        self.content
+            .iter()
+            .flat_map(|content| content.parts.iter())
+            .filter_map(move |part| match part {
+                Part::InlineDataPart(part) if part.inline_data.mime_type.starts_with(kind) => {
+                    match STANDARD.decode(&part.inline_data.data) {
+                        Ok(data) => Some((part.inline_data.mime_type.as_str(), data)),
+                        Err(e) => {
+                            tracing::warn!(error=?e, mime_type=%part.inline_data.mime_type, "skipping invalid inline data");
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            })
+    }
+}
+
+/// Raw PCM, e.g. `audio/pcm;rate=16000` or `audio/L16;codec=pcm;rate=24000`
+fn is_pcm(mime_type: &str) -> bool {
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    essence.eq_ignore_ascii_case("audio/pcm") || essence.eq_ignore_ascii_case("audio/l16")
+}
+
+/// Fails if data of the length exceeds [MAX_INLINE_DATA_BYTES] once base64 encoded
+fn check_inline_size(len: usize) -> Result<(), MediaError> {
+    let size = len.div_ceil(3) * 4;
+    match size > MAX_INLINE_DATA_BYTES {
+        true => Err(MediaError::TooLarge {
+            size,
+            limit: MAX_INLINE_DATA_BYTES,
+        }),
+        false => Ok(()),
+    }
+}
+
+impl Part {
+    /// Inlines the data base64 encoded, failing if it exceeds [MAX_INLINE_DATA_BYTES]
+    pub fn inline_data(mime_type: impl Into<String>, data: &[u8]) -> Result<Self, MediaError> {
+        check_inline_size(data.len())?;
+
+        Ok(Part::InlineDataPart(InlineDataPart {
+            inline_data: GenerativeContentBlob {
+                mime_type: mime_type.into(),
+                data: STANDARD.encode(data),
+            },
+        }))
+    }
+
+    /// Inlines the data with the MIME type detected from its content, see [sniff_mime_type]
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MediaError> {
+        let mime_type = sniff_mime_type(data).ok_or(MediaError::UnknownMimeType)?;
+        Self::inline_data(mime_type, data)
+    }
+
+    /// Inlines a file with the MIME type detected from its content or else its extension.
+    /// Files over [MAX_INLINE_DATA_BYTES] are rejected before they are read.
+    pub async fn from_path(path: impl AsRef<Path>) -> Result<Self, MediaError> {
+        let path = path.as_ref();
+        let len = tokio::fs::metadata(path).await?.len();
+        check_inline_size(usize::try_from(len).unwrap_or(usize::MAX))?;
+
+        let data = tokio::fs::read(path).await?;
+        let mime_type = sniff_mime_type(&data)
+            .or_else(|| mime_type_from_extension(&path.to_string_lossy()))
+            .ok_or(MediaError::UnknownMimeType)?;
+        Self::inline_data(mime_type, &data)
+    }
+
+    /// Inlines audio such as wav, mp3 or flac. Raw PCM can't be detected, use [Part::inline_data] with e.g. `audio/pcm;rate=16000`
+    pub fn audio(data: &[u8]) -> Result<Self, MediaError> {
+        Self::expect_kind(data, "audio")
+    }
+
+    /// Inlines a short video, longer videos should be uploaded and passed with [Part::video_file]
+    pub fn video(data: &[u8]) -> Result<Self, MediaError> {
+        Self::expect_kind(data, "video")
+    }
+
+    pub fn pdf(data: &[u8]) -> Result<Self, MediaError> {
+        match sniff_mime_type(data) {
+            Some("application/pdf") => Self::inline_data("application/pdf", data),
+            mime_type => Err(MediaError::UnexpectedMimeType {
+                expected: "application/pdf",
+                mime_type: mime_type.unwrap_or("unknown").to_string(),
+            }),
+        }
+    }
+
+    /// Inlines a document after checking the model reads documents of the type, see [DOCUMENT_MIME_TYPES]
+    pub fn document(model: &GeminiModel, mime_type: &str, data: &[u8]) -> Result<Self, MediaError> {
+        if !DOCUMENT_MIME_TYPES.contains(&mime_type) {
+            return Err(MediaError::UnexpectedMimeType {
+                expected: "document",
+                mime_type: mime_type.to_string(),
+            });
+        }
+
+        let part = Self::inline_data(mime_type, data)?;
+        part.check_supported_by(model)?;
+        Ok(part)
+    }
+
+    /// References a file, e.g. in Cloud Storage
+    pub fn file_data(file_uri: impl Into<String>, mime_type: impl Into<String>) -> Self {
+        Part::FileDataPart(FileDataPart {
+            file_data: FileData {
+                mime_type: mime_type.into(),
+                file_uri: file_uri.into(),
+            },
+            video_metadata: None,
+        })
+    }
+
+    /// References a video, e.g. `gs://bucket/video.mp4`, with the MIME type guessed from its extension
+    pub fn video_file(file_uri: impl Into<String>, video_metadata: Option<VideoMetadata>) -> Self {
+        let file_uri = file_uri.into();
+        let mime_type = mime_type_from_extension(&file_uri)
+            .filter(|mime_type| mime_type.starts_with("video/"))
+            .unwrap_or("video/mp4");
+        Self::file_data(file_uri, mime_type).with_video_metadata(video_metadata)
+    }
+
+    /// Clips the video of a file part, other parts are returned as they are
+    pub fn with_video_metadata(mut self, video_metadata: Option<VideoMetadata>) -> Self {
+        if let Part::FileDataPart(part) = &mut self {
+            part.video_metadata = video_metadata;
+        }
+        self
+    }
+
+    /// The MIME type of inline data and file parts
+    pub fn mime_type(&self) -> Option<&str> {
+        match self {
+            Part::InlineDataPart(part) => Some(&part.inline_data.mime_type),
+            Part::FileDataPart(part) => Some(&part.file_data.mime_type),
+            _ => None,
+        }
+    }
+
+    /// Checks the model accepts the media of the part, models with unknown capabilities accept everything
+    pub fn check_supported_by(&self, model: &GeminiModel) -> Result<(), MediaError> {
+        let (Some(mime_type), Some(capabilities)) = (self.mime_type(), model.capabilities()) else {
+            return Ok(());
+        };
+
+        match Modality::from_mime_type(mime_type) {
+            Some(modality) if !capabilities.supports(modality) => Err(MediaError::Unsupported {
+                model: model.to_string(),
+                mime_type: mime_type.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn expect_kind(data: &[u8], kind: &'static str) -> Result<Self, MediaError> {
+        match sniff_mime_type(data) {
+            Some(mime_type) if mime_type.starts_with(kind) => Self::inline_data(mime_type, data),
+            mime_type => Err(MediaError::UnexpectedMimeType {
+                expected: kind,
+                mime_type: mime_type.unwrap_or("unknown").to_string(),
+            }),
+        }
+    }
+}
@@ -1,5 +1,14 @@
+pub mod batch;
+pub mod capabilities;
+pub mod chat;
 pub mod claude;
 pub mod content;
 pub mod gemini;
+pub mod imagen;
+#[cfg(feature = "live")]
+pub mod live;
+pub mod media;
+#[cfg(feature = "proxy")]
+pub mod openai;
 pub mod schema;
 pub mod tools;
@@ -0,0 +1,395 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    ops::AddAssign,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::types::{chat::ModelId, claude::ClaudeUsage, content::UsageMetadata};
+
+/// Tags attached to every call made through a handle, e.g. `tenant` or `feature`.
+pub type Tags = BTreeMap<String, String>;
+
+/// Token counts of one or more calls.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// All input tokens, including cached tokens.
+    pub prompt_tokens: u64,
+    /// The generated tokens.
+    pub candidate_tokens: u64,
+    /// The input tokens which were served from a cache.
+    pub cached_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.candidate_tokens += other.candidate_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+impl From<&UsageMetadata> for TokenUsage {
+    fn from(usage: &UsageMetadata) -> Self {
+        let prompt_tokens = usage.prompt_token_count.unwrap_or_default() as u64;
+        let candidate_tokens = usage.candidates_token_count.unwrap_or_default() as u64;
+        Self {
+            prompt_tokens,
+            candidate_tokens,
+            cached_tokens: usage.cached_content_token_count.unwrap_or_default() as u64,
+            total_tokens: usage
+                .total_token_count
+                .map_or(prompt_tokens + candidate_tokens, |total| total as u64),
+        }
+    }
+}
+
+#[cfg(feature = "live")]
+impl From<&crate::types::live::LiveUsageMetadata> for TokenUsage {
+    fn from(usage: &crate::types::live::LiveUsageMetadata) -> Self {
+        let prompt_tokens = usage.prompt_token_count.unwrap_or_default() as u64;
+        let candidate_tokens = usage.response_token_count.unwrap_or_default() as u64;
+        Self {
+            prompt_tokens,
+            candidate_tokens,
+            cached_tokens: usage.cached_content_token_count.unwrap_or_default() as u64,
+            total_tokens: usage
+                .total_token_count
+                .map_or(prompt_tokens + candidate_tokens, |total| total as u64),
+        }
+    }
+}
+
+impl From<&ClaudeUsage> for TokenUsage {
+    fn from(usage: &ClaudeUsage) -> Self {
+        // anthropic reports cache reads and writes separately from the uncached input tokens
+        let cache_read = usage.cache_read_input_tokens.unwrap_or_default() as u64;
+        let cache_creation = usage.cache_creation_input_tokens.unwrap_or_default() as u64;
+        let prompt_tokens = usage.input_tokens as u64 + cache_read + cache_creation;
+        let candidate_tokens = usage.output_tokens as u64;
+        Self {
+            prompt_tokens,
+            candidate_tokens,
+            cached_tokens: cache_read,
+            total_tokens: prompt_tokens + candidate_tokens,
+        }
+    }
+}
+
+/// The price of a model in USD per million tokens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// The price of cached input tokens, defaults to the input price.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+}
+
+impl ModelPrice {
+    pub fn per_million(input: f64, output: f64) -> Self {
+        Self {
+            input,
+            output,
+            cached_input: None,
+        }
+    }
+
+    pub fn with_cached_input(mut self, cached_input: f64) -> Self {
+        self.cached_input = Some(cached_input);
+        self
+    }
+
+    /// The cost of the usage in USD
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let uncached = usage.prompt_tokens.saturating_sub(usage.cached_tokens) as f64;
+        let cached = usage.cached_tokens as f64;
+        let output = usage.candidate_tokens as f64;
+
+        (uncached * self.input
+            + cached * self.cached_input.unwrap_or(self.input)
+            + output * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// A single recorded call.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub model: ModelId,
+    pub tags: Tags,
+    pub usage: TokenUsage,
+    /// The cost in USD, if the model has a price.
+    pub cost: Option<f64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Aggregated usage of a model, tag or the whole recorder.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub calls: u64,
+    pub usage: TokenUsage,
+    /// The cost in USD of the calls to models with a price.
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.calls += 1;
+        self.usage += record.usage;
+        self.cost += record.cost.unwrap_or_default();
+    }
+}
+
+/// A limit on the usage of the whole recorder or of a single tag.
+/// Calls are rejected once the limit is reached.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    /// The tag the budget applies to, or every call if not set.
+    pub tag: Option<(String, String)>,
+    pub max_tokens: Option<u64>,
+    /// The maximum cost in USD.
+    pub max_cost: Option<f64>,
+}
+
+impl Budget {
+    /// A budget for every call.
+    pub fn total() -> Self {
+        Self::default()
+    }
+
+    /// A budget for calls with the given tag.
+    pub fn for_tag(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            tag: Some((key.into(), value.into())),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    fn applies_to(&self, tags: &Tags) -> bool {
+        match &self.tag {
+            Some((key, value)) => tags.get(key) == Some(value),
+            None => true,
+        }
+    }
+
+    /// Describes why the totals exceed the budget, if they do.
+    fn exceeded(&self, totals: &UsageTotals) -> Option<String> {
+        let scope = match &self.tag {
+            Some((key, value)) => format!("{}={}", key, value),
+            None => "all calls".to_string(),
+        };
+
+        if let Some(max_tokens) = self
+            .max_tokens
+            .filter(|max| totals.usage.total_tokens >= *max)
+        {
+            return Some(format!(
+                "token budget for {} exceeded: {} of {} tokens used",
+                scope, totals.usage.total_tokens, max_tokens
+            ));
+        }
+
+        if let Some(max_cost) = self.max_cost.filter(|max| totals.cost >= *max) {
+            return Some(format!(
+                "cost budget for {} exceeded: ${:.4} of ${:.4} used",
+                scope, totals.cost, max_cost
+            ));
+        }
+
+        None
+    }
+}
+
+type Callback = Arc<dyn Fn(&UsageRecord) + Send + Sync>;
+
+#[derive(Default)]
+struct UsageState {
+    prices: HashMap<ModelId, ModelPrice>,
+    budgets: Vec<Budget>,
+    callbacks: Vec<Callback>,
+    ledger_capacity: usize,
+    ledger: VecDeque<UsageRecord>,
+    total: UsageTotals,
+    by_model: HashMap<ModelId, UsageTotals>,
+    by_tag: HashMap<(String, String), UsageTotals>,
+}
+
+/// Records the token usage of every call made through a [crate::client::Client], streaming included.
+///
+/// Clones share the same totals, so a recorder can be kept around to read them after it was passed to the client.
+#[derive(Clone, Default)]
+pub struct UsageRecorder {
+    state: Arc<Mutex<UsageState>>,
+}
+
+impl UsageRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the price used to calculate the cost of calls to the model.
+    pub fn with_price(self, model: impl Into<ModelId>, price: ModelPrice) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .prices
+            .insert(model.into(), price);
+        self
+    }
+
+    pub fn with_budget(self, budget: Budget) -> Self {
+        self.state.lock().unwrap().budgets.push(budget);
+        self
+    }
+
+    /// Calls the callback with every record, e.g. to export it.
+    pub fn on_record(self, callback: impl Fn(&UsageRecord) + Send + Sync + 'static) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .callbacks
+            .push(Arc::new(callback));
+        self
+    }
+
+    /// Keeps the last `capacity` records in memory, see [UsageRecorder::records].
+    pub fn with_ledger(self, capacity: usize) -> Self {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.ledger_capacity = capacity;
+            while state.ledger.len() > capacity {
+                state.ledger.pop_front();
+            }
+        }
+        self
+    }
+
+    /// Records the usage of a call.
+    pub fn record(&self, model: ModelId, tags: &Tags, usage: TokenUsage) {
+        let (record, callbacks) = {
+            let mut state = self.state.lock().unwrap();
+
+            let record = UsageRecord {
+                cost: state.prices.get(&model).map(|price| price.cost(&usage)),
+                model,
+                tags: tags.clone(),
+                usage,
+                timestamp: Utc::now(),
+            };
+
+            state.total.add(&record);
+            state
+                .by_model
+                .entry(record.model.clone())
+                .or_default()
+                .add(&record);
+            for (key, value) in tags {
+                state
+                    .by_tag
+                    .entry((key.clone(), value.clone()))
+                    .or_default()
+                    .add(&record);
+            }
+
+            if state.ledger_capacity > 0 {
+                while state.ledger.len() >= state.ledger_capacity {
+                    state.ledger.pop_front();
+                }
+                state.ledger.push_back(record.clone());
+            }
+
+            (record, state.callbacks.clone())
+        };
+
+        tracing::debug!(model=%record.model, usage=?record.usage, cost=?record.cost, "recorded usage");
+
+        for callback in callbacks {
+            callback(&record);
+        }
+    }
+
+    /// Returns a description of the first exceeded budget which applies to a call with the given tags.
+    pub fn check_budget(&self, tags: &Tags) -> Result<(), String> {
+        let state = self.state.lock().unwrap();
+
+        for budget in state
+            .budgets
+            .iter()
+            .filter(|budget| budget.applies_to(tags))
+        {
+            let totals = match &budget.tag {
+                Some(tag) => state.by_tag.get(tag).copied().unwrap_or_default(),
+                None => state.total,
+            };
+            if let Some(reason) = budget.exceeded(&totals) {
+                return Err(reason);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The totals of every recorded call.
+    pub fn total(&self) -> UsageTotals {
+        self.state.lock().unwrap().total
+    }
+
+    pub fn by_model(&self) -> HashMap<ModelId, UsageTotals> {
+        self.state.lock().unwrap().by_model.clone()
+    }
+
+    /// The totals for each value of the tag.
+    pub fn by_tag(&self, key: &str) -> HashMap<String, UsageTotals> {
+        self.state
+            .lock()
+            .unwrap()
+            .by_tag
+            .iter()
+            .filter(|((k, _), _)| k == key)
+            .map(|((_, value), totals)| (value.clone(), *totals))
+            .collect()
+    }
+
+    /// The most recent records, oldest first. Empty unless [UsageRecorder::with_ledger] is set.
+    pub fn records(&self) -> Vec<UsageRecord> {
+        self.state.lock().unwrap().ledger.iter().cloned().collect()
+    }
+
+    /// Clears all totals and records, keeping prices, budgets and callbacks.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.ledger.clear();
+        state.total = UsageTotals::default();
+        state.by_model.clear();
+        state.by_tag.clear();
+    }
+}
+
+/// The recorder, model and tags of a single call, moved into streaming tasks to record the final usage.
+pub(crate) struct UsageScope {
+    pub recorder: UsageRecorder,
+    pub model: ModelId,
+    pub tags: Tags,
+}
+
+impl UsageScope {
+    pub fn record(&self, usage: impl Into<TokenUsage>) {
+        self.recorder
+            .record(self.model.clone(), &self.tags, usage.into());
+    }
+}
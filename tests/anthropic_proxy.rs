@@ -0,0 +1,257 @@
+mod common;
+
+use async_google_gemini::{
+    proxy::{anthropic::messages_request, Proxy},
+    testing::{MockReply, MockServer},
+    types::claude::{ClaudeModel, ANTHROPIC_VERSION},
+};
+use common::{claude_message, claude_reply};
+use serde_json::{json, Value};
+
+async fn start_proxy(server: &MockServer) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(Proxy::new(server.client()).serve(listener));
+    format!("http://{}/v1/messages", addr)
+}
+
+fn messages_body(stream: bool) -> Value {
+    json!({
+        "model": "claude-3-5-sonnet-20241022",
+        "max_tokens": 64,
+        "stream": stream,
+        "messages": [{ "role": "user", "content": "Hello" }]
+    })
+}
+
+#[test]
+fn anthropic_and_vertex_model_ids_are_accepted() {
+    let body = serde_json::to_vec(&messages_body(false)).unwrap();
+    let (model, request) = messages_request(&body).unwrap();
+    assert_eq!(model, ClaudeModel::Claude35SonnetV2);
+    assert_eq!(request["anthropic_version"], ANTHROPIC_VERSION);
+    assert_eq!(request["max_tokens"], 64);
+    assert!(request.get("model").is_none());
+
+    let body = json!({
+        "model": "claude-sonnet-4@20250514",
+        "max_tokens": 64,
+        "messages": [{ "role": "user", "content": "Hello" }]
+    });
+    let (model, request) = messages_request(&serde_json::to_vec(&body).unwrap()).unwrap();
+    assert_eq!(
+        model,
+        ClaudeModel::Custom("claude-sonnet-4@20250514".to_string())
+    );
+    assert_eq!(request["stream"], false);
+
+    let missing = messages_request(br#"{ "max_tokens": 64, "messages": [] }"#);
+    assert!(missing.unwrap_err().contains("model"));
+    assert!(messages_request(b"[]").is_err());
+    let invalid = messages_request(br#"{ "model": "claude-3-haiku-20240307", "messages": [] }"#);
+    assert!(invalid.unwrap_err().contains("max_tokens"));
+}
+
+#[test]
+fn anthropic_aliases_are_mapped_to_vertex_models() {
+    let body = json!({
+        "model": "claude-3-5-sonnet-latest",
+        "max_tokens": 64,
+        "messages": [{ "role": "user", "content": "Hello" }]
+    });
+    let (model, _) = messages_request(&serde_json::to_vec(&body).unwrap()).unwrap();
+    assert_eq!(model, ClaudeModel::Claude35SonnetV2);
+}
+
+#[tokio::test]
+async fn messages_are_forwarded_to_vertex() {
+    let server = MockServer::start().await;
+    let url = start_proxy(&server).await;
+    server.reply(claude_reply("Hi"));
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&messages_body(false))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let message: Value = response.json().await.unwrap();
+    assert_eq!(message["model"], "claude-3-5-sonnet-20241022");
+    assert_eq!(message["content"][0]["text"], "Hi");
+
+    let upstream = &server.requests()[0];
+    assert!(upstream
+        .path
+        .ends_with("/publishers/anthropic/models/claude-3-5-sonnet-v2@20241022:streamRawPredict"));
+    let body: Value = upstream.json().unwrap();
+    assert_eq!(body["anthropic_version"], ANTHROPIC_VERSION);
+    assert!(body.get("model").is_none());
+}
+
+#[tokio::test]
+async fn stream_events_are_passed_through() {
+    let server = MockServer::start().await;
+    let url = start_proxy(&server).await;
+    server.reply(MockReply::events([
+        r#"{"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":3,"output_tokens":0}}}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":1}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]));
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&messages_body(true))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body = response.text().await.unwrap();
+
+    let events = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("event: "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            "message_start",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop"
+        ]
+    );
+    let data = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str::<Value>(data).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(data[0]["message"]["model"], "claude-3-5-sonnet-20241022");
+    assert_eq!(data[1]["content_block"]["text"], "");
+    assert_eq!(data[2]["delta"]["text"], "Hi");
+
+    assert!(server.requests()[0]
+        .path
+        .ends_with("claude-3-5-sonnet-v2@20241022:streamRawPredict?alt=sse"));
+}
+
+#[tokio::test]
+async fn errors_keep_the_anthropic_format() {
+    let server = MockServer::start().await;
+    let url = start_proxy(&server).await;
+    server.reply(MockReply::claude_error(529, "overloaded_error", "busy"));
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&messages_body(false))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 529);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(
+        error,
+        json!({ "type": "error", "error": { "type": "overloaded_error", "message": "busy" } })
+    );
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .body("not json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["error"]["type"], "invalid_request_error");
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn fields_unknown_to_the_crate_are_forwarded() {
+    let server = MockServer::start().await;
+    let url = start_proxy(&server).await;
+    let mut message = claude_message("Hi");
+    message["content"].as_array_mut().unwrap().insert(
+        0,
+        json!({ "type": "thinking", "thinking": "Hmm", "signature": "sig" }),
+    );
+    server.reply(MockReply::json(&message));
+
+    let mut body = messages_body(false);
+    body["top_k"] = json!(5);
+    body["metadata"] = json!({ "user_id": "user-1" });
+    body["thinking"] = json!({ "type": "enabled", "budget_tokens": 32 });
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let response: Value = response.json().await.unwrap();
+    assert_eq!(response["content"][0]["signature"], "sig");
+
+    let upstream: Value = server.requests()[0].json().unwrap();
+    assert_eq!(upstream["top_k"], 5);
+    assert_eq!(upstream["metadata"]["user_id"], "user-1");
+    assert_eq!(upstream["thinking"]["budget_tokens"], 32);
+}
+
+#[tokio::test]
+async fn stream_events_keep_fields_unknown_to_the_crate() {
+    let server = MockServer::start().await;
+    let url = start_proxy(&server).await;
+    server.reply(MockReply::events([
+        r#"{"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":3,"output_tokens":0}}}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Hmm"}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"sig"}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":1}}"#,
+        r#"{"type":"message_stop"}"#,
+    ]));
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&messages_body(true))
+        .send()
+        .await
+        .unwrap();
+    let body = response.text().await.unwrap();
+    let data = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str::<Value>(data).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(data[1]["content_block"]["thinking"], "");
+    assert_eq!(data[2]["delta"]["thinking"], "Hmm");
+    assert_eq!(data[3]["delta"]["signature"], "sig");
+}
+
+#[tokio::test]
+async fn undated_models_are_rejected_before_they_reach_vertex() {
+    let server = MockServer::start().await;
+    let url = start_proxy(&server).await;
+
+    let mut body = messages_body(false);
+    body["model"] = json!("claude-sonnet-4-0");
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["error"]["type"], "invalid_request_error");
+    assert!(error["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("claude-sonnet-4-0"));
+    assert!(server.requests().is_empty());
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use async_google_gemini::{
+    cassette::{Cassette, CassetteMatcher},
+    middleware::{Middleware, RequestContext},
+    testing::{MockReply, MockServer},
+    types::batch::JobState,
+};
+use common::cassette_path;
+use serde_json::{json, Value};
+
+fn job(id: &str, state: &str) -> Value {
+    json!({
+        "name": format!("projects/test-project/locations/us-central1/batchPredictionJobs/{}", id),
+        "displayName": "nightly",
+        "model": "publishers/google/models/gemini-1.5-flash-002",
+        "inputConfig": { "instancesFormat": "jsonl", "gcsSource": { "uris": ["gs://bucket/in.jsonl"] } },
+        "outputConfig": { "predictionsFormat": "jsonl", "gcsDestination": { "outputUriPrefix": "gs://bucket/out" } },
+        "state": state
+    })
+}
+
+#[derive(Clone, Default)]
+struct Methods(Arc<Mutex<Vec<String>>>);
+
+impl Middleware for Methods {
+    fn on_request(
+        &self,
+        ctx: &RequestContext,
+        request: reqwest::RequestBuilder,
+    ) -> reqwest::RequestBuilder {
+        self.0.lock().unwrap().push(ctx.method.clone());
+        request
+    }
+}
+
+#[tokio::test]
+async fn list_all_follows_encoded_page_tokens() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::json(&json!({
+        "batchPredictionJobs": [job("1", "JOB_STATE_SUCCEEDED")],
+        "nextPageToken": "a/b+c=="
+    })));
+    server.reply(MockReply::json(&json!({
+        "batchPredictionJobs": [job("2", "JOB_STATE_RUNNING")]
+    })));
+
+    let methods = Methods::default();
+    let client = server.client().with_middleware(methods.clone());
+    let jobs = client.batch().list_all().await.unwrap();
+
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[1].state, JobState::Running);
+    let requests = server.requests();
+    assert!(!requests[0].path.contains("pageToken"));
+    assert!(requests[1].path.ends_with("?pageToken=a%2Fb%2Bc%3D%3D"));
+    assert_eq!(
+        *methods.0.lock().unwrap(),
+        vec!["batchPredictionJobs.list", "batchPredictionJobs.list"]
+    );
+}
+
+#[tokio::test]
+async fn batch_calls_are_recorded_and_replayed() {
+    let path = cassette_path("batch");
+
+    let server = MockServer::start().await;
+    server.reply(MockReply::json(&job("7", "JOB_STATE_PENDING")));
+    server.reply(MockReply::json(&job("7", "JOB_STATE_SUCCEEDED")));
+    let client = server
+        .client_builder()
+        .cassette(Cassette::record(&path))
+        .build()
+        .unwrap();
+    assert_eq!(
+        client.batch().get("7").await.unwrap().state,
+        JobState::Pending
+    );
+    assert_eq!(
+        client.batch().get("7").await.unwrap().state,
+        JobState::Succeeded
+    );
+    client.cassette().unwrap().save().await.unwrap();
+    drop(client);
+    drop(server);
+
+    // a fresh server on another port, which must not see any of the calls
+    let server = MockServer::start().await;
+    let client = server
+        .client_builder()
+        .cassette(
+            Cassette::replay(&path)
+                .unwrap()
+                .with_matcher(CassetteMatcher::new().ignore_url()),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(
+        client.batch().get("7").await.unwrap().state,
+        JobState::Pending
+    );
+    assert_eq!(
+        client.batch().get("7").await.unwrap().state,
+        JobState::Succeeded
+    );
+    assert!(server.requests().is_empty());
+
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,139 @@
+use async_google_gemini::{
+    error::{ClaudeError, GeminiError},
+    testing::MockServer,
+    types::{
+        capabilities::Capabilities,
+        claude::{ClaudeModel, RawPredictRequest},
+        content::GenerateContentRequest,
+        gemini::GeminiModel,
+    },
+};
+use serde_json::{json, Value};
+
+fn gemini_request(request: Value) -> GenerateContentRequest {
+    serde_json::from_value(request).unwrap()
+}
+
+fn claude_request(request: Value) -> RawPredictRequest {
+    serde_json::from_value(json!({
+        "anthropic_version": "vertex-2023-10-16",
+        "max_tokens": 1024,
+        "system": "",
+        "stream": false,
+        "messages": [{ "role": "user", "content": [request] }]
+    }))
+    .unwrap()
+}
+
+fn video_request() -> GenerateContentRequest {
+    gemini_request(json!({
+        "contents": [{
+            "role": "user",
+            "parts": [
+                { "text": "Describe the clip" },
+                { "fileData": { "mimeType": "video/mp4", "fileUri": "gs://bucket/clip.mp4" } }
+            ]
+        }]
+    }))
+}
+
+fn pdf_block() -> Value {
+    json!({
+        "type": "document",
+        "source": { "type": "base64", "media_type": "application/pdf", "data": "JVBERi0=" }
+    })
+}
+
+#[test]
+fn gemini_requests_are_checked_against_the_model() {
+    let text_only = GeminiModel::Gemini10Pro.capabilities().unwrap();
+    let error = text_only
+        .check_generate_content(&video_request())
+        .unwrap_err();
+    assert!(error.contains("video"), "{}", error);
+
+    let multimodal = GeminiModel::Gemini15Pro002.capabilities().unwrap();
+    assert!(multimodal.check_generate_content(&video_request()).is_ok());
+
+    let functions = gemini_request(json!({
+        "contents": [{ "role": "user", "parts": [{ "text": "Weather?" }] }],
+        "tools": [{ "functionDeclarations": [{ "name": "weather" }] }]
+    }));
+    let vision = GeminiModel::Gemini10ProVision001.capabilities().unwrap();
+    let error = vision.check_generate_content(&functions).unwrap_err();
+    assert!(error.contains("function calling"), "{}", error);
+
+    let json_output = gemini_request(json!({
+        "contents": [{ "role": "user", "parts": [{ "text": "Weather?" }] }],
+        "generationConfig": { "responseMimeType": "application/json" }
+    }));
+    let error = text_only.check_generate_content(&json_output).unwrap_err();
+    assert!(error.contains("structured output"), "{}", error);
+
+    let long_output = gemini_request(json!({
+        "contents": [{ "role": "user", "parts": [{ "text": "Weather?" }] }],
+        "generationConfig": { "maxOutputTokens": 100000 }
+    }));
+    let error = multimodal.check_generate_content(&long_output).unwrap_err();
+    assert!(error.contains("max output tokens"), "{}", error);
+}
+
+#[test]
+fn claude_requests_are_checked_against_the_model() {
+    let without_pdf = ClaudeModel::Claude35Sonnet.capabilities().unwrap();
+    let error = without_pdf
+        .check_raw_predict(&claude_request(pdf_block()))
+        .unwrap_err();
+    assert!(error.contains("pdf"), "{}", error);
+
+    let with_pdf = ClaudeModel::Claude37Sonnet.capabilities().unwrap();
+    assert!(with_pdf
+        .check_raw_predict(&claude_request(pdf_block()))
+        .is_ok());
+
+    let mut long_output = claude_request(json!({ "type": "text", "text": "Hi" }));
+    long_output.max_tokens = 10_000;
+    let haiku = ClaudeModel::Claude3Haiku.capabilities().unwrap();
+    let error = haiku.check_raw_predict(&long_output).unwrap_err();
+    assert!(error.contains("max output tokens"), "{}", error);
+}
+
+#[test]
+fn custom_models_have_no_known_capabilities() {
+    assert!(GeminiModel::Custom("gemini-exp-1206".to_string())
+        .capabilities()
+        .is_none());
+    assert!(ClaudeModel::Custom("claude-sonnet-4@20250514".to_string())
+        .capabilities()
+        .is_none());
+}
+
+#[tokio::test]
+async fn unsupported_requests_are_rejected_before_they_are_sent() {
+    let server = MockServer::start().await;
+    let client = server.client();
+
+    let error = client
+        .gemini()
+        .generate_content(GeminiModel::Gemini10Pro, video_request())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, GeminiError::UnsupportedCapability(_)),
+        "{:?}",
+        error
+    );
+
+    let error = client
+        .claude()
+        .raw_predict(ClaudeModel::Claude35Sonnet, claude_request(pdf_block()))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, ClaudeError::UnsupportedCapability(_)),
+        "{:?}",
+        error
+    );
+
+    assert!(server.requests().is_empty());
+}
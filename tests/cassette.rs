@@ -0,0 +1,203 @@
+mod common;
+
+use std::path::PathBuf;
+
+use async_google_gemini::{
+    cassette::{Cassette, CassetteMatcher, CassetteMode},
+    client::Client,
+    error::GeminiError,
+    testing::{MockReply, MockServer},
+    types::{
+        content::{GenerateContentRequest, GenerationConfig},
+        gemini::GeminiModel,
+    },
+};
+use common::{cassette_path, gemini_request, gemini_text, GEMINI_MODEL};
+use futures::StreamExt;
+
+fn request(temperature: f32) -> GenerateContentRequest {
+    let mut request = gemini_request("Hello");
+    request.base_model_params.generation_config = Some(GenerationConfig {
+        temperature: Some(temperature),
+        ..Default::default()
+    });
+    request
+}
+
+async fn replay_client(path: &PathBuf, matcher: CassetteMatcher) -> (MockServer, Client) {
+    let server = MockServer::start().await;
+    let client = server
+        .client_builder()
+        .cassette(Cassette::replay(path).unwrap().with_matcher(matcher))
+        .build()
+        .unwrap();
+    (server, client)
+}
+
+async fn streamed_text(client: &Client) -> String {
+    let mut stream = client
+        .gemini()
+        .stream_generate_content(GEMINI_MODEL, request(0.1))
+        .await
+        .unwrap();
+    let mut text = String::new();
+    while let Some(chunk) = stream.next().await {
+        text.push_str(&chunk.unwrap().text().unwrap_or_default());
+    }
+    text
+}
+
+#[tokio::test]
+async fn streams_and_errors_are_recorded_and_replayed() {
+    let path = cassette_path("stream");
+
+    let server = MockServer::start().await;
+    server.reply(MockReply::stream_generate_content(&[
+        gemini_text("Hello "),
+        gemini_text("there"),
+    ]));
+    server.reply(MockReply::gemini_error(429, "quota exceeded").with_header("x-trace", "abc"));
+    let cassette = Cassette::record(&path).redact_header("x-trace");
+    let client = server
+        .client_builder()
+        .cassette(cassette.clone())
+        .build()
+        .unwrap();
+    assert_eq!(streamed_text(&client).await, "Hello there");
+    let result = client
+        .gemini()
+        .generate_content(GEMINI_MODEL, request(0.1))
+        .await;
+    assert!(matches!(result, Err(GeminiError::ResourceExhausted)));
+
+    let interactions = cassette.interactions();
+    assert_eq!(interactions.len(), 2);
+    assert_eq!(interactions[0].request.method, "streamGenerateContent");
+    assert_eq!(interactions[0].response.events.as_ref().unwrap().len(), 2);
+    assert_eq!(interactions[1].response.status, 429);
+    for interaction in &interactions {
+        let headers = interaction
+            .request
+            .headers
+            .iter()
+            .chain(&interaction.response.headers)
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert!(!headers.contains(&"authorization"), "{:?}", headers);
+        assert!(!headers.contains(&"x-trace"), "{:?}", headers);
+    }
+    cassette.save().await.unwrap();
+
+    let (server, client) = replay_client(&path, CassetteMatcher::new().ignore_url()).await;
+    assert_eq!(client.cassette().unwrap().mode(), CassetteMode::Replay);
+    assert_eq!(streamed_text(&client).await, "Hello there");
+    let result = client
+        .gemini()
+        .generate_content(GEMINI_MODEL, request(0.1))
+        .await;
+    assert!(matches!(result, Err(GeminiError::ResourceExhausted)));
+    assert!(server.requests().is_empty());
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+async fn requests_are_matched_on_the_body_unless_fields_are_ignored() {
+    let path = cassette_path("matcher");
+
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("first")));
+    server.reply(MockReply::generate_content(&gemini_text("second")));
+    let client = server
+        .client_builder()
+        .cassette(Cassette::record(&path))
+        .build()
+        .unwrap();
+    for _ in 0..2 {
+        client
+            .gemini()
+            .generate_content(GEMINI_MODEL, request(0.1))
+            .await
+            .unwrap();
+    }
+    client.cassette().unwrap().save().await.unwrap();
+
+    let (_server, client) = replay_client(&path, CassetteMatcher::new().ignore_url()).await;
+    let result = client
+        .gemini()
+        .generate_content(GEMINI_MODEL, request(0.9))
+        .await;
+    assert!(matches!(result, Err(GeminiError::Cassette(_))));
+    let result = client
+        .gemini()
+        .generate_content(GeminiModel::Gemini15Pro002, request(0.1))
+        .await;
+    assert!(matches!(result, Err(GeminiError::Cassette(_))));
+
+    let matcher = CassetteMatcher::new()
+        .ignore_url()
+        .ignore_field("generationConfig.temperature");
+    let (server, client) = replay_client(&path, matcher).await;
+    // identical requests are answered in the recorded order, then with the last interaction
+    let mut texts = Vec::new();
+    for _ in 0..3 {
+        let response = client
+            .gemini()
+            .generate_content(GEMINI_MODEL, request(0.9))
+            .await
+            .unwrap();
+        texts.push(response.text().unwrap_or_default());
+    }
+    assert_eq!(texts, ["first", "second", "second"]);
+    assert!(server.requests().is_empty());
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+async fn interactions_are_only_written_when_the_cassette_is_saved() {
+    let path = cassette_path("save");
+    std::fs::remove_file(&path).ok();
+
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("Hi")));
+    let cassette = Cassette::record(&path);
+    let client = server
+        .client_builder()
+        .cassette(cassette.clone())
+        .build()
+        .unwrap();
+    client
+        .gemini()
+        .generate_content(GEMINI_MODEL, request(0.1))
+        .await
+        .unwrap();
+    assert_eq!(cassette.interactions().len(), 1);
+    assert!(!path.exists());
+
+    cassette.save().await.unwrap();
+    let replayed = Cassette::replay(&path).unwrap();
+    assert_eq!(replayed.interactions().len(), 1);
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+async fn responses_whose_body_cannot_be_read_are_not_recorded() {
+    let server = MockServer::start().await;
+    // the connection closes before the announced body is complete
+    server.reply(MockReply::events(["{}"]).with_header("content-length", "1000"));
+    let cassette = Cassette::record(cassette_path("broken"));
+    let client = server
+        .client_builder()
+        .cassette(cassette.clone())
+        .build()
+        .unwrap();
+
+    let result = client
+        .gemini()
+        .generate_content(GEMINI_MODEL, request(0.1))
+        .await;
+    assert!(result.is_err());
+    assert!(cassette.interactions().is_empty());
+}
@@ -0,0 +1,197 @@
+use async_google_gemini::{
+    error::ChatError,
+    testing::{MockReply, MockServer},
+    types::{
+        chat::{ChatMessage, ChatPart, ChatRequest, ChatRole, ModelId},
+        claude::ClaudeContent,
+        content::{Content, Part},
+    },
+};
+use serde_json::{json, Value};
+
+fn weather_call() -> ChatPart {
+    ChatPart::ToolCall {
+        id: None,
+        name: "weather".to_string(),
+        arguments: json!({ "city": "Paris" }),
+    }
+}
+
+fn weather_result(content: Value) -> ChatPart {
+    ChatPart::ToolResult {
+        id: None,
+        name: "weather".to_string(),
+        content,
+        is_error: None,
+    }
+}
+
+#[tokio::test]
+async fn parallel_gemini_tool_calls_get_distinct_claude_ids() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::json(&json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "content": [{ "type": "text", "text": "Sunny in both" }],
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": { "input_tokens": 10, "output_tokens": 4 }
+    })));
+
+    let request = ChatRequest {
+        messages: vec![
+            ChatMessage::user("Weather in Paris and Lyon?"),
+            ChatMessage {
+                role: ChatRole::Assistant,
+                parts: vec![weather_call(), weather_call()],
+            },
+            ChatMessage {
+                role: ChatRole::User,
+                parts: vec![
+                    weather_result(json!("sunny")),
+                    weather_result(json!("rain")),
+                ],
+            },
+        ],
+        ..Default::default()
+    };
+
+    let model: ModelId = "claude-3-5-sonnet-v2@20241022".parse().unwrap();
+    let response = server
+        .client()
+        .chat_model(model)
+        .chat(request)
+        .await
+        .unwrap();
+    assert_eq!(response.text(), "Sunny in both");
+
+    let body: Value = server.requests()[0].json().unwrap();
+    let calls = &body["messages"][1]["content"];
+    let results = &body["messages"][2]["content"];
+    assert_ne!(calls[0]["id"], calls[1]["id"]);
+    assert_eq!(results[0]["tool_use_id"], calls[0]["id"]);
+    assert_eq!(results[1]["tool_use_id"], calls[1]["id"]);
+    assert_eq!(results[0]["content"], "sunny");
+    assert_eq!(results[1]["content"], "rain");
+}
+
+#[test]
+fn tool_calls_without_id_get_unique_claude_ids() {
+    let first = ClaudeContent::try_from(weather_call()).unwrap();
+    let second = ClaudeContent::try_from(weather_call()).unwrap();
+
+    assert!(first.id.is_some());
+    assert_ne!(first.id, second.id);
+}
+
+#[test]
+fn tool_results_without_id_are_rejected_for_claude() {
+    let error = ClaudeContent::try_from(weather_result(json!("sunny"))).unwrap_err();
+    assert!(matches!(error, ChatError::Conversion(_)), "{:?}", error);
+}
+
+#[test]
+fn audio_and_video_are_rejected_for_claude() {
+    for mime_type in ["audio/wav", "video/mp4"] {
+        let part = ChatPart::InlineData {
+            mime_type: mime_type.to_string(),
+            data: "AAAA".to_string(),
+        };
+        let error = ClaudeContent::try_from(part).unwrap_err();
+        assert!(matches!(error, ChatError::Conversion(_)), "{:?}", error);
+    }
+
+    let image = ClaudeContent::try_from(ChatPart::InlineData {
+        mime_type: "image/png".to_string(),
+        data: "AAAA".to_string(),
+    })
+    .unwrap();
+    assert_eq!(image.c_type, "image");
+}
+
+#[test]
+fn tool_results_round_trip_through_gemini() {
+    for content in [
+        json!("sunny"),
+        json!(21.5),
+        json!(["a", "b"]),
+        json!({ "temperature": 21 }),
+        json!({ "content": { "nested": true } }),
+        json!({ "content": "x" }),
+    ] {
+        let part = Part::from(weather_result(content.clone()));
+        let Part::FunctionResponsePart(response) = &part else {
+            panic!("expected a function response, got {:?}", part);
+        };
+        assert!(response.function_response.response.is_object());
+
+        match ChatPart::from(part) {
+            ChatPart::ToolResult {
+                name,
+                content: back,
+                ..
+            } => {
+                assert_eq!(name, "weather");
+                assert_eq!(back, content);
+            }
+            other => panic!("expected a tool result, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn gemini_content_round_trips_through_chat() {
+    let message = ChatMessage {
+        role: ChatRole::Assistant,
+        parts: vec![ChatPart::text("Checking"), weather_call(), weather_call()],
+    };
+
+    let content = Content::from(message);
+    assert_eq!(content.role, "model");
+    assert_eq!(content.parts.len(), 3);
+
+    let back = ChatMessage::from(content);
+    assert_eq!(back.role, ChatRole::Assistant);
+    assert_eq!(back.text(), "Checking");
+    assert_eq!(
+        back.parts
+            .iter()
+            .filter(|part| matches!(part, ChatPart::ToolCall { name, .. } if name == "weather"))
+            .count(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn chat_model_in_sends_requests_to_the_given_location() {
+    let server = MockServer::start().await;
+    let reply = json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "content": [{ "type": "text", "text": "Hi" }],
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": { "input_tokens": 1, "output_tokens": 1 }
+    });
+    server.reply(MockReply::json(&reply));
+    server.reply(MockReply::json(&reply));
+
+    let client = server.client();
+    let model: ModelId = "claude-3-5-sonnet-v2@20241022".parse().unwrap();
+    for location in [None, Some("europe-west1")] {
+        client
+            .chat_model_in(model.clone(), location)
+            .chat(ChatRequest {
+                messages: vec![ChatMessage::user("Hello")],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    let requests = server.requests();
+    assert!(requests[0].path.contains("/locations/us-east5/"));
+    assert!(requests[1].path.contains("/locations/europe-west1/"));
+}
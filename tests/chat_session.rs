@@ -0,0 +1,191 @@
+mod common;
+
+use async_google_gemini::{
+    error::GeminiError,
+    testing::{MockReply, MockServer},
+    types::{
+        content::{Content, GenerateContentResponse, StartChatParams},
+        gemini::GeminiModel,
+    },
+};
+use common::{gemini_candidate, gemini_text};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+fn roles_and_texts(server: &MockServer, request: usize) -> Vec<(String, String)> {
+    let body: Value = server.requests()[request].json().unwrap();
+    body["contents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|content| {
+            (
+                content["role"].as_str().unwrap().to_string(),
+                content["parts"][0]["text"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn texts(history: &[Content]) -> Vec<String> {
+    history
+        .iter()
+        .map(|content| {
+            let part = serde_json::to_value(&content.parts[0]).unwrap();
+            part["text"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn history_is_only_updated_after_a_reply() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::gemini_error(503, "unavailable"));
+    server.reply(MockReply::generate_content(&gemini_text("Hi")));
+    server.reply(MockReply::generate_content(&gemini_text("Fine")));
+
+    let mut session = server
+        .client()
+        .gemini()
+        .start_chat(GeminiModel::Gemini15Flash002, StartChatParams::default());
+    assert!(session.send_message(vec!["Hello".into()]).await.is_err());
+    assert!(session.history().is_empty());
+
+    session.send_message(vec!["Hello".into()]).await.unwrap();
+    session
+        .send_message(vec!["How are you?".into()])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        texts(session.history()),
+        ["Hello", "Hi", "How are you?", "Fine"]
+    );
+    assert_eq!(
+        roles_and_texts(&server, 2),
+        [
+            ("user".to_string(), "Hello".to_string()),
+            ("model".to_string(), "Hi".to_string()),
+            ("user".to_string(), "How are you?".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn blocked_and_empty_replies_leave_the_history_unchanged() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_candidate(json!({
+        "index": 0,
+        "content": { "role": "model", "parts": [{ "text": "Partial" }] },
+        "finishReason": "SAFETY"
+    }))));
+    server.reply(MockReply::json(&json!({
+        "promptFeedback": { "blockReason": "PROHIBITED_CONTENT", "safetyRatings": [] }
+    })));
+    server.reply(MockReply::generate_content(&gemini_candidate(json!({
+        "index": 0,
+        "content": { "role": "model", "parts": [] },
+        "finishReason": "STOP"
+    }))));
+
+    let mut session = server
+        .client()
+        .gemini()
+        .start_chat(GeminiModel::Gemini15Flash002, StartChatParams::default());
+
+    let result = session.send_message(vec!["Hello".into()]).await;
+    assert!(matches!(result, Err(GeminiError::ResponseBlocked(_))));
+    let result = session.send_message(vec!["Hello".into()]).await;
+    assert!(matches!(result, Err(GeminiError::PromptBlocked(_))));
+    session.send_message(vec!["Hello".into()]).await.unwrap();
+
+    assert!(session.history().is_empty());
+}
+
+#[tokio::test]
+async fn snapshots_can_be_restored() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("Hi")));
+    server.reply(MockReply::generate_content(&gemini_text("Bye")));
+    server.reply(MockReply::generate_content(&gemini_text("Hi again")));
+
+    let mut session = server
+        .client()
+        .gemini()
+        .start_chat(GeminiModel::Gemini15Flash002, StartChatParams::default());
+    session.send_message(vec!["Hello".into()]).await.unwrap();
+    let snapshot = session.snapshot();
+    session.send_message(vec!["Goodbye".into()]).await.unwrap();
+    assert_eq!(session.history().len(), 4);
+
+    session.restore(snapshot);
+    assert_eq!(session.history().len(), 2);
+    session
+        .send_message(vec!["Hello again".into()])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        roles_and_texts(&server, 2),
+        [
+            ("user".to_string(), "Hello".to_string()),
+            ("model".to_string(), "Hi".to_string()),
+            ("user".to_string(), "Hello again".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn streams_commit_once_they_finish() {
+    let server = MockServer::start().await;
+    let chunks = [gemini_text("Hel"), gemini_text("lo")];
+    server.reply(MockReply::stream_generate_content(&chunks));
+    server.reply(MockReply::stream_generate_content(&chunks));
+    server.reply(MockReply::stream_generate_content(&[
+        gemini_text("Part"),
+        gemini_candidate(json!({
+            "index": 0,
+            "content": { "role": "model", "parts": [{ "text": "ial" }] },
+            "finishReason": "RECITATION"
+        })),
+    ]));
+
+    let mut session = server
+        .client()
+        .gemini()
+        .start_chat(GeminiModel::Gemini15Flash002, StartChatParams::default());
+
+    // an abandoned stream is not committed
+    let mut stream = session
+        .send_message_stream(vec!["Hi".into()])
+        .await
+        .unwrap();
+    stream.next().await.unwrap().unwrap();
+    assert_eq!(stream.response().text().as_deref(), Some("Hel"));
+    drop(stream);
+    assert!(session.history().is_empty());
+
+    let mut stream = session
+        .send_message_stream(vec!["Hi".into()])
+        .await
+        .unwrap();
+    while let Some(chunk) = stream.next().await {
+        chunk.unwrap();
+    }
+    assert_eq!(texts(session.history()), ["Hi", "Hello"]);
+
+    // a blocked stream ends with an error and keeps the partial text out of the history
+    let mut stream = session
+        .send_message_stream(vec!["Sing".into()])
+        .await
+        .unwrap();
+    let mut results = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        results.push(chunk.map(|chunk: GenerateContentResponse| chunk.text()));
+    }
+    assert!(matches!(
+        results.last(),
+        Some(Err(GeminiError::ResponseBlocked(_)))
+    ));
+    assert_eq!(session.history().len(), 2);
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use async_google_gemini::{
+    claude::conversation::RoleSequencePolicy,
+    client::Client,
+    config::ClaudeBackend,
+    error::ClaudeError,
+    testing::{test_config, MockReply, MockServer},
+    types::{
+        chat::{ChatMessage, ChatRequest},
+        claude::{ClaudeMessage, ClaudeModel},
+    },
+};
+use common::claude_reply;
+use serde_json::{json, Value};
+
+async fn chat(client: &Client) {
+    client
+        .chat_model(ClaudeModel::Claude35SonnetV2.into())
+        .chat(ChatRequest {
+            messages: vec![ChatMessage::user("Hello")],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+}
+
+fn message(role: &str, text: &str) -> ClaudeMessage {
+    ClaudeMessage {
+        role: role.to_string(),
+        content: text.into(),
+    }
+}
+
+#[tokio::test]
+async fn consecutive_roles_are_merged_or_rejected() {
+    let server = MockServer::start().await;
+    let claude = server.client().claude();
+
+    let conversation = claude
+        .conversation(ClaudeModel::Claude35SonnetV2, 256)
+        .with_history(vec![
+            message("user", "Hello"),
+            message("user", "Are you there?"),
+            message("assistant", "Yes"),
+        ])
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(conversation.messages()).unwrap(),
+        json!([
+            { "role": "user", "content": "Hello\n\nAre you there?" },
+            { "role": "assistant", "content": "Yes" }
+        ])
+    );
+
+    let mut conversation = claude
+        .conversation(ClaudeModel::Claude35SonnetV2, 256)
+        .with_policy(RoleSequencePolicy::Reject);
+    conversation.push(message("user", "Hello")).unwrap();
+    assert!(matches!(
+        conversation.push(message("user", "Again")),
+        Err(ClaudeError::InvalidRequestError(_))
+    ));
+    assert_eq!(conversation.messages().len(), 1);
+
+    let result = claude
+        .conversation(ClaudeModel::Claude35SonnetV2, 256)
+        .with_history(vec![message("assistant", "Hi")]);
+    assert!(matches!(result, Err(ClaudeError::InvalidRequestError(_))));
+    let result = claude
+        .conversation(ClaudeModel::Claude35SonnetV2, 256)
+        .with_history(vec![message("system", "Be brief")]);
+    assert!(matches!(result, Err(ClaudeError::InvalidRequestError(_))));
+}
+
+#[tokio::test]
+async fn conversation_only_keeps_answered_turns() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::claude_error(529, "overloaded_error", "busy"));
+    server.reply(claude_reply("Hi"));
+
+    let mut conversation = server
+        .client()
+        .claude()
+        .conversation(ClaudeModel::Claude35SonnetV2, 256);
+    assert!(conversation.send("Hello").await.is_err());
+    assert!(conversation.messages().is_empty());
+
+    conversation.send("Hello again").await.unwrap();
+    assert_eq!(
+        serde_json::to_value(conversation.messages()).unwrap(),
+        json!([
+            { "role": "user", "content": "Hello again" },
+            { "role": "assistant", "content": [{ "type": "text", "text": "Hi" }] }
+        ])
+    );
+
+    let body: Value = server.requests()[1].json().unwrap();
+    assert_eq!(
+        body["messages"],
+        json!([{ "role": "user", "content": "Hello again" }])
+    );
+}
+
+#[test]
+fn anthropic_ids_round_trip() {
+    let models = [
+        ClaudeModel::Claude37Sonnet,
+        ClaudeModel::Claude35SonnetV2,
+        ClaudeModel::Claude35Sonnet,
+        ClaudeModel::Claude3Opus,
+        ClaudeModel::Claude3Haiku,
+        ClaudeModel::Claude3Sonnet,
+        ClaudeModel::Custom("claude-sonnet-4@20250514".to_string()),
+    ];
+    for model in models {
+        assert_eq!(ClaudeModel::from_anthropic_id(&model.anthropic_id()), model);
+    }
+
+    assert_eq!(
+        ClaudeModel::Custom("claude-sonnet-4@20250514".to_string()).anthropic_id(),
+        "claude-sonnet-4-20250514"
+    );
+    assert_eq!(
+        ClaudeModel::from_anthropic_id("claude-3-5-sonnet-latest"),
+        ClaudeModel::Claude35SonnetV2
+    );
+    assert_eq!(
+        ClaudeModel::from_anthropic_id("claude-3-5-haiku-latest"),
+        ClaudeModel::Custom("claude-3-5-haiku@20241022".to_string())
+    );
+    assert_eq!(
+        ClaudeModel::from_anthropic_id("claude-sonnet-4-0"),
+        ClaudeModel::Custom("claude-sonnet-4-0".to_string())
+    );
+}
+
+#[tokio::test]
+async fn an_anthropic_key_alone_keeps_claude_on_vertex() {
+    let server = MockServer::start().await;
+    server.reply(claude_reply("Hi"));
+
+    let config = test_config().with_anthropic_api_key("sk-ant-test");
+    assert_eq!(config.claude_backend(), ClaudeBackend::Vertex);
+    let client = Client::builder(config)
+        .base_url(server.url())
+        .build()
+        .unwrap();
+    chat(&client).await;
+
+    let request = &server.requests()[0];
+    assert!(request.path.contains(
+        "/locations/us-east5/publishers/anthropic/models/claude-3-5-sonnet-v2@20241022:"
+    ));
+    assert!(request.header("x-api-key").is_none());
+}
+
+#[tokio::test]
+async fn the_anthropic_backend_is_chosen_explicitly() {
+    let server = MockServer::start().await;
+    server.reply(claude_reply("Hi"));
+
+    let config = test_config()
+        .with_anthropic_api_key("sk-ant-test")
+        .with_claude_backend("anthropic".parse().unwrap());
+    let client = Client::builder(config)
+        .base_url(server.url())
+        .build()
+        .unwrap();
+    chat(&client).await;
+
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/v1/messages");
+    assert_eq!(request.header("x-api-key"), Some("sk-ant-test"));
+    let body: Value = request.json().unwrap();
+    assert_eq!(body["model"], "claude-3-5-sonnet-20241022");
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use async_google_gemini::{
+    error::GeminiError,
+    limiter::{LimitBehavior, RateLimit, RateLimiter},
+    middleware::{Middleware, RequestContext},
+    testing::{MockReply, MockServer},
+};
+use common::{gemini_model_id, gemini_request, gemini_text, GEMINI_MODEL};
+use reqwest::RequestBuilder;
+
+#[derive(Clone, Default)]
+struct Counter(Arc<AtomicUsize>);
+
+impl Middleware for Counter {
+    fn on_request(&self, _ctx: &RequestContext, request: RequestBuilder) -> RequestBuilder {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        request
+    }
+}
+
+#[tokio::test]
+async fn clones_share_config_middleware_and_limiter_across_tasks() {
+    let server = MockServer::start().await;
+    server.fallback(MockReply::generate_content(&gemini_text("Hi")));
+
+    let counter = Counter::default();
+    let client = server
+        .client_builder()
+        .middleware(counter.clone())
+        .rate_limiter(
+            RateLimiter::new()
+                .with_limit(
+                    gemini_model_id(),
+                    RateLimit::new().with_requests_per_minute(2),
+                )
+                .with_behavior(LimitBehavior::FailFast),
+        )
+        .build()
+        .unwrap();
+
+    let tasks = [client.clone(), client.clone()].map(|client| {
+        tokio::spawn(async move {
+            assert_eq!(client.config().project_id(), "test-project");
+            client
+                .gemini()
+                .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+                .await
+        })
+    });
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+    assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+
+    // the quota was used up by the clones
+    let gemini = client.gemini();
+    let error = tokio::spawn(async move {
+        gemini
+            .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+            .await
+    })
+    .await
+    .unwrap()
+    .unwrap_err();
+    assert!(matches!(error, GeminiError::RateLimited(_)), "{:?}", error);
+    assert_eq!(server.requests().len(), 2);
+}
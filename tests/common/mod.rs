@@ -0,0 +1,159 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_google_gemini::{
+    testing::MockReply,
+    types::{
+        chat::ModelId,
+        content::{Content, GenerateContentRequest, GenerateContentResponse},
+        gemini::GeminiModel,
+    },
+};
+use serde_json::{json, Value};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+/// The Gemini model the fixtures are sent to.
+pub const GEMINI_MODEL: GeminiModel = GeminiModel::Gemini15Flash002;
+
+/// [GEMINI_MODEL] as a [ModelId], e.g. to configure limits or prices.
+pub fn gemini_model_id() -> ModelId {
+    GEMINI_MODEL.into()
+}
+
+/// A Gemini response with a single text candidate which finished normally.
+pub fn gemini_text(text: &str) -> GenerateContentResponse {
+    gemini_candidate(json!({
+        "index": 0,
+        "content": { "role": "model", "parts": [{ "text": text }] },
+        "finishReason": "STOP"
+    }))
+}
+
+/// A Gemini response with the given candidate.
+pub fn gemini_candidate(candidate: Value) -> GenerateContentResponse {
+    serde_json::from_value(json!({ "candidates": [candidate] })).unwrap()
+}
+
+/// A Gemini request with a single user message.
+pub fn gemini_request(text: &str) -> GenerateContentRequest {
+    GenerateContentRequest {
+        contents: vec![Content {
+            parts: vec![text.into()],
+            role: "user".to_string(),
+        }],
+        base_model_params: Default::default(),
+        cached_content: None,
+    }
+}
+
+/// A complete Claude message with a single text block.
+pub fn claude_message(text: &str) -> Value {
+    json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "content": [{ "type": "text", "text": text }],
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": { "input_tokens": 3, "output_tokens": 1 }
+    })
+}
+
+/// Replies to a Claude call with [claude_message].
+pub fn claude_reply(text: &str) -> MockReply {
+    MockReply::json(&claude_message(text))
+}
+
+/// A temporary cassette path, unique to the test process.
+pub fn cassette_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("{}-cassette-{}.json", name, std::process::id()))
+}
+
+/// The fields of a span or event.
+pub type Fields = HashMap<String, String>;
+
+/// Collects the fields of every span and event, install it with [tracing::subscriber::set_default].
+#[derive(Clone, Default)]
+pub struct CapturedTracing {
+    spans: Arc<Mutex<Vec<Fields>>>,
+    events: Arc<Mutex<Vec<Fields>>>,
+}
+
+impl CapturedTracing {
+    /// The spans with the given name, with the fields recorded so far.
+    pub fn spans(&self, name: &str) -> Vec<Fields> {
+        let spans = self.spans.lock().unwrap();
+        spans
+            .iter()
+            .filter(|fields| fields.get("span.name").is_some_and(|n| n == name))
+            .cloned()
+            .collect()
+    }
+
+    /// The events which have the given field.
+    pub fn events_with(&self, field: &str) -> Vec<Fields> {
+        let events = self.events.lock().unwrap();
+        events
+            .iter()
+            .filter(|fields| fields.contains_key(field))
+            .cloned()
+            .collect()
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for CapturedTracing {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields =
+            Fields::from([("span.name".to_string(), span.metadata().name().to_string())]);
+        span.record(&mut FieldVisitor(&mut fields));
+
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(fields);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        if let Some(fields) = spans.get_mut(span.into_u64() as usize - 1) {
+            values.record(&mut FieldVisitor(fields));
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        self.events.lock().unwrap().push(fields);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
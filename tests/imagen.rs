@@ -0,0 +1,139 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use async_google_gemini::{
+    cassette::{Cassette, CassetteMatcher},
+    error::ImagenError,
+    middleware::{Middleware, RequestContext},
+    testing::{MockReply, MockServer},
+    types::imagen::{
+        AspectRatio, EditImageRequest, EditMode, GenerateImagesRequest, Image, ImagenModel,
+    },
+};
+use common::cassette_path;
+use serde_json::{json, Value};
+
+#[derive(Clone, Default)]
+struct Models(Arc<Mutex<Vec<String>>>);
+
+impl Middleware for Models {
+    fn on_request(
+        &self,
+        ctx: &RequestContext,
+        request: reqwest::RequestBuilder,
+    ) -> reqwest::RequestBuilder {
+        let model = ctx.model.as_ref().map(ToString::to_string);
+        self.0.lock().unwrap().push(model.unwrap_or_default());
+        request
+    }
+}
+
+fn predictions() -> MockReply {
+    MockReply::json(&json!({
+        "predictions": [
+            { "bytesBase64Encoded": "iVBORw==", "mimeType": "image/png", "prompt": "a tabby cat" },
+            { "raiFilteredReason": "blocked" }
+        ]
+    }))
+}
+
+fn request() -> GenerateImagesRequest {
+    GenerateImagesRequest::builder()
+        .prompt("a cat")
+        .number_of_images(2u32)
+        .aspect_ratio(AspectRatio::Landscape16x9)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn generate_images_decodes_images_and_filter_reasons() {
+    let server = MockServer::start().await;
+    server.reply(predictions());
+
+    let models = Models::default();
+    let client = server.client().with_middleware(models.clone());
+    let response = client
+        .imagen()
+        .generate_images(ImagenModel::Imagen3Generate002, request())
+        .await
+        .unwrap();
+
+    let images = response.images().collect::<Vec<_>>();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].mime_type, "image/png");
+    assert_eq!(images[0].data, b"\x89PNG");
+    assert_eq!(response.filtered_reasons().collect::<Vec<_>>(), ["blocked"]);
+    assert_eq!(*models.0.lock().unwrap(), ["imagen-3.0-generate-002"]);
+
+    let request = &server.requests()[0];
+    assert!(request
+        .path
+        .ends_with("/publishers/google/models/imagen-3.0-generate-002:predict"));
+    let body: Value = request.json().unwrap();
+    assert_eq!(body["instances"][0]["prompt"], "a cat");
+    assert_eq!(body["parameters"]["sampleCount"], 2);
+    assert_eq!(body["parameters"]["aspectRatio"], "16:9");
+}
+
+#[tokio::test]
+async fn edit_image_needs_a_mask_for_user_provided_masks() {
+    let server = MockServer::start().await;
+    let image = Image::new("image/png", b"\x89PNG\r\n\x1a\n".to_vec());
+    let request = EditImageRequest::builder()
+        .prompt("remove the hat")
+        .image(image)
+        .edit_mode(EditMode::InpaintRemoval)
+        .build()
+        .unwrap();
+
+    let result = server
+        .client()
+        .imagen()
+        .edit_image(ImagenModel::Imagen3Capability001, request)
+        .await;
+
+    assert!(matches!(result, Err(ImagenError::InvalidRequest(_))));
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn generate_images_is_replayed_from_a_cassette() {
+    let path = cassette_path("imagen");
+
+    let server = MockServer::start().await;
+    server.reply(predictions());
+    let client = server
+        .client_builder()
+        .cassette(Cassette::record(&path))
+        .build()
+        .unwrap();
+    client
+        .imagen()
+        .generate_images(ImagenModel::Imagen3Generate002, request())
+        .await
+        .unwrap();
+    client.cassette().unwrap().save().await.unwrap();
+
+    let server = MockServer::start().await;
+    let client = server
+        .client_builder()
+        .cassette(
+            Cassette::replay(&path)
+                .unwrap()
+                .with_matcher(CassetteMatcher::new().ignore_url()),
+        )
+        .build()
+        .unwrap();
+    let response = client
+        .imagen()
+        .generate_images(ImagenModel::Imagen3Generate002, request())
+        .await
+        .unwrap();
+
+    assert_eq!(response.images().count(), 1);
+    assert!(server.requests().is_empty());
+
+    std::fs::remove_file(&path).ok();
+}
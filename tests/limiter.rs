@@ -0,0 +1,182 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use async_google_gemini::{
+    claude::{ANTHROPIC_REGION, DEFAULT_LOCATION},
+    client::Client,
+    config::ClaudeBackend,
+    error::{ClaudeError, GeminiError},
+    limiter::{LimitBehavior, RateLimit, RateLimiter},
+    testing::{test_config, MockServer},
+    types::{
+        claude::{ClaudeModel, RawPredictRequest},
+        gemini::GeminiModel,
+    },
+};
+use common::{claude_reply, gemini_model_id as model, gemini_request, GEMINI_MODEL};
+use serde_json::json;
+
+#[tokio::test]
+async fn fail_fast_rejects_calls_over_the_request_quota() {
+    let limiter = RateLimiter::new()
+        .with_limit(model(), RateLimit::new().with_requests_per_minute(2))
+        .with_behavior(LimitBehavior::FailFast);
+
+    limiter.acquire(&model(), "us-central1", 0).await.unwrap();
+    limiter.acquire(&model(), "us-central1", 0).await.unwrap();
+    let error = limiter
+        .acquire(&model(), "us-central1", 0)
+        .await
+        .err()
+        .unwrap();
+    assert!(error.contains("retry in"), "{}", error);
+
+    // every region has its own bucket, unlimited models are never held back
+    limiter.acquire(&model(), "europe-west4", 0).await.unwrap();
+    let other = GeminiModel::Gemini15Pro002.into();
+    for _ in 0..5 {
+        limiter.acquire(&other, "us-central1", 0).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn region_limits_override_model_limits() {
+    let limiter = RateLimiter::new()
+        .with_limit(model(), RateLimit::new().with_tokens_per_minute(100))
+        .with_region_limit(
+            model(),
+            "europe-west4",
+            RateLimit::new().with_tokens_per_minute(10),
+        )
+        .with_behavior(LimitBehavior::FailFast);
+
+    assert!(limiter.limits_tokens(&model(), "us-central1"));
+    limiter.acquire(&model(), "us-central1", 80).await.unwrap();
+    assert!(limiter.acquire(&model(), "us-central1", 30).await.is_err());
+    limiter.acquire(&model(), "europe-west4", 8).await.unwrap();
+    assert!(limiter.acquire(&model(), "europe-west4", 5).await.is_err());
+}
+
+#[tokio::test]
+async fn waits_until_the_token_bucket_has_refilled() {
+    // 10 tokens a second
+    let limiter =
+        RateLimiter::new().with_limit(model(), RateLimit::new().with_tokens_per_minute(600));
+    limiter.acquire(&model(), "us-central1", 600).await.unwrap();
+
+    let start = Instant::now();
+    limiter.acquire(&model(), "us-central1", 5).await.unwrap();
+    let waited = start.elapsed();
+    assert!(waited >= Duration::from_millis(400), "{:?}", waited);
+    assert!(waited < Duration::from_secs(2), "{:?}", waited);
+}
+
+#[tokio::test]
+async fn reconciled_tokens_are_taken_from_the_bucket() {
+    let limiter = RateLimiter::new()
+        .with_limit(model(), RateLimit::new().with_tokens_per_minute(100))
+        .with_behavior(LimitBehavior::FailFast);
+
+    let permit = limiter.acquire(&model(), "us-central1", 10).await.unwrap();
+    permit.reconcile(100);
+    assert!(limiter.acquire(&model(), "us-central1", 1).await.is_err());
+}
+
+#[tokio::test]
+async fn zero_limits_reject_calls_instead_of_waiting() {
+    for limit in [
+        RateLimit::new().with_requests_per_minute(0),
+        RateLimit::new().with_tokens_per_minute(0),
+    ] {
+        // waiting would never end, so even a waiting limiter rejects the call
+        let limiter = RateLimiter::new().with_limit(model(), limit);
+        let error = limiter
+            .acquire(&model(), "us-central1", 10)
+            .await
+            .err()
+            .unwrap();
+        assert!(error.contains("allows no calls"), "{}", error);
+    }
+}
+
+#[tokio::test]
+async fn concurrency_slots_are_released_with_the_permit() {
+    let limiter = RateLimiter::new()
+        .with_limit(model(), RateLimit::new().with_max_concurrency(1))
+        .with_behavior(LimitBehavior::FailFast);
+
+    let permit = limiter.acquire(&model(), "us-central1", 0).await.unwrap();
+    assert!(limiter.acquire(&model(), "us-central1", 0).await.is_err());
+    drop(permit);
+    limiter.acquire(&model(), "us-central1", 0).await.unwrap();
+}
+
+#[tokio::test]
+async fn limited_calls_are_not_sent() {
+    let server = MockServer::start().await;
+    let limiter = RateLimiter::new()
+        .with_limit(model(), RateLimit::new().with_requests_per_minute(1))
+        .with_behavior(LimitBehavior::FailFast);
+    let client = server
+        .client_builder()
+        .rate_limiter(limiter.clone())
+        .build()
+        .unwrap();
+    // clones share the quota
+    limiter.acquire(&model(), "us-central1", 0).await.unwrap();
+
+    let result = client
+        .gemini()
+        .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await;
+
+    assert!(matches!(result, Err(GeminiError::RateLimited(_))));
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn anthropic_calls_are_limited_apart_from_vertex_regions() {
+    let server = MockServer::start().await;
+    server.reply(claude_reply("Hi"));
+    let limiter = RateLimiter::new()
+        .with_limit(
+            ClaudeModel::Claude3Haiku,
+            RateLimit::new().with_requests_per_minute(1),
+        )
+        .with_behavior(LimitBehavior::FailFast);
+    let client = Client::builder(test_config().with_anthropic_api_key("sk-ant-test"))
+        .base_url(server.url())
+        .rate_limiter(limiter.clone())
+        .build()
+        .unwrap();
+    // the quota of the default Vertex region is used up
+    limiter
+        .acquire(&ClaudeModel::Claude3Haiku.into(), DEFAULT_LOCATION, 0)
+        .await
+        .unwrap();
+
+    let request = || -> RawPredictRequest {
+        serde_json::from_value(json!({
+            "anthropic_version": "vertex-2023-10-16",
+            "max_tokens": 16,
+            "system": "",
+            "stream": false,
+            "messages": [{ "role": "user", "content": "Hello" }]
+        }))
+        .unwrap()
+    };
+    let anthropic = client.claude().with_backend(ClaudeBackend::Anthropic);
+    assert_eq!(anthropic.region(), ANTHROPIC_REGION);
+    anthropic
+        .raw_predict(ClaudeModel::Claude3Haiku, request())
+        .await
+        .unwrap();
+
+    let result = client
+        .claude()
+        .raw_predict(ClaudeModel::Claude3Haiku, request())
+        .await;
+    assert!(matches!(result, Err(ClaudeError::RateLimitError(_))));
+    assert_eq!(server.requests().len(), 1);
+}
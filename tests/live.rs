@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use async_google_gemini::{
+    error::GeminiError,
+    testing::MockLiveServer,
+    types::{
+        content::{Content, RequestOptions, ResponseModality, SpeechConfig},
+        gemini::GeminiModel,
+        live::{LiveClientMessage, LiveGenerationConfig, LiveServerMessage, LiveSetup},
+    },
+};
+use serde_json::json;
+
+fn model() -> GeminiModel {
+    GeminiModel::Custom("gemini-2.0-flash-live-preview-04-09".to_string())
+}
+
+fn setup() -> LiveSetup {
+    LiveSetup::builder()
+        .generation_config(
+            LiveGenerationConfig::builder()
+                .response_modalities(vec![ResponseModality::Text])
+                .speech_config(SpeechConfig::voice("Puck"))
+                .build()
+                .unwrap(),
+        )
+        .system_instruction(Content {
+            parts: vec!["Be brief".into()],
+            role: String::new(),
+        })
+        .build()
+        .unwrap()
+}
+
+fn server_message(value: serde_json::Value) -> LiveServerMessage {
+    serde_json::from_value(value).unwrap()
+}
+
+#[tokio::test]
+async fn tool_call_round_trip() {
+    let server = MockLiveServer::start().await;
+    server.reply([server_message(json!({
+        "toolCall": { "functionCalls": [{ "id": "call_1", "name": "weather", "args": { "city": "Oslo" } }] }
+    }))]);
+    server.reply([
+        server_message(json!({ "serverContent": { "modelTurn": { "parts": [{ "text": "It is 3 degrees" }] } } })),
+        server_message(json!({ "serverContent": { "turnComplete": true } })),
+    ]);
+
+    let client = server.client();
+    let mut session = client.gemini().live(model(), setup()).await.unwrap();
+
+    session
+        .sender()
+        .send_text("Weather in Oslo?")
+        .await
+        .unwrap();
+    let message = session.next().await.unwrap().unwrap();
+    let call = message.tool_call.unwrap().function_calls.remove(0);
+    assert_eq!(call.name, "weather");
+    assert_eq!(call.args, json!({ "city": "Oslo" }));
+
+    session
+        .sender()
+        .send_tool_response(vec![call.response(json!({ "temperature": 3 }))])
+        .await
+        .unwrap();
+    let message = session.next().await.unwrap().unwrap();
+    assert_eq!(message.text().as_deref(), Some("It is 3 degrees"));
+    let message = session.next().await.unwrap().unwrap();
+    assert!(message.server_content.unwrap().turn_complete);
+    session.close().await.unwrap();
+
+    let messages = server.messages();
+    assert_eq!(messages.len(), 3);
+    let LiveClientMessage::Setup(sent) = &messages[0] else {
+        panic!("expected the setup first, got {:?}", messages[0]);
+    };
+    assert_eq!(
+        sent.model,
+        "projects/test-project/locations/us-central1/publishers/google/models/gemini-2.0-flash-live-preview-04-09"
+    );
+    let config = serde_json::to_value(&sent.generation_config).unwrap();
+    assert_eq!(config["responseModalities"], json!(["TEXT"]));
+    assert_eq!(
+        config["speechConfig"]["voiceConfig"]["prebuiltVoiceConfig"]["voiceName"],
+        "Puck"
+    );
+    assert!(matches!(
+        &messages[1],
+        LiveClientMessage::ClientContent(content) if content.turn_complete
+    ));
+    let LiveClientMessage::ToolResponse(response) = &messages[2] else {
+        panic!("expected a tool response, got {:?}", messages[2]);
+    };
+    assert_eq!(response.function_responses[0].id.as_deref(), Some("call_1"));
+    assert_eq!(
+        response.function_responses[0].response,
+        json!({ "temperature": 3 })
+    );
+
+    let handshake = &server.handshakes()[0];
+    assert!(handshake
+        .path
+        .ends_with("/ws/google.cloud.aiplatform.v1beta1.LlmBidiService/BidiGenerateContent"));
+    assert!(handshake.header("authorization").is_some());
+}
+
+#[tokio::test]
+async fn rejected_setup_is_an_error() {
+    let server = MockLiveServer::start().await;
+    server.fail_setup("unknown model");
+
+    let result = server.client().gemini().live(model(), setup()).await;
+
+    match result {
+        Err(GeminiError::Live(message)) => assert!(message.contains("unknown model")),
+        other => panic!("expected a live error, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn invalid_headers_are_an_error() {
+    let server = MockLiveServer::start().await;
+    let options = RequestOptions {
+        custom_headers: Some(HashMap::from([(
+            "x-tenant".to_string(),
+            "line\nbreak".to_string(),
+        )])),
+        ..Default::default()
+    };
+
+    let result = server
+        .client()
+        .gemini()
+        .with_options(options)
+        .live(model(), setup())
+        .await;
+
+    assert!(matches!(result, Err(GeminiError::Live(_))));
+    assert!(server.handshakes().is_empty());
+}
@@ -0,0 +1,234 @@
+use async_google_gemini::{
+    error::MediaError,
+    testing::{MockReply, MockServer},
+    types::{
+        content::{
+            BaseModelParams, Content, GenerateContentRequest, GenerateContentResponse,
+            GenerationConfig, Part, ResponseModality, SpeechConfig,
+        },
+        gemini::GeminiModel,
+        media::{mime_type_from_extension, sniff_mime_type, MAX_INLINE_DATA_BYTES},
+    },
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+fn ftyp(brand: &[u8; 4]) -> Vec<u8> {
+    [b"\0\0\0\x20ftyp".as_slice(), brand, b"\0\0\0\0"].concat()
+}
+
+#[test]
+fn sniffs_common_media_types() {
+    let cases: [(&[u8], Option<&str>); 14] = [
+        (b"\x89PNG\r\n\x1a\n....", Some("image/png")),
+        (b"\xff\xd8\xff\xe0", Some("image/jpeg")),
+        (b"GIF89a", Some("image/gif")),
+        (b"RIFF\0\0\0\0WEBPVP8 ", Some("image/webp")),
+        (b"RIFF\0\0\0\0WAVEfmt ", Some("audio/wav")),
+        (b"%PDF-1.7", Some("application/pdf")),
+        (b"ID3\x04", Some("audio/mpeg")),
+        (b"\xff\xfb\x90\x00", Some("audio/mpeg")),
+        (b"\xff\xf1\x50\x80", Some("audio/aac")),
+        (b"OggS\0", Some("audio/ogg")),
+        (b"\x1a\x45\xdf\xa3", Some("video/webm")),
+        (b"plain text", None),
+        (b"", None),
+        (b"\0\0\0", None),
+    ];
+    for (data, expected) in cases {
+        assert_eq!(sniff_mime_type(data), expected, "{:?}", data);
+    }
+}
+
+#[test]
+fn sniffs_iso_media_by_brand() {
+    assert_eq!(sniff_mime_type(&ftyp(b"isom")), Some("video/mp4"));
+    assert_eq!(sniff_mime_type(&ftyp(b"mp42")), Some("video/mp4"));
+    assert_eq!(sniff_mime_type(&ftyp(b"qt  ")), Some("video/quicktime"));
+    assert_eq!(sniff_mime_type(&ftyp(b"3gp5")), Some("video/3gpp"));
+    assert_eq!(sniff_mime_type(&ftyp(b"M4A ")), Some("audio/mp4"));
+    assert_eq!(sniff_mime_type(&ftyp(b"heic")), Some("image/heic"));
+    assert_eq!(sniff_mime_type(&ftyp(b"avif")), None);
+    assert_eq!(sniff_mime_type(&ftyp(b"avis")), None);
+}
+
+#[test]
+fn guesses_mime_types_from_extensions() {
+    assert_eq!(
+        mime_type_from_extension("clip.MOV"),
+        Some("video/quicktime")
+    );
+    assert_eq!(mime_type_from_extension("song.mp3"), Some("audio/mpeg"));
+    assert_eq!(
+        mime_type_from_extension("https://example.com/a.png?sig=x.y"),
+        Some("image/png")
+    );
+    assert_eq!(mime_type_from_extension("notes"), None);
+    assert_eq!(mime_type_from_extension("data.json"), None);
+}
+
+#[test]
+fn inline_data_is_limited_by_its_encoded_size() {
+    let part = Part::from_bytes(b"%PDF-1.7 tiny").unwrap();
+    assert_eq!(part.mime_type(), Some("application/pdf"));
+
+    let limit = MAX_INLINE_DATA_BYTES / 4 * 3;
+    assert!(Part::inline_data("audio/wav", &vec![0; limit]).is_ok());
+    assert!(matches!(
+        Part::inline_data("audio/wav", &vec![0; limit + 1]),
+        Err(MediaError::TooLarge { .. })
+    ));
+    assert!(matches!(
+        Part::audio(b"\x89PNG\r\n\x1a\n"),
+        Err(MediaError::UnexpectedMimeType {
+            expected: "audio",
+            ..
+        })
+    ));
+}
+
+fn audio_part(mime_type: &str, data: &str) -> GenerateContentResponse {
+    serde_json::from_value(json!({
+        "candidates": [{
+            "index": 0,
+            "content": {
+                "role": "model",
+                "parts": [{ "inlineData": { "mimeType": mime_type, "data": data } }]
+            }
+        }]
+    }))
+    .unwrap()
+}
+
+fn audio_chunk(data: &str) -> GenerateContentResponse {
+    audio_part("audio/L16;codec=pcm;rate=24000", data)
+}
+
+fn request() -> GenerateContentRequest {
+    GenerateContentRequest {
+        contents: vec![Content {
+            parts: vec!["Say hello".into()],
+            role: "user".to_string(),
+        }],
+        base_model_params: BaseModelParams {
+            generation_config: Some(GenerationConfig {
+                response_modalities: Some(vec![ResponseModality::Audio]),
+                speech_config: Some(SpeechConfig::voice("Aoede")),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        cached_content: None,
+    }
+}
+
+#[test]
+fn images_are_decoded_per_candidate() {
+    let response: GenerateContentResponse = serde_json::from_value(json!({
+        "candidates": [
+            { "index": 0, "content": { "role": "model", "parts": [
+                { "text": "Here you go" },
+                { "inlineData": { "mimeType": "image/png", "data": "iVBORw==" } },
+                { "inlineData": { "mimeType": "image/png", "data": "not base64!" } }
+            ] } },
+            { "index": 1, "content": { "role": "model", "parts": [
+                { "inlineData": { "mimeType": "image/jpeg", "data": "/9j/" } }
+            ] } }
+        ]
+    }))
+    .unwrap();
+
+    let images = response.images();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].data, b"\x89PNG");
+
+    let candidates = response.candidates.as_ref().unwrap();
+    assert_eq!(candidates[1].images()[0].mime_type, "image/jpeg");
+    assert_eq!(candidates[1].images()[0].data, b"\xff\xd8\xff");
+    assert!(response.audio().is_none());
+}
+
+#[test]
+fn audio_of_merged_chunks_is_concatenated() {
+    let mut response = GenerateContentResponse::default();
+    for chunk in [audio_chunk("AAEC"), audio_chunk("AwQF")] {
+        response.merge(chunk);
+    }
+
+    let audio = response.audio().unwrap();
+    assert_eq!(audio.data, [0, 1, 2, 3, 4, 5]);
+    assert_eq!(audio.mime_type, "audio/L16;codec=pcm;rate=24000");
+    assert_eq!(audio.sample_rate(), Some(24000));
+    assert!(audio.is_pcm());
+}
+
+#[test]
+fn only_raw_pcm_of_the_same_format_is_concatenated() {
+    let mut response = GenerateContentResponse::default();
+    for chunk in [
+        audio_part("audio/wav", "AAEC"),
+        audio_part("audio/wav", "AwQF"),
+    ] {
+        response.merge(chunk);
+    }
+    let audio = response.audio().unwrap();
+    assert_eq!(audio.data, [0, 1, 2]);
+    assert!(!audio.is_pcm());
+
+    let mut response = GenerateContentResponse::default();
+    for chunk in [
+        audio_part("audio/pcm;rate=24000", "AAEC"),
+        audio_part("audio/pcm;rate=24000", "AwQF"),
+        audio_part("audio/pcm;rate=16000", "BgcI"),
+    ] {
+        response.merge(chunk);
+    }
+    assert_eq!(response.audio().unwrap().data, [0, 1, 2, 3, 4, 5]);
+}
+
+#[tokio::test]
+async fn files_over_the_limit_are_rejected_before_they_are_read() {
+    let path = std::env::temp_dir().join(format!("large-media-{}.wav", std::process::id()));
+    // a sparse file, which takes no disk space
+    let file = std::fs::File::create(&path).unwrap();
+    file.set_len(MAX_INLINE_DATA_BYTES as u64).unwrap();
+
+    let result = Part::from_path(&path).await;
+    std::fs::remove_file(&path).ok();
+    assert!(matches!(result, Err(MediaError::TooLarge { .. })));
+}
+
+#[tokio::test]
+async fn streamed_audio_is_decoded_per_chunk() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::stream_generate_content(&[
+        audio_chunk("AAEC"),
+        audio_chunk("AwQF"),
+    ]));
+
+    let mut stream = server
+        .client()
+        .gemini()
+        .stream_generate_content(GeminiModel::Gemini20Flash001, request())
+        .await
+        .unwrap();
+    let mut response = GenerateContentResponse::default();
+    let mut chunks = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.unwrap();
+        chunks.push(chunk.audio().unwrap().data);
+        response.merge(chunk);
+    }
+
+    assert_eq!(chunks, [vec![0, 1, 2], vec![3, 4, 5]]);
+    assert_eq!(response.audio().unwrap().data, [0, 1, 2, 3, 4, 5]);
+
+    let body: Value = server.requests()[0].json().unwrap();
+    assert_eq!(
+        body["generationConfig"],
+        json!({
+            "responseModalities": ["AUDIO"],
+            "speechConfig": { "voiceConfig": { "prebuiltVoiceConfig": { "voiceName": "Aoede" } } }
+        })
+    );
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use async_google_gemini::{
+    middleware::{HeaderMiddleware, Middleware, RequestContext},
+    testing::{MockReply, MockServer},
+    types::claude::{ClaudeModel, RawPredictRequest},
+};
+use common::{
+    claude_reply, gemini_candidate, gemini_request, gemini_text, CapturedTracing, GEMINI_MODEL,
+};
+use futures::StreamExt;
+use reqwest::{header::HeaderMap, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+
+/// Records every hook call as a line, e.g. `response generateContent 200`.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Recorder {
+    fn calls(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Middleware for Recorder {
+    fn on_request(&self, ctx: &RequestContext, request: RequestBuilder) -> RequestBuilder {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("request {} {}", ctx.method, ctx.stream));
+        request.header("x-recorded", "yes")
+    }
+
+    fn on_response(
+        &self,
+        ctx: &RequestContext,
+        status: StatusCode,
+        _headers: &HeaderMap,
+        body: Option<&str>,
+    ) {
+        self.0.lock().unwrap().push(format!(
+            "response {} {} {}",
+            ctx.method,
+            status.as_u16(),
+            body.is_some()
+        ));
+    }
+
+    fn on_event(&self, ctx: &RequestContext, data: &str) {
+        let event: Value = serde_json::from_str(data).unwrap();
+        let text = event["candidates"][0]["content"]["parts"][0]["text"].clone();
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("event {} {}", ctx.method, text));
+    }
+}
+
+fn claude_request() -> RawPredictRequest {
+    serde_json::from_value(json!({
+        "anthropic_version": "vertex-2023-10-16",
+        "max_tokens": 16,
+        "system": "",
+        "stream": false,
+        "messages": [{ "role": "user", "content": "Hello" }]
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn requests_and_responses_pass_through_the_middleware() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("Hi")));
+    server.reply(claude_reply("Hi"));
+
+    let recorder = Recorder::default();
+    let client = server.client().with_middleware(recorder.clone());
+    client
+        .gemini()
+        .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .unwrap();
+    client
+        .claude()
+        .raw_predict(ClaudeModel::Claude3Haiku, claude_request())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        recorder.calls(),
+        vec![
+            "request generateContent false",
+            "response generateContent 200 true",
+            "request streamRawPredict false",
+            "response streamRawPredict 200 true",
+        ]
+    );
+    for request in server.requests() {
+        assert_eq!(request.header("x-recorded"), Some("yes"));
+    }
+}
+
+#[tokio::test]
+async fn each_stream_event_is_passed_to_the_middleware_once() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::stream_generate_content(&[
+        gemini_candidate(json!({
+            "index": 0,
+            "content": { "role": "model", "parts": [{ "text": "Bon" }] }
+        })),
+        gemini_text("jour"),
+    ]));
+
+    let recorder = Recorder::default();
+    let client = server.client().with_middleware(recorder.clone());
+    let mut stream = client
+        .gemini()
+        .stream_generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .unwrap();
+    while let Some(chunk) = stream.next().await {
+        chunk.unwrap();
+    }
+
+    assert_eq!(
+        recorder.calls(),
+        vec![
+            "request streamGenerateContent true",
+            "response streamGenerateContent 200 false",
+            "event streamGenerateContent \"Bon\"",
+            "event streamGenerateContent \"jour\"",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn header_middleware_adds_static_and_tag_headers() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("Hi")));
+
+    let client = server.client().with_middleware(
+        HeaderMiddleware::new()
+            .with_header("x-team", "search")
+            .with_user_project("billing-project")
+            .with_tag_headers("x-tag-"),
+    );
+    client
+        .gemini()
+        .with_tag("tenant", "acme")
+        .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.header("x-team"), Some("search"));
+    assert_eq!(
+        request.header("x-goog-user-project"),
+        Some("billing-project")
+    );
+    assert_eq!(request.header("x-tag-tenant"), Some("acme"));
+}
+
+struct Redact;
+
+impl Middleware for Redact {
+    fn redact_prompt(&self, prompt: &mut Value) {
+        prompt["contents"][0]["parts"][0]["text"] = json!("[redacted]");
+    }
+}
+
+#[tokio::test]
+async fn prompts_are_redacted_before_they_are_logged() {
+    let captured = CapturedTracing::default();
+    let _guard = tracing::subscriber::set_default(captured.clone());
+
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("Hi")));
+    let client = server
+        .client_builder()
+        .capture_content(true)
+        .middleware(Redact)
+        .build()
+        .unwrap();
+    client
+        .gemini()
+        .generate_content(GEMINI_MODEL, gemini_request("my secret"))
+        .await
+        .unwrap();
+
+    let prompts = captured.events_with("gen_ai.prompt");
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0]["gen_ai.prompt"].contains("[redacted]"));
+    assert!(!prompts[0]["gen_ai.prompt"].contains("my secret"));
+    // only the logged copy is redacted
+    assert!(server.requests()[0].body.contains("my secret"));
+}
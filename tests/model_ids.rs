@@ -0,0 +1,85 @@
+mod common;
+
+use async_google_gemini::{
+    testing::{MockReply, MockServer},
+    types::{claude::ClaudeModel, gemini::GeminiModel, imagen::ImagenModel},
+};
+use common::{gemini_request, gemini_text};
+
+#[test]
+fn custom_models_convert_into_the_same_static_str() {
+    let gemini = GeminiModel::Custom("gemini-exp-1206".to_string());
+    let claude = ClaudeModel::Custom("claude-sonnet-4@20250514".to_string());
+    let imagen = ImagenModel::Custom("imagen-4.0-generate-preview".to_string());
+
+    assert_eq!(<&'static str>::from(&gemini), "custom");
+    assert_eq!(<&'static str>::from(&claude), "custom");
+    assert_eq!(<&'static str>::from(&imagen), "custom");
+    assert_eq!(gemini.to_string(), "gemini-exp-1206");
+    assert_eq!(claude.to_string(), "claude-sonnet-4@20250514");
+    assert_eq!(imagen.to_string(), "imagen-4.0-generate-preview");
+}
+
+#[test]
+fn custom_models_round_trip_through_serde_and_strum() {
+    let gemini = GeminiModel::Custom("gemini-exp-1206".to_string());
+    let json = serde_json::to_string(&gemini).unwrap();
+    assert_eq!(json, "\"gemini-exp-1206\"");
+    assert_eq!(serde_json::from_str::<GeminiModel>(&json).unwrap(), gemini);
+    assert_eq!(gemini.to_string().parse::<GeminiModel>().unwrap(), gemini);
+
+    let claude = ClaudeModel::Custom("claude-sonnet-4@20250514".to_string());
+    let json = serde_json::to_string(&claude).unwrap();
+    assert_eq!(json, "\"claude-sonnet-4@20250514\"");
+    assert_eq!(serde_json::from_str::<ClaudeModel>(&json).unwrap(), claude);
+    assert_eq!(claude.to_string().parse::<ClaudeModel>().unwrap(), claude);
+
+    let imagen = ImagenModel::Custom("imagen-4.0-generate-preview".to_string());
+    let json = serde_json::to_string(&imagen).unwrap();
+    assert_eq!(json, "\"imagen-4.0-generate-preview\"");
+    assert_eq!(serde_json::from_str::<ImagenModel>(&json).unwrap(), imagen);
+    assert_eq!(imagen.to_string().parse::<ImagenModel>().unwrap(), imagen);
+
+    // known ids still parse into their variant
+    assert_eq!(
+        serde_json::from_str::<GeminiModel>("\"gemini-1.5-pro-002\"").unwrap(),
+        GeminiModel::Gemini15Pro002
+    );
+    assert_eq!(
+        "claude-3-haiku@20240307".parse::<ClaudeModel>().unwrap(),
+        ClaudeModel::Claude3Haiku
+    );
+}
+
+#[test]
+fn tuned_endpoints_expose_their_resource_name_and_location() {
+    let tuned = GeminiModel::endpoint("tuning-project", "europe-west4", "1234");
+    assert_eq!(
+        tuned.resource_name(),
+        Some("projects/tuning-project/locations/europe-west4/endpoints/1234")
+    );
+    assert_eq!(tuned.resource_location(), Some("europe-west4"));
+
+    let custom = GeminiModel::Custom("gemini-exp-1206".to_string());
+    assert_eq!(custom.resource_name(), None);
+    assert_eq!(custom.resource_location(), None);
+    assert_eq!(GeminiModel::Gemini20Flash001.resource_name(), None);
+}
+
+#[tokio::test]
+async fn tuned_endpoints_are_called_by_their_resource_name() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("Bonjour")));
+
+    let tuned = GeminiModel::endpoint("tuning-project", "europe-west4", "1234");
+    server
+        .client()
+        .gemini()
+        .generate_content(tuned, gemini_request("Hello"))
+        .await
+        .unwrap();
+
+    assert!(server.requests()[0].path.ends_with(
+        "/v1/projects/tuning-project/locations/europe-west4/endpoints/1234:generateContent"
+    ));
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use async_google_gemini::{
+    cassette::{Cassette, CassetteMatcher},
+    middleware::{Middleware, RequestContext},
+    testing::{MockReply, MockServer},
+    types::gemini::GeminiModel,
+};
+use common::cassette_path;
+use serde_json::json;
+
+#[derive(Clone, Default)]
+struct Methods(Arc<Mutex<Vec<String>>>);
+
+impl Middleware for Methods {
+    fn on_request(
+        &self,
+        ctx: &RequestContext,
+        request: reqwest::RequestBuilder,
+    ) -> reqwest::RequestBuilder {
+        self.0.lock().unwrap().push(ctx.method.clone());
+        request
+    }
+}
+
+fn models_page() -> MockReply {
+    MockReply::json(&json!({
+        "publisherModels": [{ "name": "publishers/google/models/gemini-1.5-pro-002", "versionId": "002" }],
+        "nextPageToken": "next"
+    }))
+}
+
+#[tokio::test]
+async fn list_models_goes_through_middleware_and_encodes_the_page_token() {
+    let server = MockServer::start().await;
+    server.reply(models_page());
+
+    let methods = Methods::default();
+    let client = server.client().with_middleware(methods.clone());
+    let page = client
+        .gemini()
+        .list_models(Some(10), Some("a b&c"))
+        .await
+        .unwrap();
+
+    assert_eq!(page.models[0].model(), GeminiModel::Gemini15Pro002);
+    assert_eq!(page.next_page_token.as_deref(), Some("next"));
+    assert!(server.requests()[0]
+        .path
+        .ends_with("/v1beta1/publishers/google/models?pageSize=10&pageToken=a+b%26c"));
+    assert_eq!(*methods.0.lock().unwrap(), vec!["models.list"]);
+}
+
+#[tokio::test]
+async fn list_models_is_replayed_from_a_cassette() {
+    let path = cassette_path("models");
+
+    let server = MockServer::start().await;
+    server.reply(models_page());
+    let client = server
+        .client_builder()
+        .cassette(Cassette::record(&path))
+        .build()
+        .unwrap();
+    client.gemini().list_models(None, None).await.unwrap();
+    client.cassette().unwrap().save().await.unwrap();
+
+    let server = MockServer::start().await;
+    let client = server
+        .client_builder()
+        .cassette(
+            Cassette::replay(&path)
+                .unwrap()
+                .with_matcher(CassetteMatcher::new().ignore_url()),
+        )
+        .build()
+        .unwrap();
+    let page = client.gemini().list_models(None, None).await.unwrap();
+
+    assert_eq!(page.models.len(), 1);
+    assert!(server.requests().is_empty());
+
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,170 @@
+use async_google_gemini::{
+    error::ChatError,
+    proxy::{openai::chat_request, Proxy},
+    testing::{MockReply, MockServer},
+    types::{
+        chat::{ChatPart, ChatRole},
+        content::{
+            Content, FinishReason, FunctionCall, FunctionCallPart, GenerateContentCandidate,
+            GenerateContentResponse, Part, UsageMetadata,
+        },
+    },
+};
+use serde_json::{json, Value};
+
+async fn start_proxy(server: &MockServer) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(Proxy::new(server.client()).serve(listener));
+    format!("http://{}/v1/chat/completions", addr)
+}
+
+fn parallel_tool_call_request() -> Value {
+    json!({
+        "model": "gemini-2.0-flash-001",
+        "messages": [
+            { "role": "system", "content": "Be brief" },
+            { "role": "user", "content": "Weather in Paris and Lyon?" },
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [
+                    { "id": "call_1", "type": "function", "function": { "name": "weather", "arguments": "{\"city\":\"Paris\"}" } },
+                    { "id": "call_2", "type": "function", "function": { "name": "weather", "arguments": "{\"city\":\"Lyon\"}" } }
+                ]
+            },
+            { "role": "tool", "tool_call_id": "call_1", "content": "sunny" },
+            { "role": "tool", "tool_call_id": "call_2", "content": "{\"rain\":true}" }
+        ]
+    })
+}
+
+#[test]
+fn tool_messages_are_merged_and_named_after_their_call() {
+    let request = serde_json::from_value(parallel_tool_call_request()).unwrap();
+    let request = chat_request(request).unwrap();
+
+    assert_eq!(request.system.as_deref(), Some("Be brief"));
+    assert_eq!(request.messages.len(), 3);
+
+    let results = &request.messages[2];
+    assert_eq!(results.role, ChatRole::User);
+    let results = results
+        .parts
+        .iter()
+        .map(|part| match part {
+            ChatPart::ToolResult {
+                id, name, content, ..
+            } => (id.clone().unwrap(), name.clone(), content.clone()),
+            other => panic!("expected a tool result, got {:?}", other),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        vec![
+            ("call_1".to_string(), "weather".to_string(), json!("sunny")),
+            (
+                "call_2".to_string(),
+                "weather".to_string(),
+                json!({ "rain": true })
+            ),
+        ]
+    );
+}
+
+#[test]
+fn unknown_tool_call_id_is_rejected() {
+    let request = serde_json::from_value(json!({
+        "model": "gemini-2.0-flash-001",
+        "messages": [
+            { "role": "user", "content": "Hi" },
+            { "role": "tool", "tool_call_id": "call_9", "content": "sunny" }
+        ]
+    }))
+    .unwrap();
+
+    match chat_request(request) {
+        Err(ChatError::Conversion(message)) => assert!(message.contains("call_9")),
+        other => panic!("expected a conversion error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn completion_is_translated_from_gemini() {
+    let server = MockServer::start().await;
+    let url = start_proxy(&server).await;
+
+    server.reply(MockReply::generate_content(&GenerateContentResponse {
+        candidates: Some(vec![GenerateContentCandidate {
+            content: Some(Content {
+                parts: vec![Part::FunctionCallPart(FunctionCallPart {
+                    function_call: FunctionCall {
+                        name: "weather".to_string(),
+                        args: json!({ "city": "Nice" }),
+                    },
+                })],
+                role: "model".to_string(),
+            }),
+            index: Some(0),
+            finish_reason: Some(FinishReason::Stop),
+            ..Default::default()
+        }]),
+        usage_metadata: Some(UsageMetadata {
+            prompt_token_count: Some(12),
+            candidates_token_count: Some(3),
+            total_token_count: Some(15),
+            cached_content_token_count: None,
+        }),
+        ..Default::default()
+    }));
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&parallel_tool_call_request())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let completion: Value = response.json().await.unwrap();
+
+    assert_eq!(completion["object"], "chat.completion");
+    assert_eq!(completion["model"], "gemini-2.0-flash-001");
+    let call = &completion["choices"][0]["message"]["tool_calls"][0];
+    assert_eq!(call["function"]["name"], "weather");
+    let arguments: Value =
+        serde_json::from_str(call["function"]["arguments"].as_str().unwrap()).unwrap();
+    assert_eq!(arguments, json!({ "city": "Nice" }));
+    assert_eq!(completion["choices"][0]["finish_reason"], "tool_calls");
+    assert_eq!(completion["usage"]["total_tokens"], 15);
+
+    let upstream: Value = server.requests()[0].json().unwrap();
+    let responses = &upstream["contents"][2]["parts"];
+    assert_eq!(responses[0]["functionResponse"]["name"], "weather");
+    assert_eq!(
+        responses[0]["functionResponse"]["response"],
+        json!({ "__tool_result": "sunny" })
+    );
+    assert_eq!(
+        responses[1]["functionResponse"]["response"],
+        json!({ "rain": true })
+    );
+}
+
+#[tokio::test]
+async fn unknown_tool_call_id_is_a_bad_request() {
+    let server = MockServer::start().await;
+    let url = start_proxy(&server).await;
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&json!({
+            "model": "gemini-2.0-flash-001",
+            "messages": [{ "role": "tool", "tool_call_id": "call_9", "content": "sunny" }]
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 400);
+    assert!(server.requests().is_empty());
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::time::Duration;
+
+use async_google_gemini::{
+    error::GeminiError,
+    testing::{MockReply, MockServer},
+    types::content::{GenerateContentResponse, RequestOptions},
+};
+use common::{gemini_candidate, gemini_request, gemini_text, GEMINI_MODEL};
+use futures::StreamExt;
+use serde_json::json;
+
+fn options(timeout: u64, stream_idle_timeout: Option<u64>) -> RequestOptions {
+    RequestOptions {
+        timeout: Some(timeout),
+        stream_idle_timeout,
+        ..Default::default()
+    }
+}
+
+fn chunks() -> Vec<GenerateContentResponse> {
+    ["Un", "deux", "trois"]
+        .into_iter()
+        .map(|text| {
+            gemini_candidate(json!({
+                "index": 0,
+                "content": { "role": "model", "parts": [{ "text": text }] }
+            }))
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn the_http_client_of_the_builder_is_used() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("Hi")));
+
+    let http_client = reqwest::Client::builder()
+        .user_agent("custom-agent/1.0")
+        .build()
+        .unwrap();
+    let client = server
+        .client_builder()
+        .http_client(http_client)
+        .build()
+        .unwrap();
+    client
+        .gemini()
+        .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        server.requests()[0].header("user-agent"),
+        Some("custom-agent/1.0")
+    );
+}
+
+#[tokio::test]
+async fn the_timeout_covers_the_response_of_unary_calls() {
+    let server = MockServer::start().await;
+    server.reply(
+        MockReply::generate_content(&gemini_text("Hi")).with_delay(Duration::from_millis(500)),
+    );
+
+    let error = server
+        .client()
+        .gemini()
+        .with_options(options(100, None))
+        .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, GeminiError::DeadlineExceeded),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn the_timeout_of_streams_only_covers_the_response_headers() {
+    let server = MockServer::start().await;
+    server.reply(
+        MockReply::stream_generate_content(&chunks()).with_event_delay(Duration::from_millis(100)),
+    );
+    server.reply(
+        MockReply::stream_generate_content(&chunks()).with_delay(Duration::from_millis(500)),
+    );
+    let gemini = server.client().gemini().with_options(options(200, None));
+
+    // the whole stream takes longer than the timeout, but its headers arrive in time
+    let mut stream = gemini
+        .stream_generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .unwrap();
+    let mut chunks = 0;
+    while let Some(chunk) = stream.next().await {
+        chunk.unwrap();
+        chunks += 1;
+    }
+    assert_eq!(chunks, 3);
+
+    let error = gemini
+        .stream_generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        matches!(error, GeminiError::DeadlineExceeded),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn the_stream_idle_timeout_bounds_the_wait_for_each_chunk() {
+    let server = MockServer::start().await;
+    server.reply(
+        MockReply::stream_generate_content(&chunks()).with_event_delay(Duration::from_millis(300)),
+    );
+
+    let mut stream = server
+        .client()
+        .gemini()
+        .with_options(options(10_000, Some(100)))
+        .stream_generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .unwrap();
+
+    let error = stream.next().await.unwrap().unwrap_err();
+    assert!(
+        matches!(error, GeminiError::DeadlineExceeded),
+        "{:?}",
+        error
+    );
+}
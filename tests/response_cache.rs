@@ -0,0 +1,207 @@
+mod common;
+
+use std::time::Duration;
+
+use async_google_gemini::{
+    client::Client,
+    config::ClaudeBackend,
+    response_cache::{CacheMode, CacheStats, ResponseCache},
+    testing::{test_config, MockReply, MockServer},
+    types::{
+        claude::{ClaudeModel, RawPredictRequest},
+        content::{GenerateContentRequest, GenerateContentResponse, RequestOptions},
+    },
+};
+use common::{claude_reply, gemini_request, gemini_text, GEMINI_MODEL};
+use futures::StreamExt;
+use serde_json::json;
+
+fn request() -> GenerateContentRequest {
+    gemini_request("Hello")
+}
+
+fn key(location: &str, model: &str, request: serde_json::Value) -> String {
+    ResponseCache::key("vertex", "test-project", location, &model, &request)
+}
+
+#[test]
+fn entries_expire_after_the_ttl() {
+    let cache = ResponseCache::memory(10).with_ttl(Duration::from_millis(50));
+    cache.insert("key", &json!({ "text": "cached" }));
+    assert_eq!(
+        cache.get::<serde_json::Value>("key"),
+        Some(json!({ "text": "cached" }))
+    );
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(cache.get::<serde_json::Value>("key"), None);
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            hits: 1,
+            misses: 1,
+            expired: 1,
+            writes: 1,
+        }
+    );
+
+    // the expired entry was removed, so it is not counted as expired again
+    assert_eq!(cache.get::<serde_json::Value>("key"), None);
+    assert_eq!(cache.stats().expired, 1);
+}
+
+#[test]
+fn least_recently_used_entries_are_evicted() {
+    let cache = ResponseCache::memory(2);
+    cache.insert("a", &1);
+    cache.insert("b", &2);
+    assert_eq!(cache.get::<u32>("a"), Some(1));
+    cache.insert("c", &3);
+
+    assert_eq!(cache.get::<u32>("a"), Some(1));
+    assert_eq!(cache.get::<u32>("b"), None);
+    assert_eq!(cache.get::<u32>("c"), Some(3));
+}
+
+#[test]
+fn keys_ignore_field_order_and_the_stream_flag() {
+    let a = key(
+        "us-central1",
+        "model",
+        json!({ "a": 1, "b": { "d": 2, "c": 3 } }),
+    );
+    let b = key(
+        "us-central1",
+        "model",
+        json!({ "stream": true, "b": { "c": 3, "d": 2 }, "a": 1 }),
+    );
+    assert_eq!(a, b);
+    assert_ne!(a, key("us-central1", "other", json!({ "a": 1 })));
+}
+
+#[test]
+fn keys_differ_per_backend_project_and_location() {
+    let request = json!({ "a": 1 });
+    let a = key("us-central1", "model", request.clone());
+    assert_ne!(a, key("europe-west4", "model", request.clone()));
+    assert_ne!(
+        a,
+        ResponseCache::key("vertex", "other-project", "us-central1", &"model", &request)
+    );
+    assert_ne!(
+        a,
+        ResponseCache::key(
+            "developer_api",
+            "test-project",
+            "us-central1",
+            &"model",
+            &request
+        )
+    );
+}
+
+#[tokio::test]
+async fn cached_responses_are_served_until_they_expire() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("first")));
+    server.reply(MockReply::generate_content(&gemini_text("second")));
+    server.reply(MockReply::generate_content(&gemini_text("third")));
+
+    let cache = ResponseCache::memory(10).with_ttl(Duration::from_millis(200));
+    let client = server
+        .client_builder()
+        .response_cache(cache.clone())
+        .build()
+        .unwrap();
+    let gemini = client.gemini();
+    let model = GEMINI_MODEL;
+
+    let text = |response: GenerateContentResponse| response.text().unwrap_or_default();
+    let first = gemini.generate_content(model.clone(), request()).await;
+    assert_eq!(text(first.unwrap()), "first");
+    let cached = gemini.generate_content(model.clone(), request()).await;
+    assert_eq!(text(cached.unwrap()), "first");
+
+    // streams replay the cached response
+    let mut stream = gemini
+        .stream_generate_content(model.clone(), request())
+        .await
+        .unwrap();
+    let mut streamed = String::new();
+    while let Some(chunk) = stream.next().await {
+        streamed.push_str(&chunk.unwrap().text().unwrap_or_default());
+    }
+    assert_eq!(streamed, "first");
+
+    let bypassed = gemini
+        .clone()
+        .with_options(RequestOptions {
+            cache_mode: Some(CacheMode::Bypass),
+            ..Default::default()
+        })
+        .generate_content(model.clone(), request())
+        .await;
+    assert_eq!(text(bypassed.unwrap()), "second");
+    assert_eq!(server.requests().len(), 2);
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let refreshed = gemini.generate_content(model.clone(), request()).await;
+    assert_eq!(text(refreshed.unwrap()), "third");
+    assert_eq!(server.requests().len(), 3);
+    assert_eq!(cache.stats().expired, 1);
+}
+
+#[tokio::test]
+async fn calls_to_another_region_or_backend_are_not_served_from_the_cache() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&gemini_text("first")));
+    server.reply(MockReply::generate_content(&gemini_text("second")));
+    server.reply(claude_reply("vertex"));
+    server.reply(claude_reply("anthropic"));
+
+    let cache = ResponseCache::memory(10);
+    let client = Client::builder(test_config().with_anthropic_api_key("sk-ant-test"))
+        .base_url(server.url())
+        .response_cache(cache.clone())
+        .build()
+        .unwrap();
+
+    let gemini = client.gemini();
+    gemini
+        .generate_content(GEMINI_MODEL, request())
+        .await
+        .unwrap();
+    let moved = gemini
+        .clone()
+        .with_location("europe-west4")
+        .generate_content(GEMINI_MODEL, request())
+        .await
+        .unwrap();
+    assert_eq!(moved.text().unwrap_or_default(), "second");
+
+    let claude_request = || -> RawPredictRequest {
+        serde_json::from_value(json!({
+            "anthropic_version": "vertex-2023-10-16",
+            "max_tokens": 16,
+            "system": "",
+            "stream": false,
+            "messages": [{ "role": "user", "content": "Hello" }]
+        }))
+        .unwrap()
+    };
+    let claude = client.claude();
+    claude
+        .raw_predict(ClaudeModel::Claude3Haiku, claude_request())
+        .await
+        .unwrap();
+    let anthropic = claude
+        .clone()
+        .with_backend(ClaudeBackend::Anthropic)
+        .raw_predict(ClaudeModel::Claude3Haiku, claude_request())
+        .await
+        .unwrap();
+    assert_eq!(anthropic.content[0].text, "anthropic");
+
+    assert_eq!(server.requests().len(), 4);
+    assert_eq!(cache.stats().hits, 0);
+}
@@ -0,0 +1,78 @@
+use async_google_gemini::{
+    error::{ChatError, GeminiError},
+    router::RouteTarget,
+    testing::{MockReply, MockServer},
+    types::{
+        chat::{ChatMessage, ChatRequest},
+        claude::ClaudeModel,
+        gemini::GeminiModel,
+    },
+};
+use serde_json::json;
+
+fn claude_reply() -> MockReply {
+    MockReply::json(&json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "content": [{ "type": "text", "text": "Hello from Claude" }],
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": { "input_tokens": 2, "output_tokens": 4 }
+    }))
+}
+
+fn request() -> ChatRequest {
+    ChatRequest {
+        messages: vec![ChatMessage::user("Hello")],
+        ..Default::default()
+    }
+}
+
+fn targets() -> Vec<RouteTarget> {
+    vec![
+        RouteTarget::new(GeminiModel::Gemini15Pro002).with_region("europe-west4"),
+        RouteTarget::new(ClaudeModel::Claude35SonnetV2),
+    ]
+}
+
+#[tokio::test]
+async fn falls_back_on_retryable_errors_and_skips_cooling_targets() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::gemini_error(429, "quota exceeded"));
+    server.reply(claude_reply());
+    server.reply(claude_reply());
+
+    let router = server.client().router(targets());
+    let routed = router.chat(request()).await.unwrap();
+    assert_eq!(routed.response.text(), "Hello from Claude");
+    assert_eq!(routed.target.to_string(), targets()[1].to_string());
+
+    // the gemini target is cooling down, so it is not tried again
+    router.chat(request()).await.unwrap();
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].path.contains("/locations/europe-west4/"));
+    assert!(requests[1].path.contains("/publishers/anthropic/"));
+    assert!(requests[2].path.contains("/publishers/anthropic/"));
+
+    let health = router.health();
+    assert_eq!(health[0].failed, 1);
+    assert!(health[0].cooldown_remaining.is_some());
+    assert_eq!(health[1].served, 2);
+}
+
+#[tokio::test]
+async fn authentication_failures_are_not_retried() {
+    let server = MockServer::start().await;
+    server.reply(MockReply::gemini_error(401, "invalid credentials"));
+
+    let router = server.client().router(targets());
+    let result = router.chat(request()).await;
+
+    assert!(matches!(
+        result,
+        Err(ChatError::Gemini(GeminiError::PermissionDenied))
+    ));
+    assert_eq!(server.requests().len(), 1);
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use async_google_gemini::{
+    testing::{MockReply, MockServer},
+    types::content::GenerateContentResponse,
+};
+use common::{gemini_request, CapturedTracing, GEMINI_MODEL};
+use serde_json::json;
+
+fn reply() -> GenerateContentResponse {
+    serde_json::from_value(json!({
+        "candidates": [{
+            "index": 0,
+            "content": { "role": "model", "parts": [{ "text": "Hi" }] },
+            "finishReason": "STOP"
+        }],
+        "usageMetadata": { "promptTokenCount": 4, "candidatesTokenCount": 2, "totalTokenCount": 6 }
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn calls_run_in_a_span_with_the_gen_ai_attributes() {
+    let captured = CapturedTracing::default();
+    let _guard = tracing::subscriber::set_default(captured.clone());
+
+    let server = MockServer::start().await;
+    server.reply(MockReply::generate_content(&reply()));
+    let mut request = gemini_request("Hello");
+    request.base_model_params.generation_config =
+        Some(serde_json::from_value(json!({ "maxOutputTokens": 64, "temperature": 0.5 })).unwrap());
+    server
+        .client()
+        .gemini()
+        .generate_content(GEMINI_MODEL, request)
+        .await
+        .unwrap();
+
+    let spans = captured.spans("gen_ai");
+    assert_eq!(spans.len(), 1);
+    let span = &spans[0];
+    assert_eq!(span["gen_ai.operation.name"], "chat");
+    assert_eq!(span["gen_ai.system"], "vertex_ai");
+    assert_eq!(span["gen_ai.request.model"], "gemini-1.5-flash-002");
+    assert_eq!(span["gen_ai.request.max_tokens"], "64");
+    assert_eq!(span["gen_ai.request.temperature"], "0.5");
+    assert_eq!(span["gen_ai.response.finish_reasons"], "[\"stop\"]");
+    assert_eq!(span["gen_ai.usage.input_tokens"], "4");
+    assert_eq!(span["gen_ai.usage.output_tokens"], "2");
+    assert!(!span.contains_key("error.type"));
+    // prompts are only logged when content capture is enabled
+    assert!(captured.events_with("gen_ai.prompt").is_empty());
+}
+
+#[tokio::test]
+async fn failed_calls_record_the_error_type() {
+    let captured = CapturedTracing::default();
+    let _guard = tracing::subscriber::set_default(captured.clone());
+
+    let server = MockServer::start().await;
+    server.reply(MockReply::gemini_error(429, "quota exceeded"));
+    server
+        .client()
+        .gemini()
+        .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+        .await
+        .unwrap_err();
+
+    let span = &captured.spans("gen_ai")[0];
+    assert_eq!(span["error.type"], "ResourceExhausted");
+    assert_eq!(span["otel.status_code"], "ERROR");
+    assert!(!span.contains_key("gen_ai.usage.input_tokens"));
+}
+
+#[cfg(feature = "metrics")]
+mod metrics {
+    use std::sync::{Arc, Mutex};
+
+    use async_google_gemini::{telemetry::metric, testing::MockReply};
+    use metrics::{
+        Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString,
+        Unit,
+    };
+
+    use super::{gemini_request, reply, MockServer, GEMINI_MODEL};
+
+    /// A recorded histogram sample, with the metric name and labels.
+    type Sample = (String, Vec<(String, String)>, f64);
+
+    #[derive(Clone, Default)]
+    struct CapturedMetrics(Arc<Mutex<Vec<Sample>>>);
+
+    struct CapturedHistogram {
+        key: Key,
+        samples: CapturedMetrics,
+    }
+
+    impl HistogramFn for CapturedHistogram {
+        fn record(&self, value: f64) {
+            let labels = self
+                .key
+                .labels()
+                .map(|label| (label.key().to_string(), label.value().to_string()))
+                .collect();
+            self.samples
+                .0
+                .lock()
+                .unwrap()
+                .push((self.key.name().to_string(), labels, value));
+        }
+    }
+
+    impl Recorder for CapturedMetrics {
+        fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
+        }
+
+        fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+        fn describe_histogram(
+            &self,
+            _key: KeyName,
+            _unit: Option<Unit>,
+            _description: SharedString,
+        ) {
+        }
+
+        fn register_counter(&self, _key: &Key, _metadata: &Metadata<'_>) -> Counter {
+            panic!("only semantic convention histograms are recorded");
+        }
+
+        fn register_gauge(&self, _key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+            panic!("only semantic convention histograms are recorded");
+        }
+
+        fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(Arc::new(CapturedHistogram {
+                key: key.clone(),
+                samples: self.clone(),
+            }))
+        }
+    }
+
+    fn label<'a>(labels: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        labels
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn calls_record_duration_and_token_usage_histograms() {
+        let captured = CapturedMetrics::default();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        metrics::with_local_recorder(&captured, || {
+            runtime.block_on(async {
+                let server = MockServer::start().await;
+                server.reply(MockReply::generate_content(&reply()));
+                server.reply(MockReply::gemini_error(429, "quota exceeded"));
+                let gemini = server.client().gemini();
+
+                gemini
+                    .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+                    .await
+                    .unwrap();
+                gemini
+                    .generate_content(GEMINI_MODEL, gemini_request("Hello"))
+                    .await
+                    .unwrap_err();
+            })
+        });
+
+        let samples = captured.0.lock().unwrap().clone();
+        assert!(samples.iter().all(|(name, _, _)| {
+            name == metric::OPERATION_DURATION || name == metric::TOKEN_USAGE
+        }));
+
+        let durations: Vec<_> = samples
+            .iter()
+            .filter(|(name, _, _)| name == metric::OPERATION_DURATION)
+            .collect();
+        assert_eq!(durations.len(), 2);
+        assert_eq!(label(&durations[0].1, "gen_ai.system"), Some("vertex_ai"));
+        assert_eq!(label(&durations[0].1, "error.type"), None);
+        assert_eq!(
+            label(&durations[1].1, "error.type"),
+            Some("ResourceExhausted")
+        );
+
+        let tokens: Vec<_> = samples
+            .iter()
+            .filter(|(name, _, _)| name == metric::TOKEN_USAGE)
+            .map(|(_, labels, value)| (label(labels, "gen_ai.token.type").unwrap(), *value))
+            .collect();
+        assert_eq!(tokens, vec![("input", 4.0), ("output", 2.0)]);
+    }
+}
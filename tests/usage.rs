@@ -0,0 +1,269 @@
+mod common;
+
+use async_google_gemini::{
+    error::{ClaudeError, GeminiError},
+    testing::{MockReply, MockServer},
+    types::{chat::ModelId, claude::ClaudeModel, gemini::GeminiModel},
+    usage::{Budget, ModelPrice, Tags, TokenUsage, UsageRecorder},
+};
+use common::{gemini_candidate, gemini_request};
+use futures::StreamExt;
+use serde_json::json;
+
+fn usage(prompt_tokens: u64, candidate_tokens: u64) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens,
+        candidate_tokens,
+        cached_tokens: 0,
+        total_tokens: prompt_tokens + candidate_tokens,
+    }
+}
+
+fn tags(key: &str, value: &str) -> Tags {
+    Tags::from([(key.to_string(), value.to_string())])
+}
+
+fn flash() -> ModelId {
+    GeminiModel::Gemini20Flash001.into()
+}
+
+#[test]
+fn cost_uses_the_cached_input_price() {
+    let price = ModelPrice::per_million(1.0, 4.0);
+    let usage = TokenUsage {
+        prompt_tokens: 1_000_000,
+        candidate_tokens: 500_000,
+        cached_tokens: 0,
+        total_tokens: 1_500_000,
+    };
+    assert_eq!(price.cost(&usage), 3.0);
+
+    let cached = TokenUsage {
+        cached_tokens: 400_000,
+        ..usage
+    };
+    assert_eq!(price.cost(&cached), 3.0);
+    assert_eq!(price.with_cached_input(0.25).cost(&cached), 0.6 + 0.1 + 2.0);
+}
+
+#[test]
+fn records_are_priced_and_totalled_per_model_and_tag() {
+    let recorder = UsageRecorder::new().with_price(flash(), ModelPrice::per_million(1.0, 2.0));
+    let claude: ModelId = ClaudeModel::Claude3Haiku.into();
+
+    recorder.record(flash(), &tags("tenant", "a"), usage(1_000_000, 0));
+    recorder.record(flash(), &tags("tenant", "b"), usage(0, 1_000_000));
+    recorder.record(claude.clone(), &tags("tenant", "a"), usage(10, 5));
+
+    let total = recorder.total();
+    assert_eq!(total.calls, 3);
+    assert_eq!(total.usage.total_tokens, 2_000_015);
+    assert_eq!(total.cost, 3.0);
+
+    let by_model = recorder.by_model();
+    assert_eq!(by_model[&flash()].calls, 2);
+    assert_eq!(by_model[&claude].cost, 0.0);
+
+    let by_tenant = recorder.by_tag("tenant");
+    assert_eq!(by_tenant["a"].calls, 2);
+    assert_eq!(by_tenant["a"].usage.total_tokens, 1_000_015);
+    assert_eq!(by_tenant["a"].cost, 1.0);
+    assert_eq!(by_tenant["b"].cost, 2.0);
+    assert!(recorder.by_tag("feature").is_empty());
+
+    recorder.reset();
+    assert_eq!(recorder.total().calls, 0);
+    assert!(recorder.by_tag("tenant").is_empty());
+}
+
+#[test]
+fn budgets_apply_to_their_tag() {
+    let recorder = UsageRecorder::new()
+        .with_price(flash(), ModelPrice::per_million(1.0, 1.0))
+        .with_budget(Budget::for_tag("tenant", "a").with_max_tokens(100))
+        .with_budget(Budget::total().with_max_cost(1.0));
+
+    recorder.record(flash(), &tags("tenant", "a"), usage(60, 40));
+    let error = recorder.check_budget(&tags("tenant", "a")).unwrap_err();
+    assert!(error.contains("tenant=a"), "{}", error);
+    assert!(recorder.check_budget(&tags("tenant", "b")).is_ok());
+
+    recorder.record(flash(), &tags("tenant", "b"), usage(1_000_000, 0));
+    let error = recorder.check_budget(&tags("tenant", "b")).unwrap_err();
+    assert!(error.contains("all calls"), "{}", error);
+}
+
+#[test]
+fn the_ledger_keeps_the_most_recent_records() {
+    let recorder = UsageRecorder::new().with_ledger(3);
+    for prompt_tokens in 1..=5 {
+        recorder.record(flash(), &Tags::new(), usage(prompt_tokens, 0));
+    }
+
+    let recorded: Vec<u64> = recorder
+        .records()
+        .iter()
+        .map(|record| record.usage.prompt_tokens)
+        .collect();
+    assert_eq!(recorded, vec![3, 4, 5]);
+
+    // shrinking the ledger drops the oldest records
+    let recorder = recorder.with_ledger(1);
+    assert_eq!(recorder.records()[0].usage.prompt_tokens, 5);
+    recorder.record(flash(), &Tags::new(), usage(6, 0));
+    assert_eq!(recorder.records().len(), 1);
+    assert_eq!(recorder.records()[0].usage.prompt_tokens, 6);
+}
+
+#[tokio::test]
+async fn calls_over_budget_are_rejected_before_they_are_sent() {
+    let recorder =
+        UsageRecorder::new().with_budget(Budget::for_tag("tenant", "a").with_max_tokens(10));
+    recorder.record(flash(), &tags("tenant", "a"), usage(10, 0));
+
+    let server = MockServer::start().await;
+    let client = server
+        .client_builder()
+        .usage_recorder(recorder)
+        .build()
+        .unwrap();
+
+    let error = client
+        .gemini()
+        .with_tag("tenant", "a")
+        .generate_content(GeminiModel::Gemini20Flash001, gemini_request("Hello"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, GeminiError::BudgetExceeded(_)),
+        "{:?}",
+        error
+    );
+
+    let error = client
+        .claude()
+        .with_tag("tenant", "a")
+        .raw_predict(
+            ClaudeModel::Claude3Haiku,
+            serde_json::from_value(json!({
+                "anthropic_version": "vertex-2023-10-16",
+                "max_tokens": 16,
+                "system": "",
+                "stream": false,
+                "messages": [{ "role": "user", "content": "Hello" }]
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, ClaudeError::BudgetExceeded(_)),
+        "{:?}",
+        error
+    );
+
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn usage_is_recorded_once_a_gemini_stream_ends() {
+    let recorder = UsageRecorder::new();
+    let server = MockServer::start().await;
+    server.reply(MockReply::stream_generate_content(&[
+        gemini_candidate(json!({
+            "index": 0,
+            "content": { "role": "model", "parts": [{ "text": "Bon" }] }
+        })),
+        serde_json::from_value(json!({
+            "candidates": [{
+                "index": 0,
+                "content": { "role": "model", "parts": [{ "text": "jour" }] },
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": { "promptTokenCount": 4, "candidatesTokenCount": 2, "totalTokenCount": 6 }
+        }))
+        .unwrap(),
+    ]));
+    let client = server
+        .client_builder()
+        .usage_recorder(recorder.clone())
+        .build()
+        .unwrap();
+
+    let mut stream = client
+        .gemini()
+        .with_tag("feature", "greeting")
+        .stream_generate_content(GeminiModel::Gemini20Flash001, gemini_request("Hello"))
+        .await
+        .unwrap();
+    stream.next().await.unwrap().unwrap();
+    assert_eq!(recorder.total().calls, 0);
+    while let Some(chunk) = stream.next().await {
+        chunk.unwrap();
+    }
+
+    assert_eq!(recorder.total().calls, 1);
+    assert_eq!(recorder.total().usage.total_tokens, 6);
+    assert_eq!(recorder.by_tag("feature")["greeting"].calls, 1);
+}
+
+#[tokio::test]
+async fn usage_is_recorded_once_a_claude_stream_ends() {
+    let recorder = UsageRecorder::new();
+    let server = MockServer::start().await;
+    server.reply(MockReply::events([
+        json!({
+            "type": "message_start",
+            "message": {
+                "id": "msg_1",
+                "type": "message",
+                "role": "assistant",
+                "content": [],
+                "model": "claude-3-haiku-20240307",
+                "stop_reason": null,
+                "stop_sequence": null,
+                "usage": { "input_tokens": 8, "output_tokens": 1 }
+            }
+        })
+        .to_string(),
+        json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "text", "text": "" } }).to_string(),
+        json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "Hi" } }).to_string(),
+        json!({ "type": "content_block_stop", "index": 0 }).to_string(),
+        json!({
+            "type": "message_delta",
+            "delta": { "stop_reason": "end_turn", "stop_sequence": null },
+            "usage": { "output_tokens": 3 }
+        })
+        .to_string(),
+        json!({ "type": "message_stop" }).to_string(),
+    ]));
+    let client = server
+        .client_builder()
+        .usage_recorder(recorder.clone())
+        .build()
+        .unwrap();
+
+    let mut stream = client
+        .claude()
+        .stream_raw_predict(
+            ClaudeModel::Claude3Haiku,
+            serde_json::from_value(json!({
+                "anthropic_version": "vertex-2023-10-16",
+                "max_tokens": 16,
+                "system": "",
+                "stream": true,
+                "messages": [{ "role": "user", "content": "Hello" }]
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+    while let Some(event) = stream.next().await {
+        event.unwrap();
+    }
+
+    let total = recorder.total();
+    assert_eq!(total.calls, 1);
+    assert_eq!(total.usage.prompt_tokens, 8);
+    assert_eq!(total.usage.candidate_tokens, 3);
+}